@@ -0,0 +1,345 @@
+// runs a rom uncapped and without a frontend. the rom is run once decoding every instruction as it
+// runs, once with the decoded instruction cache and once with the block backend to compare their
+// throughput, then once more timing every instruction on its own to see where the time goes.
+// the results can be printed as json to keep track of them over time
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    io::Error,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::{blocks::Backend, cpu::Cpu, keyboard::KeyBoard, loader, opcode::Op, quirks::Quirks};
+
+// per instruction timings are kept with 1ns resolution up to this, anything slower is counted in
+// the last bucket
+const MAX_NANOS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BenchOptions {
+    pub instructions: u64,
+    // instructions per frame, the timers tick once per frame
+    pub tickrate: u32,
+    pub quirks: Quirks,
+    pub format: Format,
+}
+
+pub struct BenchResult {
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+// how long each instruction of one class took
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    sum_nanos: u64,
+    max_nanos: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; MAX_NANOS + 1],
+            total: 0,
+            sum_nanos: 0,
+            max_nanos: 0,
+        }
+    }
+
+    fn add(&mut self, nanos: u64) {
+        self.counts[(nanos as usize).min(MAX_NANOS)] += 1;
+        self.total += 1;
+        self.sum_nanos += nanos;
+        self.max_nanos = self.max_nanos.max(nanos);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.sum_nanos += other.sum_nanos;
+        self.max_nanos = self.max_nanos.max(other.max_nanos);
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum_nanos as f64 / self.total.max(1) as f64
+    }
+
+    // the smallest duration that p percent of the instructions didn't exceed
+    fn percentile(&self, p: f64) -> u64 {
+        let target = ((p / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (nanos, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return nanos as u64;
+            }
+        }
+        self.max_nanos
+    }
+
+    // counts in power of two buckets: 0-1ns, 2-3ns, 4-7ns, ...
+    fn buckets(&self) -> Vec<(u64, u64, u64)> {
+        let mut buckets: Vec<(u64, u64, u64)> = Vec::new();
+        for (nanos, &count) in self.counts.iter().enumerate() {
+            let low = if nanos < 2 {
+                0
+            } else {
+                1 << (usize::BITS - 1 - nanos.leading_zeros())
+            };
+            match buckets.last_mut() {
+                Some((start, _, total)) if *start == low => *total += count,
+                _ => buckets.push((low, (low * 2).max(2) - 1, count)),
+            }
+        }
+        while buckets.last().is_some_and(|&(_, _, count)| count == 0) {
+            buckets.pop();
+        }
+        buckets
+    }
+}
+
+pub fn run(src: &str, options: BenchOptions) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    let runs = [
+        ("uncached", false, Backend::Interpreter),
+        ("cached", true, Backend::Interpreter),
+        ("blocks", true, Backend::Blocks),
+    ]
+    .iter()
+    .map(|&(name, cache, backend)| {
+        bench(&program, &options, cache, backend).map(|result| (name, result))
+    })
+    .collect::<Result<Vec<(&str, BenchResult)>, Error>>()?;
+    let overhead = timer_overhead();
+    let classes = time_instructions(&program, &options, overhead)?;
+
+    match options.format {
+        Format::Text => print_text(&options, &runs, &classes, overhead),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&to_json(src, &options, &runs, &classes, overhead))
+                .map_err(Error::other)?
+        ),
+    }
+    Ok(())
+}
+
+fn new_cpu(program: &[u8], options: &BenchOptions) -> Result<Cpu, Error> {
+    let mut cpu = Cpu::init(false);
+    cpu.quirks = options.quirks;
+    // same seed for every run so they execute the same instructions
+    cpu.seed(0);
+    cpu.add_program(program)?;
+    Ok(cpu)
+}
+
+fn stopped(executed: u64, e: impl std::fmt::Display) -> Error {
+    Error::other(format!(
+        "the rom stopped after {} instructions: {}",
+        executed, e
+    ))
+}
+
+pub fn bench(
+    program: &[u8],
+    options: &BenchOptions,
+    cache: bool,
+    backend: Backend,
+) -> Result<BenchResult, Error> {
+    let mut cpu = new_cpu(program, options)?;
+    cpu.set_instruction_cache(cache);
+    cpu.set_block_backend(backend == Backend::Blocks);
+    let keyboard = KeyBoard::new();
+    let tickrate = options.tickrate as u64;
+
+    let start = Instant::now();
+    let mut executed = 0;
+    while executed < options.instructions {
+        // blocks never run past a timer tick or the end of the run
+        let until_tick = tickrate - executed % tickrate;
+        let max_steps = (options.instructions - executed).min(until_tick) as u32;
+        match cpu.step_block(&keyboard, max_steps) {
+            Ok((steps, _)) => executed += steps as u64,
+            Err(e) => return Err(stopped(executed, e)),
+        }
+        if executed % tickrate == 0 {
+            cpu.tick_timers();
+        }
+    }
+    Ok(BenchResult {
+        instructions: executed,
+        elapsed: start.elapsed(),
+    })
+}
+
+// what timing nothing at all takes, taken off every instruction's time
+fn timer_overhead() -> u64 {
+    let mut histogram = Histogram::new();
+    for _ in 0..100_000 {
+        let start = Instant::now();
+        histogram.add(black_box(start).elapsed().as_nanos() as u64);
+    }
+    histogram.percentile(50.0)
+}
+
+// times every instruction with the cached interpreter, grouped by instruction class
+fn time_instructions(
+    program: &[u8],
+    options: &BenchOptions,
+    overhead: u64,
+) -> Result<BTreeMap<&'static str, Histogram>, Error> {
+    let mut cpu = new_cpu(program, options)?;
+    let keyboard = KeyBoard::new();
+    let mut classes = BTreeMap::new();
+
+    for executed in 0..options.instructions {
+        let class = match cpu.instruction_at(cpu.pc) {
+            Some(instruction) => Op::decode(instruction).class(),
+            None => "invalid",
+        };
+        let start = Instant::now();
+        let res = cpu.step(&keyboard);
+        let nanos = start.elapsed().as_nanos() as u64;
+        if let Err(e) = res {
+            return Err(stopped(executed, e));
+        }
+        classes
+            .entry(class)
+            .or_insert_with(Histogram::new)
+            .add(nanos.saturating_sub(overhead));
+        if (executed + 1) % options.tickrate as u64 == 0 {
+            cpu.tick_timers();
+        }
+    }
+    Ok(classes)
+}
+
+fn total(classes: &BTreeMap<&'static str, Histogram>) -> Histogram {
+    let mut total = Histogram::new();
+    for histogram in classes.values() {
+        total.merge(histogram);
+    }
+    total
+}
+
+fn print_text(
+    options: &BenchOptions,
+    runs: &[(&str, BenchResult)],
+    classes: &BTreeMap<&'static str, Histogram>,
+    overhead: u64,
+) {
+    let baseline = runs[0].1.per_second();
+    for (name, result) in runs {
+        println!(
+            "{:<9} {} instructions in {:.3}s, {:.0} instructions/s, {:.0} frames/s, {:.2}x",
+            format!("{}:", name),
+            result.instructions,
+            result.elapsed.as_secs_f64(),
+            result.per_second(),
+            result.per_second() / options.tickrate as f64,
+            result.per_second() / baseline
+        );
+    }
+
+    let total = total(classes);
+    println!(
+        "\ntime per instruction with the cached interpreter ({}ns of timer overhead removed):",
+        overhead
+    );
+    println!(
+        "{:<8}{:>12}{:>8}{:>10}{:>8}{:>8}{:>8}{:>12}",
+        "class", "count", "time", "mean", "p50", "p90", "p99", "max"
+    );
+    let mut by_time = classes.iter().collect::<Vec<(&&str, &Histogram)>>();
+    by_time.sort_by_key(|(_, histogram)| std::cmp::Reverse(histogram.sum_nanos));
+    for (class, histogram) in by_time.into_iter().chain([(&"all", &total)]) {
+        println!(
+            "{:<8}{:>12}{:>7.1}%{:>10}{:>8}{:>8}{:>8}{:>12}",
+            class,
+            histogram.total,
+            histogram.sum_nanos as f64 * 100.0 / total.sum_nanos.max(1) as f64,
+            format!("{:.1}ns", histogram.mean()),
+            format!("{}ns", histogram.percentile(50.0)),
+            format!("{}ns", histogram.percentile(90.0)),
+            format!("{}ns", histogram.percentile(99.0)),
+            format!("{}ns", histogram.max_nanos)
+        );
+    }
+
+    println!("\nhistogram:");
+    for (low, high, count) in total.buckets() {
+        let share = count as f64 / total.total.max(1) as f64;
+        let range = if low as usize >= MAX_NANOS {
+            format!("{}ns+", low)
+        } else {
+            format!("{}-{}ns", low, high)
+        };
+        println!(
+            "{:>12} {:<40} {:.2}%",
+            range,
+            "#".repeat((share * 40.0).round() as usize),
+            share * 100.0
+        );
+    }
+}
+
+fn to_json(
+    src: &str,
+    options: &BenchOptions,
+    runs: &[(&str, BenchResult)],
+    classes: &BTreeMap<&'static str, Histogram>,
+    overhead: u64,
+) -> Value {
+    let stats = |histogram: &Histogram| {
+        json!({
+            "count": histogram.total,
+            "total_ns": histogram.sum_nanos,
+            "mean_ns": histogram.mean(),
+            "p50_ns": histogram.percentile(50.0),
+            "p90_ns": histogram.percentile(90.0),
+            "p99_ns": histogram.percentile(99.0),
+            "max_ns": histogram.max_nanos,
+        })
+    };
+    let total = total(classes);
+    json!({
+        "rom": src,
+        "instructions": options.instructions,
+        "tickrate": options.tickrate,
+        "runs": runs.iter().map(|(name, result)| json!({
+            "name": name,
+            "instructions": result.instructions,
+            "seconds": result.elapsed.as_secs_f64(),
+            "instructions_per_second": result.per_second(),
+            "frames_per_second": result.per_second() / options.tickrate as f64,
+        })).collect::<Vec<Value>>(),
+        "timer_overhead_ns": overhead,
+        "classes": classes
+            .iter()
+            .map(|(class, histogram)| (class.to_string(), stats(histogram)))
+            .collect::<serde_json::Map<String, Value>>(),
+        "all": stats(&total),
+        "histogram": total.buckets().iter().map(|&(low, high, count)| json!({
+            "from_ns": low,
+            "to_ns": high,
+            "count": count,
+        })).collect::<Vec<Value>>(),
+    })
+}
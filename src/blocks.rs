@@ -0,0 +1,65 @@
+// the basic block backend. runs of instructions that can't change the flow of the program, touch
+// the screen, the keyboard or memory are decoded once into a block and then executed back to back
+// without going through fetch and the debug logging in Cpu::step. everything else still goes
+// through Cpu::step. meant for running roms headless as fast as possible
+use crate::opcode::Op;
+
+// longest run of instructions compiled into one block
+pub const MAX_BLOCK_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    // every instruction goes through Cpu::step
+    Interpreter,
+    Blocks,
+}
+
+impl Backend {
+    pub fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "interpreter" => Some(Backend::Interpreter),
+            "blocks" => Some(Backend::Blocks),
+            _ => None,
+        }
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        vec!["interpreter", "blocks"]
+    }
+}
+
+// instructions that can be part of a block. none of them can fail, jump or write memory, so a
+// block always runs to its end and can't overwrite itself
+pub fn is_straight_line(op: Op) -> bool {
+    matches!(
+        op,
+        Op::Sys(_)
+            | Op::SetImm(..)
+            | Op::AddImm(..)
+            | Op::Set(..)
+            | Op::Or(..)
+            | Op::And(..)
+            | Op::Xor(..)
+            | Op::Add(..)
+            | Op::Sub(..)
+            | Op::Shr(..)
+            | Op::SubN(..)
+            | Op::Shl(..)
+            | Op::SetI(_)
+            | Op::Rand(..)
+            | Op::GetDelay(_)
+            | Op::SetDelay(_)
+            | Op::SetSound(_)
+            | Op::AddI(_)
+    )
+}
+
+// the block starting at addr, empty if the instruction there has to go through Cpu::step
+pub fn compile(mem: &[u8], addr: usize) -> Box<[Op]> {
+    mem[addr.min(mem.len())..]
+        .chunks_exact(2)
+        .map(|w| Op::decode((w[0] as u16) << 8 | w[1] as u16))
+        .take_while(|&op| is_straight_line(op))
+        .take(MAX_BLOCK_LEN)
+        .collect()
+}
@@ -0,0 +1,212 @@
+// octo cartridges are gifs with a program and its options hidden in the pixels.
+// every pixel's palette index carries 2 bits of the payload (most significant bits first, 4 pixels
+// per byte), continuing through the frames in order. the payload starts with its length as a 4 byte
+// big endian number followed by that many bytes of json: {"program": ..., "options": {...}} with
+// the program as octo source. it's compiled by octo.rs, so cartridges of SUPER-CHIP and XO-CHIP
+// programs can't be loaded
+use std::io::{Error, ErrorKind};
+
+use minifb::Key;
+use serde_json::{Map, Value};
+
+use crate::{
+    font::Font,
+    keyboard,
+    loader::RomOptions,
+    palette::{parse_color, Palette},
+    quirks::Quirks,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    // octo source code, it has to be compiled before it can run
+    pub program: String,
+    pub options: RomOptions,
+}
+
+pub fn is_cartridge(data: &[u8]) -> bool {
+    data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+}
+
+pub fn decode(data: &[u8]) -> Result<Cartridge, Error> {
+    let payload = read_payload(data)?;
+    let json: Value = serde_json::from_slice(&payload)
+        .map_err(|e| invalid(format!("the embedded payload is not valid json: {}", e)))?;
+    let object = json
+        .as_object()
+        .ok_or_else(|| invalid(String::from("the embedded payload is not a json object")))?;
+
+    let program = match object.get("program") {
+        Some(Value::String(source)) => source.clone(),
+        _ => {
+            return Err(invalid(String::from(
+                "the payload doesn't contain the source of a program",
+            )))
+        }
+    };
+    let options = match object.get("options") {
+        Some(Value::Object(options)) => parse_options(options)?,
+        _ => RomOptions::default(),
+    };
+    Ok(Cartridge { program, options })
+}
+
+fn invalid(reason: String) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid octo cartridge: {}", reason),
+    )
+}
+
+fn read_payload(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(data)
+        .map_err(|e| invalid(format!("corrupt gif: {}", e)))?;
+
+    let mut bytes = Vec::new();
+    let mut byte = 0u8;
+    let mut bits = 0;
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|e| invalid(format!("corrupt gif: {}", e)))?
+    {
+        for index in frame.buffer.iter() {
+            byte = byte << 2 | (index & 3);
+            bits += 2;
+            if bits == 8 {
+                bytes.push(byte);
+                byte = 0;
+                bits = 0;
+            }
+        }
+    }
+
+    if bytes.len() < 4 {
+        return Err(invalid(String::from(
+            "the image is too small to hold a payload",
+        )));
+    }
+    let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    if bytes.len() - 4 < len {
+        return Err(invalid(format!(
+            "the payload says it is {} bytes but the image only holds {}",
+            len,
+            bytes.len() - 4
+        )));
+    }
+    Ok(bytes[4..4 + len].to_vec())
+}
+
+// option names are the ones octo writes
+fn parse_options(options: &Map<String, Value>) -> Result<RomOptions, Error> {
+    let flag = |name: &str| options.get(name).and_then(Value::as_bool);
+
+    let tickrate = match options.get("tickrate") {
+        Some(value) => Some(
+            value
+                .as_u64()
+                .filter(|&rate| rate > 0)
+                .and_then(|rate| u32::try_from(rate).ok())
+                .ok_or_else(|| invalid(format!("{} is not a valid tickrate", value)))?,
+        ),
+        None => None,
+    };
+
+    // octo's quirk flags are all off by default, which matches the default preset
+    let quirk_names = [
+        "shiftQuirks",
+        "loadStoreQuirks",
+        "logicQuirks",
+        "jumpQuirks",
+        "clipQuirks",
+    ];
+    let quirks = if quirk_names.iter().any(|name| flag(name).is_some()) {
+        let mut quirks = Quirks::default();
+        if let Some(on) = flag("shiftQuirks") {
+            quirks.shift_in_place = on;
+        }
+        if let Some(on) = flag("loadStoreQuirks") {
+            quirks.load_store_increments_i = !on;
+        }
+        if let Some(on) = flag("logicQuirks") {
+            quirks.logic_resets_vf = on;
+        }
+        if let Some(on) = flag("jumpQuirks") {
+            quirks.jump_uses_vx = on;
+        }
+        if let Some(on) = flag("clipQuirks") {
+            quirks.clip_sprites = on;
+        }
+        Some(quirks)
+    } else {
+        None
+    };
+
+    let color = |name: &str| -> Result<Option<u32>, Error> {
+        match options.get(name).and_then(Value::as_str) {
+            Some(color) => parse_color(color)
+                .map(Some)
+                .map_err(|e| invalid(format!("{}: {}", name, e))),
+            None => Ok(None),
+        }
+    };
+    let background = color("backgroundColor")?;
+    let foreground = color("fillColor")?;
+    let palette = match (background, foreground) {
+        (None, None) => None,
+        (background, foreground) => {
+            let default = Palette::default();
+            Some(Palette {
+                background: background.unwrap_or(default.background),
+                foreground: foreground.unwrap_or(default.foreground),
+            })
+        }
+    };
+
+    // octo's other font styles (dream6800, eti660 and fish) aren't built in
+    let font = options
+        .get("fontStyle")
+        .and_then(Value::as_str)
+        .and_then(Font::preset);
+
+    let keymap = match options.get("keys") {
+        Some(Value::Object(keys)) => Some(parse_keymap(keys)?),
+        _ => None,
+    };
+
+    Ok(RomOptions {
+        tickrate,
+        quirks,
+        palette,
+        keymap,
+        font,
+    })
+}
+
+// {"0": ["x"], "1": ["1", 49], ...}: chip8 key as a hex digit -> keyboard keys
+fn parse_keymap(keys: &Map<String, Value>) -> Result<Vec<(Key, u8)>, Error> {
+    let mut keymap = Vec::new();
+    for (digit, bound) in keys {
+        let value = u8::from_str_radix(digit, 16)
+            .ok()
+            .filter(|&v| v <= 0xf)
+            .ok_or_else(|| invalid(format!("\"{}\" is not a chip8 key", digit)))?;
+        let names = match bound {
+            Value::Array(names) => names.clone(),
+            name => vec![name.clone()],
+        };
+        for name in names {
+            let name = match name {
+                Value::String(name) => name,
+                other => other.to_string(),
+            };
+            let key = keyboard::parse_key(&name).ok_or_else(|| {
+                invalid(format!("unknown key \"{}\" for chip8 key {}", name, digit))
+            })?;
+            keymap.push((key, value));
+        }
+    }
+    Ok(keymap)
+}
@@ -0,0 +1,434 @@
+// a static control flow graph of a rom, for finding your way around roms without source. starting
+// at the load address every jump, call, return and skip is followed to split the reachable code
+// into basic blocks, and the blocks reachable from each 2NNN target without going through another
+// call make up its subroutine. BNNN jumps to an address computed at runtime, so they are flagged
+// as indirect and not followed: code only reachable through them won't show up
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Error,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    font::{BIG_GLYPH_SIZE, SMALL_FONT_SIZE, SMALL_GLYPH_SIZE},
+    loader,
+    memory_map::MemoryMap,
+    opcode::Op,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dot,
+    Json,
+}
+
+// how control leaves a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    // the next instruction starts another block
+    Next,
+    Jump(usize),
+    // continues after the call once the subroutine returns
+    Call(usize),
+    // 3XNN, 4XNN, 5XY0, 9XY0, EX9E and EXA1
+    Skip,
+    Return,
+    // BNNN, V0 (or VX with the jump quirk) plus NNN
+    Indirect(usize),
+    // stops the interpreter
+    Invalid,
+    // runs past the last byte of the rom
+    End,
+}
+
+impl Exit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exit::Next => "next",
+            Exit::Jump(_) => "jump",
+            Exit::Call(_) => "call",
+            Exit::Skip => "skip",
+            Exit::Return => "return",
+            Exit::Indirect(_) => "indirect",
+            Exit::Invalid => "invalid",
+            Exit::End => "end",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    // (address, instruction)
+    pub instructions: Vec<(usize, u16)>,
+    pub exit: Exit,
+    // addresses execution can continue at within the same subroutine, may be outside the rom
+    pub successors: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: usize,
+    // start addresses of the blocks, a block can belong to more than one subroutine
+    pub blocks: Vec<usize>,
+    pub calls: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    // where the rom is loaded, the main program is the subroutine starting here
+    pub start: usize,
+    pub end: usize,
+    pub blocks: BTreeMap<usize, Block>,
+    pub subroutines: BTreeMap<usize, Subroutine>,
+}
+
+// what is known about the registers at some point in a block
+#[derive(Debug, Clone, Copy)]
+pub struct Known {
+    pub v: [Option<u8>; 16],
+    pub i: Option<usize>,
+}
+
+impl Known {
+    pub fn new() -> Known {
+        Known {
+            v: [None; 16],
+            i: None,
+        }
+    }
+
+    // what op does to the registers
+    pub fn apply(&mut self, op: Op, memory_map: &MemoryMap) {
+        match op {
+            Op::SetImm(x, nn) => self.v[x as usize] = Some(nn),
+            Op::AddImm(x, nn) => {
+                self.v[x as usize] = self.v[x as usize].map(|v| v.wrapping_add(nn))
+            }
+            Op::Set(x, y) => self.v[x as usize] = self.v[y as usize],
+            Op::Or(x, _)
+            | Op::And(x, _)
+            | Op::Xor(x, _)
+            | Op::Add(x, _)
+            | Op::Sub(x, _)
+            | Op::Shr(x, _)
+            | Op::SubN(x, _)
+            | Op::Shl(x, _) => {
+                self.v[x as usize] = None;
+                self.v[0xf] = None;
+            }
+            Op::Rand(x, _) | Op::GetDelay(x) | Op::WaitKey(x) => self.v[x as usize] = None,
+            Op::Draw(..) => self.v[0xf] = None,
+            Op::SetI(nnn) => self.i = Some(nnn as usize),
+            Op::AddI(x) => self.i = self.i.zip(self.v[x as usize]).map(|(i, v)| i + v as usize),
+            Op::Font(x) => {
+                self.i = self.v[x as usize]
+                    .map(|digit| memory_map.font_address + digit as usize * SMALL_GLYPH_SIZE)
+            }
+            Op::BigFont(x) => {
+                self.i = self.v[x as usize].map(|digit| {
+                    memory_map.font_address + SMALL_FONT_SIZE + digit as usize * BIG_GLYPH_SIZE
+                })
+            }
+            // whether I moves depends on the quirks
+            Op::Store(_) | Op::Bcd(_) => self.i = None,
+            Op::Load(x) => {
+                self.i = None;
+                for v in &mut self.v[..=x as usize] {
+                    *v = None;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn instruction(program: &[u8], start: usize, addr: usize) -> Option<u16> {
+    let offset = addr.checked_sub(start)?;
+    let bytes = program.get(offset..offset + 2)?;
+    Some((bytes[0] as u16) << 8 | bytes[1] as u16)
+}
+
+// where an instruction can go next, None if it falls through to the next one
+fn targets(op: Op, addr: usize) -> Option<Vec<usize>> {
+    match op {
+        Op::Jump(nnn) => Some(vec![nnn as usize]),
+        // the return address, the subroutine itself is analysed on its own
+        Op::Call(_) => Some(vec![addr + 2]),
+        Op::SkipEqImm(..)
+        | Op::SkipNeImm(..)
+        | Op::SkipEqReg(..)
+        | Op::SkipNeReg(..)
+        | Op::SkipKey(_)
+        | Op::SkipNotKey(_) => Some(vec![addr + 2, addr + 4]),
+        Op::Ret | Op::JumpOffset(..) | Op::Invalid(_) => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+impl Cfg {
+    pub fn build(program: &[u8], start: usize) -> Cfg {
+        let end = start + program.len();
+        let in_rom = |addr: usize| instruction(program, start, addr).is_some();
+
+        // find the reachable instructions and where blocks have to start
+        let mut leaders = BTreeSet::from([start]);
+        let mut entries = BTreeSet::from([start]);
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![start];
+        while let Some(addr) = pending.pop() {
+            let Some(instruction) = instruction(program, start, addr) else {
+                continue;
+            };
+            if !seen.insert(addr) {
+                continue;
+            }
+            let op = Op::decode(instruction);
+            if let Op::Call(nnn) = op {
+                entries.insert(nnn as usize);
+                leaders.insert(nnn as usize);
+                pending.push(nnn as usize);
+            }
+            match targets(op, addr) {
+                Some(targets) => {
+                    for target in targets {
+                        leaders.insert(target);
+                        pending.push(target);
+                    }
+                }
+                None => pending.push(addr + 2),
+            }
+        }
+
+        let blocks = leaders
+            .iter()
+            .filter(|&&leader| in_rom(leader))
+            .map(|&leader| {
+                let mut block = Block {
+                    start: leader,
+                    instructions: Vec::new(),
+                    exit: Exit::End,
+                    successors: Vec::new(),
+                };
+                let mut addr = leader;
+                while let Some(instruction) = instruction(program, start, addr) {
+                    block.instructions.push((addr, instruction));
+                    let op = Op::decode(instruction);
+                    if let Some(targets) = targets(op, addr) {
+                        block.exit = match op {
+                            Op::Jump(nnn) => Exit::Jump(nnn as usize),
+                            Op::Call(nnn) => Exit::Call(nnn as usize),
+                            Op::Ret => Exit::Return,
+                            Op::JumpOffset(_, nnn) => Exit::Indirect(nnn as usize),
+                            Op::Invalid(_) => Exit::Invalid,
+                            _ => Exit::Skip,
+                        };
+                        block.successors = targets;
+                        break;
+                    }
+                    addr += 2;
+                    if leaders.contains(&addr) {
+                        block.exit = Exit::Next;
+                        block.successors = vec![addr];
+                        break;
+                    }
+                }
+                (leader, block)
+            })
+            .collect::<BTreeMap<usize, Block>>();
+
+        let subroutines = entries
+            .iter()
+            .filter(|&&entry| in_rom(entry))
+            .map(|&entry| {
+                let mut members = BTreeSet::new();
+                let mut calls = BTreeSet::new();
+                let mut pending = vec![entry];
+                while let Some(addr) = pending.pop() {
+                    let Some(block) = blocks.get(&addr) else {
+                        continue;
+                    };
+                    if !members.insert(addr) {
+                        continue;
+                    }
+                    if let Exit::Call(target) = block.exit {
+                        calls.insert(target);
+                    }
+                    pending.extend(&block.successors);
+                }
+                (
+                    entry,
+                    Subroutine {
+                        entry,
+                        blocks: members.into_iter().collect(),
+                        calls: calls.into_iter().collect(),
+                    },
+                )
+            })
+            .collect();
+
+        Cfg {
+            start,
+            end,
+            blocks,
+            subroutines,
+        }
+    }
+
+    // (caller, callee) for every subroutine call
+    pub fn call_graph(&self) -> Vec<(usize, usize)> {
+        self.subroutines
+            .values()
+            .flat_map(|s| s.calls.iter().map(|&callee| (s.entry, callee)))
+            .collect()
+    }
+
+    // every byte of a reachable instruction
+    pub fn code_bytes(&self) -> BTreeSet<usize> {
+        self.blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .flat_map(|&(addr, _)| [addr, addr + 1])
+            .collect()
+    }
+
+    // blocks ending in a BNNN jump
+    pub fn indirect_jumps(&self) -> Vec<&Block> {
+        self.blocks
+            .values()
+            .filter(|block| matches!(block.exit, Exit::Indirect(_)))
+            .collect()
+    }
+
+    fn name(&self, addr: usize) -> String {
+        if addr == self.start {
+            String::from("main")
+        } else {
+            format!("sub_{:03x}", addr)
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n");
+        out.push_str("  node [shape=box fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if self.subroutines.contains_key(&block.start) {
+                label.push_str(&format!("{}:\\l", self.name(block.start)));
+            }
+            for &(addr, instruction) in &block.instructions {
+                label.push_str(&format!(
+                    "{:#05x}  {:04X}  {}\\l",
+                    addr,
+                    instruction,
+                    Op::decode(instruction).class()
+                ));
+            }
+            let style = match block.exit {
+                Exit::Indirect(_) => " color=red",
+                Exit::Invalid => " color=orange",
+                _ => "",
+            };
+            out.push_str(&format!(
+                "  \"{:#05x}\" [label=\"{}\"{}];\n",
+                block.start, label, style
+            ));
+        }
+
+        let mut outside = BTreeSet::new();
+        for block in self.blocks.values() {
+            for (n, &to) in block.successors.iter().enumerate() {
+                let label = match block.exit {
+                    Exit::Skip if n == 0 => " [label=\"no skip\"]",
+                    Exit::Skip => " [label=\"skip\"]",
+                    Exit::Call(_) => " [label=\"return\" style=dotted]",
+                    _ => "",
+                };
+                out.push_str(&format!(
+                    "  \"{:#05x}\" -> \"{:#05x}\"{};\n",
+                    block.start, to, label
+                ));
+                if !self.blocks.contains_key(&to) {
+                    outside.insert(to);
+                }
+            }
+            match block.exit {
+                Exit::Call(target) => {
+                    out.push_str(&format!(
+                        "  \"{:#05x}\" -> \"{:#05x}\" [label=\"call\" style=dashed color=blue];\n",
+                        block.start, target
+                    ));
+                    if !self.blocks.contains_key(&target) {
+                        outside.insert(target);
+                    }
+                }
+                Exit::Indirect(base) => out.push_str(&format!(
+                    "  \"{:#05x}_indirect\" [label=\"indirect jump to V0 + {:#05x}\" shape=plaintext fontcolor=red];\n  \"{:#05x}\" -> \"{:#05x}_indirect\" [style=dashed color=red];\n",
+                    block.start, base, block.start, block.start
+                )),
+                _ => {}
+            }
+        }
+        for addr in outside {
+            out.push_str(&format!(
+                "  \"{:#05x}\" [label=\"{:#05x}\\noutside the rom\" shape=ellipse style=dashed];\n",
+                addr, addr
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let hex = |addr: usize| format!("{:#05x}", addr);
+        json!({
+            "start": hex(self.start),
+            "end": hex(self.end),
+            "blocks": self.blocks.values().map(|block| {
+                let target = match block.exit {
+                    Exit::Jump(addr) | Exit::Call(addr) | Exit::Indirect(addr) => Some(hex(addr)),
+                    _ => None,
+                };
+                json!({
+                    "start": hex(block.start),
+                    "instructions": block.instructions.iter().map(|&(addr, instruction)| json!({
+                        "address": hex(addr),
+                        "instruction": format!("{:04X}", instruction),
+                        "class": Op::decode(instruction).class(),
+                    })).collect::<Vec<Value>>(),
+                    "exit": block.exit.name(),
+                    "target": target,
+                    "indirect": matches!(block.exit, Exit::Indirect(_)),
+                    "successors": block.successors.iter().map(|&addr| hex(addr)).collect::<Vec<String>>(),
+                })
+            }).collect::<Vec<Value>>(),
+            "subroutines": self.subroutines.values().map(|subroutine| json!({
+                "name": self.name(subroutine.entry),
+                "entry": hex(subroutine.entry),
+                "blocks": subroutine.blocks.iter().map(|&addr| hex(addr)).collect::<Vec<String>>(),
+                "calls": subroutine.calls.iter().map(|&addr| hex(addr)).collect::<Vec<String>>(),
+            })).collect::<Vec<Value>>(),
+            "call_graph": self.call_graph().iter().map(|&(caller, callee)| json!({
+                "caller": hex(caller),
+                "callee": hex(callee),
+            })).collect::<Vec<Value>>(),
+            "indirect_jumps": self.indirect_jumps().iter().map(|block| {
+                hex(block.instructions.last().map_or(block.start, |&(addr, _)| addr))
+            }).collect::<Vec<String>>(),
+        })
+    }
+}
+
+pub fn run(src: &str, load_address: usize, format: Format) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    let cfg = Cfg::build(&program, load_address);
+    match format {
+        Format::Dot => print!("{}", cfg.to_dot()),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&cfg.to_json()).map_err(Error::other)?
+        ),
+    }
+    Ok(())
+}
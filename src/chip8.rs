@@ -1,145 +1,444 @@
 use std::{
-    process::exit,
-    thread::sleep,
-    time::{Duration, Instant},
+    path::{Path, PathBuf},
+    sync::mpsc::{RecvTimeoutError, TryRecvError},
 };
 
-use minifb::{Window, WindowOptions};
-
 use crate::{
+    blocks::Backend,
+    coverage::Coverage,
     cpu::{self, HEIGHT, WIDTH},
+    display::Display,
     ext::ToARGB,
+    font::Font,
+    frontend::{Frontend, FrontendKind, HeadlessFrontend, WindowFrontend},
     keyboard,
+    machine::{self, Input, Machine, Output, Snapshot, FRAME_TIME},
+    memory_map::MemoryMap,
+    palette::Palette,
+    profiler::Profiler,
+    quirks::Quirks,
+    recording::Recorder,
+    screenshot,
+    terminal::{Glyphs, TerminalFrontend},
 };
 
 const SCALE: usize = 8;
 
-pub struct Chip8 {
-    cpu: cpu::Cpu,
-    io: IO,
-    timing: bool,
-    timing_data: Timing,
-    scaled_buffer: Vec<u32>,
+pub struct Config {
+    pub debug: bool,
+    pub palette: Palette,
+    // write a screenshot to the path once the given frame has been emulated
+    pub screenshot_at: Option<(u64, PathBuf)>,
+    // save screenshots at 64x32 instead of the window resolution
+    pub screenshot_native: bool,
+    // start recording to this path (.gif or .y4m) from the first frame
+    pub record_video: Option<PathBuf>,
+    pub frontend: FrontendKind,
+    // only used by the terminal frontend
+    pub glyphs: Glyphs,
+    // seed for the random number generator, makes runs reproducible
+    pub seed: Option<u64>,
+    // stop after this many frames
+    pub max_frames: Option<u64>,
+    pub quirks: Quirks,
+    // instructions executed per frame
+    pub tickrate: u32,
+    pub keymap: Vec<(minifb::Key, u8)>,
+    // shown in the window title
+    pub title: Option<String>,
+    pub memory_map: MemoryMap,
+    pub font: Font,
+    pub backend: Backend,
+    // print a profile when the rom stops, and write its folded stacks to the path if there is one
+    pub profile: bool,
+    pub profile_folded: Option<PathBuf>,
+    // write a coverage report of the rom to the path when it stops
+    pub coverage: Option<PathBuf>,
 }
 
-struct Timing {
-    avg: f64,
-    instructions: u32,
-    last_time: Instant,
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            debug: false,
+            palette: Palette::default(),
+            screenshot_at: None,
+            screenshot_native: false,
+            record_video: None,
+            frontend: FrontendKind::Window,
+            glyphs: Glyphs::HalfBlock,
+            seed: None,
+            max_frames: None,
+            quirks: Quirks::default(),
+            tickrate: 1,
+            keymap: keyboard::DEFAULT_KEYMAP.to_vec(),
+            title: None,
+            memory_map: MemoryMap::default(),
+            font: Font::default(),
+            backend: Backend::Interpreter,
+            profile: false,
+            profile_folded: None,
+            coverage: None,
+        }
+    }
 }
 
-pub struct IO {
-    window: Window,
-    keyboard: keyboard::KeyBoard,
+pub struct Chip8 {
+    machine: Machine,
+    screen: Screen,
+    max_frames: Option<u64>,
+}
+
+// everything on the window side: the frontend and what is done with finished frames
+struct Screen {
+    frontend: Box<dyn Frontend>,
+    // the display of the last frame
+    display: Display,
+    scaled_buffer: Vec<u32>,
+    palette: Palette,
+    // number of the last frame
+    frame: u64,
+    screenshot_at: Option<(u64, PathBuf)>,
+    screenshot_native: bool,
+    recorder: Option<(Recorder, PathBuf)>,
+    running: bool,
 }
 
 impl Chip8 {
-    pub fn new(debug: bool, timing: bool) -> Chip8 {
-        let window = Window::new("CHIP8", WIDTH * 8, HEIGHT * 8, WindowOptions::default())
-            .expect("failed to create a window");
-        Chip8 {
-            cpu: cpu::Cpu::init(debug),
-            io: IO {
-                window,
-                keyboard: keyboard::KeyBoard::new(),
-            },
-            timing,
-            timing_data: Timing {
-                avg: 0f64,
-                instructions: 0,
-                last_time: Instant::now(),
+    pub fn new(config: Config) -> Result<Chip8, std::io::Error> {
+        let frontend: Box<dyn Frontend> = match config.frontend {
+            FrontendKind::Window => Box::new(WindowFrontend::new(SCALE, config.title.as_deref())),
+            FrontendKind::Terminal => {
+                Box::new(TerminalFrontend::new(config.glyphs, config.palette)?)
+            }
+            FrontendKind::Headless => Box::new(HeadlessFrontend::new(Vec::new())),
+        };
+        Chip8::with_frontend(config, frontend)
+    }
+
+    pub fn with_frontend(
+        config: Config,
+        frontend: Box<dyn Frontend>,
+    ) -> Result<Chip8, std::io::Error> {
+        config
+            .memory_map
+            .validate()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let mut cpu = cpu::Cpu::with_memory_map(config.debug, config.memory_map);
+        cpu.load_font(&config.font);
+        if let Some(seed) = config.seed {
+            cpu.seed(seed);
+        }
+        cpu.quirks = config.quirks;
+        cpu.set_block_backend(config.backend == Backend::Blocks);
+        let mut keyboard = keyboard::KeyBoard::with_keymap(config.keymap);
+        keyboard.echo = config.frontend != FrontendKind::Terminal;
+        let mut machine = Machine::new(cpu, keyboard, config.tickrate);
+        if config.profile || config.profile_folded.is_some() {
+            machine.profiler = Some(Profiler::new());
+            machine.profile_folded = config.profile_folded;
+        }
+        machine.coverage_path = config.coverage;
+        let mut chip8 = Chip8 {
+            machine,
+            screen: Screen {
+                frontend,
+                display: Display::new(),
+                scaled_buffer: vec![0u32; WIDTH * HEIGHT * SCALE * SCALE],
+                palette: config.palette,
+                frame: 0,
+                screenshot_at: config.screenshot_at,
+                screenshot_native: config.screenshot_native,
+                recorder: None,
+                running: true,
             },
-            scaled_buffer: vec![0u32; WIDTH * HEIGHT * 64],
+            max_frames: config.max_frames,
+        };
+        if let Some(path) = config.record_video {
+            chip8.screen.start_recording(path)?;
         }
+        Ok(chip8)
     }
 
-    pub fn run(&mut self) {
-        loop {
-            let res = self.cpu.step(&self.io.keyboard);
+    // frontends that show frames as they happen get the machine on its own thread, the others
+    // run frame by frame on this one as fast as possible
+    pub fn run(mut self) {
+        if !self.screen.frontend.realtime() {
+            while self.run_frame() {}
+            self.screen.stop_recording();
+            self.machine.finish();
+            return;
+        }
 
-            if self.timing {
-                let now = Instant::now();
-                let elapsed = now.duration_since(self.timing_data.last_time).as_micros() as f64;
-                self.timing_data.last_time = now;
-                println!("time: {} microsecs", elapsed);
-                self.timing_data.instructions += 1;
-                self.timing_data.avg =
-                    (self.timing_data.avg * (self.timing_data.instructions - 1) as f64 + elapsed)
-                        / (self.timing_data.instructions as f64);
+        let Chip8 {
+            mut machine,
+            mut screen,
+            max_frames,
+        } = self;
+        machine.every_frame = screen.needs_every_frame();
+        let mut every_frame = machine.every_frame;
+        let (inputs, outputs, handle) = machine::spawn(machine, max_frames);
+        'ui: while screen.running && screen.frontend.is_open() {
+            let mut sent = screen.check_keypresses();
+            if screen.needs_every_frame() != every_frame {
+                every_frame = !every_frame;
+                sent.push(Input::EveryFrame(every_frame));
             }
-            self.reset_keys();
-            match res {
-                Ok(did_draw) => {
-                    self.check_keypresses();
-                    if did_draw {
-                        self.update_window();
+            for input in sent {
+                if inputs.send(input).is_err() {
+                    break 'ui;
+                }
+            }
+            // wait up to a frame for the machine, then take whatever else it finished meanwhile
+            let mut drew = false;
+            let mut output = outputs.recv_timeout(FRAME_TIME).map_err(|e| match e {
+                RecvTimeoutError::Timeout => TryRecvError::Empty,
+                RecvTimeoutError::Disconnected => TryRecvError::Disconnected,
+            });
+            loop {
+                match output {
+                    Ok(Output::Frame(snapshot)) => {
+                        screen.present(&snapshot);
+                        drew |= snapshot.dirty != 0;
+                    }
+                    Ok(Output::Stopped) | Err(TryRecvError::Disconnected) => {
+                        screen.show(drew);
+                        break 'ui;
                     }
-                    sleep(Duration::from_millis(1000 / 60));
+                    Err(TryRecvError::Empty) => break,
+                }
+                output = outputs.try_recv();
+            }
+            screen.show(drew);
+        }
+        // the machine may be waiting for room in the queue
+        drop(inputs);
+        drop(outputs);
+        let _ = handle.join();
+        screen.stop_recording();
+    }
+
+    // emulates a single frame, returns false once the program can't or shouldn't continue
+    pub fn run_frame(&mut self) -> bool {
+        if !self.screen.running || !self.screen.frontend.is_open() {
+            return false;
+        }
+        if self.max_frames.is_some_and(|max| self.machine.frame >= max) {
+            return false;
+        }
+
+        match self.machine.run_frame() {
+            Ok(()) => {
+                for input in self.screen.check_keypresses() {
+                    self.machine.apply(input);
                 }
-                Err(e) => {
-                    eprintln!("{}", e.to_string());
-                    return;
+                let snapshot = self.machine.snapshot();
+                self.screen.present(&snapshot);
+                if snapshot.dirty != 0 {
+                    self.screen.show(true);
                 }
+                true
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                false
             }
         }
     }
 
+    #[cfg(test)]
+    pub fn cpu(&self) -> &cpu::Cpu {
+        &self.machine.cpu
+    }
+
+    #[cfg(test)]
     pub fn add_program(&mut self, program: &[u8]) -> Result<(), std::io::Error> {
-        self.cpu.add_program(program)?;
+        self.add_named_program("rom", program)
+    }
+
+    // like add_program, the name is used by the coverage report
+    pub fn add_named_program(&mut self, name: &str, program: &[u8]) -> Result<(), std::io::Error> {
+        self.machine.cpu.add_program(program)?;
+        if self.machine.coverage_path.is_some() {
+            let cpu = &self.machine.cpu;
+            self.machine.coverage = Some(Coverage::new(
+                name,
+                cpu.memory_map.load_address,
+                cpu.program_end_addr,
+            ));
+        }
         Ok(())
     }
+}
 
-    fn scale_d_buffer(&mut self) {
-        for (y, row) in self.cpu.d_buffer.borrow().chunks(WIDTH).enumerate() {
-            let base_y = y * SCALE;
-            for (x, &val) in row.iter().enumerate() {
-                let color = val.to_argb();
-                let base_x = x * SCALE;
+impl Screen {
+    // takes in a finished frame. only the rows that changed are scaled again
+    fn present(&mut self, snapshot: &Snapshot) {
+        self.display.clone_from(&snapshot.display);
+        self.frame = snapshot.frame;
+        render_rows(
+            &self.display,
+            &self.palette,
+            SCALE,
+            &mut self.scaled_buffer,
+            snapshot.dirty,
+        );
+        self.check_scheduled_screenshot();
+        self.record_frame();
+        self.frontend.end_frame(snapshot);
+    }
 
-                // fill 8x8 block directly
-                for dy in 0..SCALE {
-                    let row_start = (base_y + dy) * WIDTH * SCALE + base_x;
-                    self.scaled_buffer[row_start..row_start + SCALE].fill(color);
-                }
+    // draws the last frame, or only lets the frontend handle its events when nothing changed
+    fn show(&mut self, changed: bool) {
+        if changed {
+            self.frontend.draw(&self.display, &self.scaled_buffer);
+        } else {
+            self.frontend.idle();
+        }
+    }
+
+    fn save_screenshot(&mut self, path: &Path) {
+        let scale = if self.screenshot_native { 1 } else { SCALE };
+        let mut pixels = vec![0u32; WIDTH * HEIGHT * scale * scale];
+        render(&self.display, &self.palette, scale, &mut pixels);
+
+        match screenshot::save(path, &pixels, WIDTH * scale, HEIGHT * scale, &self.palette) {
+            Ok(()) => self.frontend.message(format!(
+                "Saved screenshot of frame {} to {}",
+                self.frame,
+                path.display()
+            )),
+            Err(e) => self.frontend.error(format!(
+                "Failed to save screenshot to {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    fn start_recording(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
+        let recorder = Recorder::start(&path, WIDTH * SCALE, HEIGHT * SCALE, self.palette)?;
+        self.frontend.message(format!(
+            "Recording to {} from frame {}",
+            path.display(),
+            self.frame
+        ));
+        self.recorder = Some((recorder, path));
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some((recorder, path)) = self.recorder.take() {
+            let frames = recorder.frames();
+            match recorder.finish() {
+                Ok(()) => self.frontend.message(format!(
+                    "Saved {} frames of video to {}",
+                    frames,
+                    path.display()
+                )),
+                Err(e) => self.frontend.error(format!(
+                    "Failed to save recording to {}: {}",
+                    path.display(),
+                    e
+                )),
+            }
+        }
+    }
+
+    fn record_frame(&mut self) {
+        if let Some((recorder, path)) = &mut self.recorder {
+            if let Err(e) = recorder.push_frame(&self.scaled_buffer) {
+                let text = format!("Failed to record frame to {}: {}", path.display(), e);
+                self.frontend.error(text);
+                self.stop_recording();
             }
         }
     }
 
-    fn update_window(&mut self) {
-        self.scale_d_buffer();
-        self.io
-            .window
-            .update_with_buffer(&self.scaled_buffer, WIDTH * 8, HEIGHT * 8)
-            .expect("Failed to draw window");
+    // recordings and scheduled screenshots must not miss frames
+    fn needs_every_frame(&self) -> bool {
+        self.recorder.is_some() || self.screenshot_at.is_some()
+    }
+
+    fn check_scheduled_screenshot(&mut self) {
+        // the window may not get the exact frame when it falls behind
+        if let Some((_, path)) = self
+            .screenshot_at
+            .take_if(|(frame, _)| *frame <= self.frame)
+        {
+            self.save_screenshot(&path);
+        }
     }
 
-    fn check_keypresses(&mut self) {
-        let pressed = self.io.window.get_keys_pressed(minifb::KeyRepeat::Yes);
-        match pressed.last() {
-            Some(k) => match *k {
+    // handles the hotkeys that only concern the window, everything else has to be sent to the
+    // machine
+    fn check_keypresses(&mut self) -> Vec<Input> {
+        let pressed = self.frontend.keys_pressed();
+        let mut inputs = Vec::new();
+        if let Some(k) = pressed.last() {
+            match *k {
                 minifb::Key::NumPad1 => {
-                    self.cpu.dump(true, 0);
+                    inputs.push(Input::Dump { everything: false });
                 }
                 minifb::Key::NumPad2 => {
-                    self.cpu.dump_everything();
+                    inputs.push(Input::Dump { everything: true });
                 }
                 minifb::Key::NumPad3 => {
                     self.die();
                 }
+                minifb::Key::NumPad4 => {
+                    let path = PathBuf::from(format!("chip8-frame-{}.png", self.frame));
+                    self.save_screenshot(&path);
+                }
+                minifb::Key::NumPad5 => {
+                    if self.recorder.is_some() {
+                        self.stop_recording();
+                    } else {
+                        let path = PathBuf::from(format!("chip8-recording-{}.gif", self.frame));
+                        if let Err(e) = self.start_recording(path) {
+                            self.frontend
+                                .error(format!("Failed to start recording: {}", e));
+                        }
+                    }
+                }
                 _ => {}
-            },
-            None => {}
+            }
+            inputs.push(Input::Key(*k));
         }
-        self.io.keyboard.set_key_pressed(pressed.last());
+        inputs
     }
-    fn reset_keys(&mut self) {
-        self.io.keyboard.key_pressed = None;
+
+    fn die(&mut self) {
+        self.running = false;
     }
+}
+
+// draws the display into a 0RGB buffer, each pixel becoming a scale x scale block
+pub fn render(d_buffer: &Display, palette: &Palette, scale: usize, out: &mut [u32]) {
+    render_rows(d_buffer, palette, scale, out, u64::MAX);
+}
+
+// same as render but only for the rows set in the rows mask, bit y for row y
+pub fn render_rows(
+    d_buffer: &Display,
+    palette: &Palette,
+    scale: usize,
+    out: &mut [u32],
+    rows: u64,
+) {
+    for (y, &row) in d_buffer.rows().iter().enumerate() {
+        if rows & (1 << y) == 0 {
+            continue;
+        }
+        let base_y = y * scale;
+        for x in 0..WIDTH {
+            let color = ((row >> (WIDTH - 1 - x)) as u8 & 1).to_argb(palette);
+            let base_x = x * scale;
 
-    fn die(&self) {
-        if self.timing {
-            println!("Avg time for instruction: {} micros", self.timing_data.avg);
+            // fill scale x scale block directly
+            for dy in 0..scale {
+                let row_start = (base_y + dy) * WIDTH * scale + base_x;
+                out[row_start..row_start + scale].fill(color);
+            }
         }
-        exit(0);
     }
 }
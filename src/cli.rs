@@ -1,16 +1,90 @@
-use clap::{Arg, ArgAction, Command};
+use std::path::PathBuf;
+
+use clap::{
+    builder::PossibleValuesParser, error::ErrorKind, parser::ValueSource, Arg, ArgAction, Command,
+};
+
+use crate::{
+    bench::{self, BenchOptions},
+    blocks::Backend,
+    cfg,
+    chip8::Config,
+    decompile,
+    font::{Font, PRESET_NAMES},
+    frontend::FrontendKind,
+    lint,
+    memory_map::{self, MemoryMap},
+    optimize::VerifyOptions,
+    palette::Palette,
+    quirks::Quirks,
+    sprites::{self, ImportOptions, Patch},
+    terminal::Glyphs,
+};
 
 pub enum Chip8Command {
     Emulate {
         src: String,
-        debug: bool,
-        timing: bool,
+        config: Box<Config>,
+        // ids of the options given on the command line, these win over options in octo cartridges
+        explicit: Vec<String>,
+        // extra rom database to look the rom up in, on top of the built in one
+        rom_db: Option<PathBuf>,
+        use_rom_db: bool,
+    },
+    Conformance {
+        dir: PathBuf,
+        frames: u64,
+        tickrate: u32,
+    },
+    Bench {
+        src: String,
+        options: BenchOptions,
+    },
+    Cfg {
+        src: String,
+        load_address: usize,
+        format: cfg::Format,
+    },
+    Lint {
+        src: String,
+        memory_map: MemoryMap,
+        format: lint::Format,
+    },
+    Info {
+        src: String,
+        memory_map: MemoryMap,
+    },
+    Build {
+        src: PathBuf,
+        output: Option<PathBuf>,
+    },
+    Decompile {
+        src: String,
+        load_address: usize,
+        format: decompile::Format,
+    },
+    Optimize {
+        src: String,
+        output: Option<PathBuf>,
+        load_address: usize,
+        verify: Option<VerifyOptions>,
+    },
+    SpritesExtract {
+        src: String,
+        memory_map: MemoryMap,
+        output: Option<PathBuf>,
+        scale: usize,
+    },
+    SpritesImport {
+        image: PathBuf,
+        options: ImportOptions,
+        patch: Option<Patch>,
     },
     PrintKeyMap,
 }
 
 pub fn parse_args() -> Option<Chip8Command> {
-    let matched = Command::new("chip8")
+    let mut command = Command::new("chip8")
         .about("a chip8 emulator")
         .author("rheasan :3")
         .arg_required_else_help(true)
@@ -18,10 +92,10 @@ pub fn parse_args() -> Option<Chip8Command> {
         // emulate
         .subcommand(
             Command::new("emulate")
-                .about("assemble and run a chip8 program. the input should be a binary c8 assembly file")
+                .about("assemble and run a chip8 program. the input can be a binary rom, a gzip/zip archive, intel hex, a hex text dump or an octo cartridge (.gif). cartridges of SUPER-CHIP and XO-CHIP programs aren't supported")
                 .arg(
                     Arg::new("src")
-                        .help("source for the chip8 program")
+                        .help("source for the chip8 program, - reads from stdin")
                         .num_args(1)
                         .required(true)
                         .action(ArgAction::Set),
@@ -35,16 +109,577 @@ pub fn parse_args() -> Option<Chip8Command> {
                         .required(false),
                 )
                 .arg(
-                    Arg::new("timing")
-                        .help("print time taken per consecutive instructions")
-                        .long("timing")
-                        .short('t')
+                    Arg::new("palette")
+                        .help("background and foreground colors as hex, eg 000000,ffffff")
+                        .long("palette")
+                        .num_args(1)
+                        .value_parser(Palette::parse)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("screenshot-at-frame")
+                        .help("save a screenshot (.png, .ppm or .pbm) once FRAME frames have been emulated")
+                        .long("screenshot-at-frame")
+                        .num_args(2)
+                        .value_names(["FRAME", "PATH"])
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("screenshot-native")
+                        .help("save screenshots at 64x32 instead of the window resolution")
+                        .long("screenshot-native")
+                        .action(ArgAction::SetTrue)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("record-video")
+                        .help("record every emulated frame to a .gif or .y4m file")
+                        .long("record-video")
+                        .num_args(1)
+                        .value_name("PATH")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("frontend")
+                        .help("where to show the display, terminal works over ssh and headless runs uncapped without one")
+                        .long("frontend")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["window", "terminal", "headless"]))
+                        .default_value("window"),
+                )
+                .arg(
+                    Arg::new("glyphs")
+                        .help("characters used by the terminal frontend")
+                        .long("glyphs")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["half-block", "braille"]))
+                        .default_value("half-block"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .help("seed for the random number generator to make runs reproducible")
+                        .long("seed")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64))
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .help("stop after emulating this many frames")
+                        .long("frames")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64))
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("quirks")
+                        .help("interpreter behavior to emulate")
+                        .long("quirks")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(Quirks::preset_names()))
+                        .default_value("default"),
+                )
+                .arg(
+                    Arg::new("tickrate")
+                        .help("instructions to execute per frame")
+                        .long("tickrate")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .help("count where the rom spends its instructions and print a report when it stops")
+                        .long("profile")
                         .action(ArgAction::SetTrue)
-                        .required(false)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("profile-folded")
+                        .help("write the profile as folded stacks for flamegraph tools, implies --profile")
+                        .long("profile-folded")
+                        .value_name("PATH")
+                        .num_args(1)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("coverage")
+                        .help("write which bytes of the rom were executed, read and written when it stops, as an annotated listing or as lcov if the path ends in .info or .lcov")
+                        .long("coverage")
+                        .value_name("PATH")
+                        .num_args(1)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("backend")
+                        .help("how instructions are executed, blocks runs straight line code in precompiled blocks")
+                        .long("backend")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(Backend::names()))
+                        .default_value("interpreter"),
+                )
+                .arg(
+                    Arg::new("font")
+                        .help(format!(
+                            "hex digit font: {} or a .bin/.png font file. defaults to the font of the --quirks platform",
+                            PRESET_NAMES.join(", ")
+                        ))
+                        .long("font")
+                        .num_args(1)
+                        .value_parser(Font::parse)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("memory-map")
+                        .help("ram size, load address and font location to use")
+                        .long("memory-map")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(MemoryMap::preset_names()))
+                        .default_value("default"),
+                )
+                .arg(
+                    Arg::new("ram-size")
+                        .help("bytes of ram, overrides the memory map")
+                        .long("ram-size")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address programs are loaded and started at, overrides the memory map")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("font-address")
+                        .help("address of the built in font, overrides the memory map")
+                        .long("font-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("rom-db")
+                        .help("programs.json (or its directory) from the chip-8-database to look up per game settings in")
+                        .long("rom-db")
+                        .num_args(1)
+                        .value_name("PATH")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("no-rom-db")
+                        .help("don't apply settings from the rom database")
+                        .long("no-rom-db")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("rom-db")
+                        .required(false),
+                ),
+        )
+        // conformance
+        .subcommand(
+            Command::new("conformance")
+                .about("run a directory of test roms under every quirks preset and print which tests pass")
+                .arg(
+                    Arg::new("dir")
+                        .help("directory containing the test roms, and optionally glyphs/pass.pbm, glyphs/fail.pbm and labels/<rom name>.txt with one test name per line")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .help("frames (60 per second, the timers tick once each) to run each rom for before reading the results")
+                        .long("frames")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("1200"),
+                )
+                .arg(
+                    Arg::new("tickrate")
+                        .help("instructions to execute per frame")
+                        .long("tickrate")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("10"),
+                ),
+        )
+        // bench
+        .subcommand(
+            Command::new("bench")
+                .about("run a rom uncapped without a frontend, compare the interpreter backends and time every kind of instruction")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("instructions")
+                        .help("instructions to execute in each run [default: 10000000]")
+                        .long("instructions")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .conflicts_with("frames"),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .help("frames to run instead of a number of instructions")
+                        .long("frames")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64).range(1..)),
+                )
+                .arg(
+                    Arg::new("tickrate")
+                        .help("instructions to execute per frame")
+                        .long("tickrate")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("quirks")
+                        .help("interpreter behavior to emulate")
+                        .long("quirks")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(Quirks::preset_names()))
+                        .default_value("default"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("print the results as text or json")
+                        .long("format")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["text", "json"]))
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("cfg")
+                .about("print the control flow graph of a rom: its basic blocks, subroutines and calls")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address the program is loaded and started at")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .default_value("0x200"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("print the graph as graphviz dot or json")
+                        .long("format")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["dot", "json"]))
+                        .default_value("dot"),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("check a rom for likely bugs without running it, exits with an error if it finds any")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("memory-map")
+                        .help("ram size, load address and font location to check against")
+                        .long("memory-map")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(MemoryMap::preset_names()))
+                        .default_value("default"),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address the program is loaded and started at, overrides the memory map")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("print the findings as text or json")
+                        .long("format")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["text", "json"]))
+                        .default_value("text"),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("print what can be learned about a rom without running it")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("memory-map")
+                        .help("ram size, load address and font location to assume")
+                        .long("memory-map")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(MemoryMap::preset_names()))
+                        .default_value("default"),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address the program is loaded and started at, overrides the memory map")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .required(false),
                 ),
         )
-        .subcommand(Command::new("keymap").about("print keymap"))
-        .get_matches();
+        .subcommand(
+            Command::new("build")
+                .about("compile octo source code into a rom")
+                .arg(
+                    Arg::new("src")
+                        .help("the .8o file to compile")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("where to write the rom [default: the source file with a .ch8 extension]")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .num_args(1)
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("decompile")
+                .about("lift the code of a rom into structured octo or c like pseudocode")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address the program is loaded and started at")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .default_value("0x200"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("write octo or c like pseudocode")
+                        .long("format")
+                        .num_args(1)
+                        .value_parser(PossibleValuesParser::new(["octo", "c"]))
+                        .default_value("octo"),
+                ),
+        )
+        .subcommand(
+            Command::new("optimize")
+                .about("rewrite a rom to save bytes and cycles: thread jumps, remove jumps to the next instruction, fold adds and drop unreachable code")
+                .arg(
+                    Arg::new("src")
+                        .help("source for the chip8 program")
+                        .num_args(1)
+                        .required(true)
+                        .action(ArgAction::Set),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("where to write the optimized rom [default: the source file with a .opt.ch8 extension]")
+                        .short('o')
+                        .long("output")
+                        .value_name("PATH")
+                        .num_args(1)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("load-address")
+                        .help("address the program is loaded and started at")
+                        .long("load-address")
+                        .num_args(1)
+                        .value_parser(memory_map::parse_number)
+                        .default_value("0x200"),
+                )
+                .arg(
+                    Arg::new("verify")
+                        .help("run the original and the optimized rom with the same input and only write the result if they show the same screens")
+                        .long("verify")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .help("frames to run both roms for with --verify")
+                        .long("frames")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .default_value("600"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .help("seed for the random numbers and key presses of --verify")
+                        .long("seed")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("0"),
+                ),
+        )
+        .subcommand(
+            Command::new("sprites")
+                .about("move sprites between roms and png images")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("extract")
+                        .about("save the sprites a rom draws as a png sheet")
+                        .arg(
+                            Arg::new("src")
+                                .help("source for the chip8 program")
+                                .num_args(1)
+                                .required(true)
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .help("where to save the sheet, .png, .ppm or .pbm [default: the source file with a .sprites.png extension]")
+                                .short('o')
+                                .long("output")
+                                .value_name("PATH")
+                                .num_args(1)
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("scale")
+                                .help("size of a sprite pixel in the sheet")
+                                .long("scale")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(u32).range(1..=32))
+                                .default_value("4"),
+                        )
+                        .arg(
+                            Arg::new("memory-map")
+                                .help("ram size, load address and font location to assume")
+                                .long("memory-map")
+                                .num_args(1)
+                                .value_parser(PossibleValuesParser::new(MemoryMap::preset_names()))
+                                .default_value("default"),
+                        )
+                        .arg(
+                            Arg::new("load-address")
+                                .help("address the program is loaded and started at, overrides the memory map")
+                                .long("load-address")
+                                .num_args(1)
+                                .value_parser(memory_map::parse_number)
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("turn a png into sprite bytes, printed as source or patched into a rom")
+                        .arg(
+                            Arg::new("image")
+                                .help("the png to convert, cut into sprites left to right, top to bottom")
+                                .num_args(1)
+                                .required(true)
+                                .action(ArgAction::Set),
+                        )
+                        .arg(
+                            Arg::new("width")
+                                .help("sprite width, 16 for 16x16 SCHIP sprites")
+                                .long("width")
+                                .num_args(1)
+                                .value_parser(PossibleValuesParser::new(["8", "16"]))
+                                .default_value("8"),
+                        )
+                        .arg(
+                            Arg::new("height")
+                                .help("rows per sprite [default: the height of the image]")
+                                .long("height")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(u32).range(1..=15))
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("planes")
+                                .help("1 for chip8 sprites, 2 for 4 color XO-CHIP sprites")
+                                .long("planes")
+                                .num_args(1)
+                                .value_parser(PossibleValuesParser::new(["1", "2"]))
+                                .default_value("1"),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .help("print the bytes as octo or rust source, or as hex")
+                                .long("format")
+                                .num_args(1)
+                                .value_parser(PossibleValuesParser::new(["octo", "rust", "hex"]))
+                                .default_value("octo"),
+                        )
+                        .arg(
+                            Arg::new("name")
+                                .help("label of the sprites [default: the name of the image]")
+                                .long("name")
+                                .num_args(1)
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("patch")
+                                .help("write the bytes into this rom instead of printing them")
+                                .long("patch")
+                                .value_name("ROM")
+                                .num_args(1)
+                                .requires("address")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("address")
+                                .help("where in memory the sprites go with --patch")
+                                .long("address")
+                                .num_args(1)
+                                .value_parser(memory_map::parse_number)
+                                .requires("patch")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("load-address")
+                                .help("address the patched rom is loaded at")
+                                .long("load-address")
+                                .num_args(1)
+                                .value_parser(memory_map::parse_number)
+                                .default_value("0x200"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .help("where to write the patched rom [default: the rom with a .patched.ch8 extension]")
+                                .short('o')
+                                .long("output")
+                                .value_name("PATH")
+                                .num_args(1)
+                                .requires("patch")
+                                .required(false),
+                        ),
+                ),
+        )
+        .subcommand(Command::new("keymap").about("print keymap"));
+    let matched = command.get_matches_mut();
 
     match matched.subcommand() {
         Some(("emulate", emulate_args)) => {
@@ -54,10 +689,246 @@ pub fn parse_args() -> Option<Chip8Command> {
 
             let src = emulate_args.get_one::<String>("src")?.to_owned();
             let debug = *emulate_args.get_one::<bool>("debug").unwrap_or(&false);
-            let timing = *emulate_args.get_one::<bool>("timing").unwrap_or(&false);
-            return Some(Chip8Command::Emulate { src, debug, timing });
+            let palette = emulate_args
+                .get_one::<Palette>("palette")
+                .copied()
+                .unwrap_or_default();
+            let screenshot_at = match emulate_args.get_many::<String>("screenshot-at-frame") {
+                Some(mut values) => {
+                    let frame = values.next()?;
+                    let path = values.next()?;
+                    match frame.parse::<u64>() {
+                        Ok(frame) => Some((frame, PathBuf::from(path))),
+                        Err(_) => command
+                            .error(
+                                ErrorKind::ValueValidation,
+                                format!("\"{}\" is not a valid frame number", frame),
+                            )
+                            .exit(),
+                    }
+                }
+                None => None,
+            };
+            let screenshot_native = *emulate_args
+                .get_one::<bool>("screenshot-native")
+                .unwrap_or(&false);
+            let record_video = emulate_args
+                .get_one::<String>("record-video")
+                .map(PathBuf::from);
+            let frontend = match emulate_args.get_one::<String>("frontend")?.as_str() {
+                "terminal" => FrontendKind::Terminal,
+                "headless" => FrontendKind::Headless,
+                _ => FrontendKind::Window,
+            };
+            let glyphs = match emulate_args.get_one::<String>("glyphs")?.as_str() {
+                "braille" => Glyphs::Braille,
+                _ => Glyphs::HalfBlock,
+            };
+
+            let mut memory_map = MemoryMap::preset(emulate_args.get_one::<String>("memory-map")?)?;
+            if let Some(size) = emulate_args.get_one::<usize>("ram-size") {
+                memory_map.size = *size;
+            }
+            if let Some(address) = emulate_args.get_one::<usize>("load-address") {
+                memory_map.load_address = *address;
+            }
+            if let Some(address) = emulate_args.get_one::<usize>("font-address") {
+                memory_map.font_address = *address;
+            }
+            if let Err(e) = memory_map.validate() {
+                command.error(ErrorKind::ValueValidation, e).exit();
+            }
+
+            let quirks_preset = emulate_args.get_one::<String>("quirks")?;
+            let font = match emulate_args.get_one::<Font>("font") {
+                Some(font) => font.clone(),
+                None => Font::for_platform(quirks_preset).unwrap_or_default(),
+            };
+
+            let explicit = emulate_args
+                .ids()
+                .filter(|id| {
+                    emulate_args.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+                })
+                .map(|id| id.to_string())
+                .collect();
+
+            Some(Chip8Command::Emulate {
+                src,
+                explicit,
+                rom_db: emulate_args.get_one::<String>("rom-db").map(PathBuf::from),
+                use_rom_db: !emulate_args.get_flag("no-rom-db"),
+                config: Box::new(Config {
+                    debug,
+                    palette,
+                    screenshot_at,
+                    screenshot_native,
+                    record_video,
+                    frontend,
+                    glyphs,
+                    seed: emulate_args.get_one::<u64>("seed").copied(),
+                    max_frames: emulate_args.get_one::<u64>("frames").copied(),
+                    quirks: Quirks::preset(quirks_preset)?,
+                    tickrate: *emulate_args.get_one::<u32>("tickrate")?,
+                    backend: Backend::from_name(emulate_args.get_one::<String>("backend")?)?,
+                    profile: emulate_args.get_flag("profile"),
+                    profile_folded: emulate_args
+                        .get_one::<String>("profile-folded")
+                        .map(PathBuf::from),
+                    coverage: emulate_args
+                        .get_one::<String>("coverage")
+                        .map(PathBuf::from),
+                    memory_map,
+                    font,
+                    ..Config::default()
+                }),
+            })
+        }
+        Some(("conformance", conformance_args)) => {
+            let dir = PathBuf::from(conformance_args.get_one::<String>("dir")?);
+            let frames = *conformance_args.get_one::<u64>("frames")?;
+            let tickrate = *conformance_args.get_one::<u32>("tickrate")?;
+            Some(Chip8Command::Conformance {
+                dir,
+                frames,
+                tickrate,
+            })
         }
-        Some(("keymap", _)) => return Some(Chip8Command::PrintKeyMap),
+        Some(("bench", bench_args)) => {
+            let tickrate = *bench_args.get_one::<u32>("tickrate")?;
+            let instructions = match bench_args.get_one::<u64>("frames") {
+                Some(frames) => frames.saturating_mul(tickrate as u64),
+                None => bench_args
+                    .get_one::<u64>("instructions")
+                    .copied()
+                    .unwrap_or(10_000_000),
+            };
+            Some(Chip8Command::Bench {
+                src: bench_args.get_one::<String>("src")?.to_owned(),
+                options: BenchOptions {
+                    instructions,
+                    tickrate,
+                    quirks: Quirks::preset(bench_args.get_one::<String>("quirks")?)?,
+                    format: match bench_args.get_one::<String>("format")?.as_str() {
+                        "json" => bench::Format::Json,
+                        _ => bench::Format::Text,
+                    },
+                },
+            })
+        }
+        Some(("cfg", cfg_args)) => Some(Chip8Command::Cfg {
+            src: cfg_args.get_one::<String>("src")?.to_owned(),
+            load_address: *cfg_args.get_one::<usize>("load-address")?,
+            format: match cfg_args.get_one::<String>("format")?.as_str() {
+                "json" => cfg::Format::Json,
+                _ => cfg::Format::Dot,
+            },
+        }),
+        Some(("lint", lint_args)) => {
+            let mut memory_map = MemoryMap::preset(lint_args.get_one::<String>("memory-map")?)?;
+            if let Some(address) = lint_args.get_one::<usize>("load-address") {
+                memory_map.load_address = *address;
+            }
+            if let Err(e) = memory_map.validate() {
+                command.error(ErrorKind::ValueValidation, e).exit();
+            }
+            Some(Chip8Command::Lint {
+                src: lint_args.get_one::<String>("src")?.to_owned(),
+                memory_map,
+                format: match lint_args.get_one::<String>("format")?.as_str() {
+                    "json" => lint::Format::Json,
+                    _ => lint::Format::Text,
+                },
+            })
+        }
+        Some(("info", info_args)) => {
+            let mut memory_map = MemoryMap::preset(info_args.get_one::<String>("memory-map")?)?;
+            if let Some(address) = info_args.get_one::<usize>("load-address") {
+                memory_map.load_address = *address;
+            }
+            if let Err(e) = memory_map.validate() {
+                command.error(ErrorKind::ValueValidation, e).exit();
+            }
+            Some(Chip8Command::Info {
+                src: info_args.get_one::<String>("src")?.to_owned(),
+                memory_map,
+            })
+        }
+        Some(("build", build_args)) => Some(Chip8Command::Build {
+            src: PathBuf::from(build_args.get_one::<String>("src")?),
+            output: build_args.get_one::<String>("output").map(PathBuf::from),
+        }),
+        Some(("decompile", decompile_args)) => Some(Chip8Command::Decompile {
+            src: decompile_args.get_one::<String>("src")?.to_owned(),
+            load_address: *decompile_args.get_one::<usize>("load-address")?,
+            format: match decompile_args.get_one::<String>("format")?.as_str() {
+                "c" => decompile::Format::C,
+                _ => decompile::Format::Octo,
+            },
+        }),
+        Some(("optimize", optimize_args)) => Some(Chip8Command::Optimize {
+            src: optimize_args.get_one::<String>("src")?.to_owned(),
+            output: optimize_args.get_one::<String>("output").map(PathBuf::from),
+            load_address: *optimize_args.get_one::<usize>("load-address")?,
+            verify: optimize_args.get_flag("verify").then(|| VerifyOptions {
+                frames: *optimize_args.get_one::<u64>("frames").unwrap(),
+                seed: *optimize_args.get_one::<u64>("seed").unwrap(),
+            }),
+        }),
+        Some(("sprites", sprites_args)) => match sprites_args.subcommand() {
+            Some(("extract", extract_args)) => {
+                let mut memory_map =
+                    MemoryMap::preset(extract_args.get_one::<String>("memory-map")?)?;
+                if let Some(address) = extract_args.get_one::<usize>("load-address") {
+                    memory_map.load_address = *address;
+                }
+                if let Err(e) = memory_map.validate() {
+                    command.error(ErrorKind::ValueValidation, e).exit();
+                }
+                Some(Chip8Command::SpritesExtract {
+                    src: extract_args.get_one::<String>("src")?.to_owned(),
+                    memory_map,
+                    output: extract_args.get_one::<String>("output").map(PathBuf::from),
+                    scale: *extract_args.get_one::<u32>("scale")? as usize,
+                })
+            }
+            Some(("import", import_args)) => {
+                let image = PathBuf::from(import_args.get_one::<String>("image")?);
+                let name = match import_args.get_one::<String>("name") {
+                    Some(name) => name.to_owned(),
+                    None => image
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().replace(['-', ' ', '.'], "_"))
+                        .unwrap_or_else(|| String::from("sprite")),
+                };
+                let options = ImportOptions {
+                    width: import_args.get_one::<String>("width")?.parse().ok()?,
+                    height: import_args
+                        .get_one::<u32>("height")
+                        .map(|&height| height as usize),
+                    planes: import_args.get_one::<String>("planes")?.parse().ok()?,
+                    format: match import_args.get_one::<String>("format")?.as_str() {
+                        "rust" => sprites::Format::Rust,
+                        "hex" => sprites::Format::Hex,
+                        _ => sprites::Format::Octo,
+                    },
+                    name,
+                };
+                let patch = import_args.get_one::<String>("patch").map(|rom| Patch {
+                    rom: rom.to_owned(),
+                    load_address: *import_args.get_one::<usize>("load-address").unwrap(),
+                    address: *import_args.get_one::<usize>("address").unwrap(),
+                    output: import_args.get_one::<String>("output").map(PathBuf::from),
+                });
+                Some(Chip8Command::SpritesImport {
+                    image,
+                    options,
+                    patch,
+                })
+            }
+            _ => unreachable!(),
+        },
+        Some(("keymap", _)) => Some(Chip8Command::PrintKeyMap),
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,408 @@
+// runs a directory of test roms (like the ones from Timendus' chip8-test-suite) under every quirks
+// preset and reads the pass/fail marks they draw back out of the display buffer. the marks are
+// named after the label the rom draws left of them, or after the lines of a labels/<rom name>.txt
+// file next to the roms
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cpu::{Cpu, HEIGHT, WIDTH},
+    display::Display,
+    keyboard::KeyBoard,
+    loader,
+    machine::Machine,
+    quirks::{Quirks, PRESETS},
+    terminal::braille_dots,
+};
+
+// the test suite skips its platform menu when this byte is set before starting,
+// 1 = chip8, 2 = schip, 3 = xochip
+const SUITE_PLATFORM_ADDR: usize = 0x1ff;
+
+// a check mark and a cross drawn from memory, they may not match the suite pixel for pixel. the
+// real ones can be used by putting pass.pbm and fail.pbm (as saved by --screenshot-native and
+// cropped) into a `glyphs` directory next to the roms
+pub const PASS_GLYPH: &[&str] = &["......#", ".....#.", "#...#..", ".#.#...", "..#...."];
+pub const FAIL_GLYPH: &[&str] = &["#...#", ".#.#.", "..#..", ".#.#.", "#...#"];
+
+// roms that wait for keys to be pressed, there's nobody to press them
+const NEEDS_INPUT: &[&str] = &["keypad"];
+
+// empty columns looked past from a mark for its label, and between the letters of a label
+const LABEL_GAP: usize = 8;
+const LETTER_GAP: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Pass,
+    Fail,
+}
+
+pub struct Glyph {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+struct RomResult {
+    rom: String,
+    // the test names from the rom's labels file, if it has one
+    labels: Option<Vec<String>>,
+    // one entry per preset
+    runs: Vec<Run>,
+}
+
+// the marks found in reading order with the label drawn next to each, or why the rom stopped
+type Run = Result<Vec<(Mark, Vec<String>)>, String>;
+
+pub fn run(dir: &Path, frames: u64, tickrate: u32) -> Result<(), Error> {
+    let mut roms = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("ch8") | Some("c8") | Some("gz") | Some("zip") | Some("hex") | Some("gif")
+            )
+        })
+        .collect::<Vec<PathBuf>>();
+    roms.sort();
+    if roms.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "No roms (.ch8, .c8, .gz, .zip, .hex or .gif) found in {}",
+                dir.display()
+            ),
+        ));
+    }
+
+    let pass = load_glyph(&dir.join("glyphs/pass.pbm"))?.unwrap_or_else(|| glyph(PASS_GLYPH));
+    let fail = load_glyph(&dir.join("glyphs/fail.pbm"))?.unwrap_or_else(|| glyph(FAIL_GLYPH));
+
+    let mut results = Vec::new();
+    for path in roms {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if NEEDS_INPUT
+            .iter()
+            .any(|key| name.to_ascii_lowercase().contains(key))
+        {
+            println!("skipping {}, it needs keys to be pressed", name);
+            continue;
+        }
+        let program = loader::load(&path.to_string_lossy())?;
+        let labels = match path.file_stem() {
+            Some(stem) => load_labels(&dir.join("labels").join(stem).with_extension("txt"))?,
+            None => None,
+        };
+
+        let runs = PRESETS
+            .iter()
+            .enumerate()
+            .map(|(preset_index, (_, quirks))| {
+                let platform = suite_platform(preset_index);
+                let d_buffer = run_rom(&program, *quirks, platform, frames, tickrate)?;
+                let marks = find_marks(&d_buffer, &pass, &fail);
+                let labels = read_labels(&d_buffer, &marks, &pass, &fail);
+                Ok(marks
+                    .into_iter()
+                    .map(|(_, _, mark)| mark)
+                    .zip(labels)
+                    .collect())
+            })
+            .collect();
+        results.push(RomResult {
+            rom: name,
+            labels,
+            runs,
+        });
+    }
+
+    print_matrix(&results);
+    Ok(())
+}
+
+fn suite_platform(preset_index: usize) -> u8 {
+    match PRESETS[preset_index].0 {
+        "schip" => 2,
+        "xochip" => 3,
+        _ => 1,
+    }
+}
+
+// frames are emulated like emulate does, tickrate instructions and a timer tick each
+pub fn run_rom(
+    program: &[u8],
+    quirks: Quirks,
+    platform: u8,
+    frames: u64,
+    tickrate: u32,
+) -> Result<Display, String> {
+    let mut cpu = Cpu::init(false);
+    cpu.quirks = quirks;
+    cpu.seed(0);
+    cpu.add_program(program).map_err(|e| e.to_string())?;
+    cpu.write_mem(SUITE_PLATFORM_ADDR, &[platform]);
+    let mut machine = Machine::new(cpu, KeyBoard::new(), tickrate);
+
+    while machine.frame < frames {
+        if let Err(e) = machine.run_frame() {
+            return Err(format!("stopped at {:#05x}: {}", machine.cpu.pc, e));
+        }
+    }
+    Ok(machine.cpu.d_buffer)
+}
+
+pub fn glyph(rows: &[&str]) -> Glyph {
+    Glyph {
+        width: rows[0].len(),
+        height: rows.len(),
+        pixels: rows
+            .iter()
+            .flat_map(|row| row.bytes().map(|b| (b == b'#') as u8))
+            .collect(),
+    }
+}
+
+// reads a binary pbm (P4) as written by the screenshot code
+fn load_glyph(path: &Path) -> Result<Option<Glyph>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read(path)?;
+    let bad = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is not a binary pbm image", path.display()),
+        )
+    };
+    let mut parts = data.splitn(3, |&b| b == b'\n');
+    if parts.next() != Some(b"P4") {
+        return Err(bad());
+    }
+    let size = String::from_utf8_lossy(parts.next().ok_or_else(bad)?).into_owned();
+    let (width, height) = size
+        .split_once(' ')
+        .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+        .ok_or_else(bad)?;
+    let bits = parts.next().ok_or_else(bad)?;
+    let stride = width.div_ceil(8);
+    if bits.len() < stride * height {
+        return Err(bad());
+    }
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (y, x)))
+        .map(|(y, x)| (bits[y * stride + x / 8] >> (7 - x % 8)) & 1)
+        .collect();
+    Ok(Some(Glyph {
+        width,
+        height,
+        pixels,
+    }))
+}
+
+// one test name per line
+fn load_labels(path: &Path) -> Result<Option<Vec<String>>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(
+        fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect(),
+    ))
+}
+
+// finds every place a glyph is drawn with an empty 1 pixel border around it, so marks aren't
+// confused with parts of the text next to them
+fn find_glyph(d_buffer: &Display, glyph: &Glyph) -> Vec<(usize, usize)> {
+    let pixel = |x: isize, y: isize| {
+        if x < 0 || y < 0 || x >= WIDTH as isize || y >= HEIGHT as isize {
+            0
+        } else {
+            d_buffer.pixel(x as usize, y as usize) as u8
+        }
+    };
+    let mut found = Vec::new();
+    for y in 0..=(HEIGHT - glyph.height.min(HEIGHT)) as isize {
+        for x in 0..=(WIDTH - glyph.width.min(WIDTH)) as isize {
+            let matches = (-1..=glyph.height as isize).all(|gy| {
+                (-1..=glyph.width as isize).all(|gx| {
+                    let expected = if gy < 0
+                        || gx < 0
+                        || gy >= glyph.height as isize
+                        || gx >= glyph.width as isize
+                    {
+                        0
+                    } else {
+                        glyph.pixels[gy as usize * glyph.width + gx as usize]
+                    };
+                    pixel(x + gx, y + gy) == expected
+                })
+            });
+            if matches {
+                found.push((x as usize, y as usize));
+            }
+        }
+    }
+    found
+}
+
+// every mark on screen with its position, in the order the results are meant to be read
+pub fn find_marks(d_buffer: &Display, pass: &Glyph, fail: &Glyph) -> Vec<(usize, usize, Mark)> {
+    let mut marks = find_glyph(d_buffer, pass)
+        .into_iter()
+        .map(|(x, y)| (x, y, Mark::Pass))
+        .chain(
+            find_glyph(d_buffer, fail)
+                .into_iter()
+                .map(|(x, y)| (x, y, Mark::Fail)),
+        )
+        .collect::<Vec<(usize, usize, Mark)>>();
+    // marks closer horizontally than the narrower glyph is wide overlap, so they are stacked in
+    // the same column of results. a column starts at its leftmost mark
+    marks.sort_by_key(|&(x, y, _)| (x, y));
+    let overlap = pass.width.min(fail.width);
+    let mut column = 0;
+    let mut left = marks.first().map_or(0, |&(x, _, _)| x);
+    let mut columns = Vec::new();
+    for &(x, y, mark) in &marks {
+        if x >= left + overlap {
+            column += 1;
+            left = x;
+        }
+        columns.push((column, y, x, mark));
+    }
+    columns.sort_by_key(|&(column, y, _, _)| (column, y));
+    columns
+        .into_iter()
+        .map(|(_, y, x, mark)| (x, y, mark))
+        .collect()
+}
+
+// the label left of every mark, as lines of braille characters made of its pixels. marks that
+// follow another mark on the same line (a test with several checks) share its label, marks with
+// nothing left of them get none
+pub fn read_labels(
+    d_buffer: &Display,
+    marks: &[(usize, usize, Mark)],
+    pass: &Glyph,
+    fail: &Glyph,
+) -> Vec<Vec<String>> {
+    let size = |mark: Mark| match mark {
+        Mark::Pass => (pass.width, pass.height),
+        Mark::Fail => (fail.width, fail.height),
+    };
+    // left to right, so the mark a label is shared from is done first
+    let mut order = (0..marks.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|&n| marks[n].0);
+
+    let mut labels = vec![Vec::new(); marks.len()];
+    for n in order {
+        let (x, y, mark) = marks[n];
+        let bottom = y + size(mark).1;
+        let lit = |column: usize| (y..bottom).any(|row| d_buffer.pixel(column, row));
+        let mark_at = |column: usize| {
+            marks.iter().position(|&(mx, my, m)| {
+                let (width, height) = size(m);
+                column >= mx && column < mx + width && my < bottom && y < my + height
+            })
+        };
+
+        let Some(right) = (x.saturating_sub(LABEL_GAP)..x).rev().find(|&c| lit(c)) else {
+            continue;
+        };
+        if let Some(other) = mark_at(right) {
+            labels[n] = labels[other].clone();
+            continue;
+        }
+        let mut left = right;
+        for column in (0..right).rev() {
+            if left - column > LETTER_GAP + 1 || mark_at(column).is_some() {
+                break;
+            }
+            if lit(column) {
+                left = column;
+            }
+        }
+
+        labels[n] = (y..bottom)
+            .step_by(4)
+            .map(|top| {
+                (left..=right)
+                    .step_by(2)
+                    .map(|col| {
+                        braille_dots(|dx, dy| {
+                            col + dx <= right
+                                && top + dy < bottom
+                                && d_buffer.pixel(col + dx, top + dy)
+                        })
+                    })
+                    .collect::<String>()
+            })
+            .filter(|line| line.chars().any(|c| c != '\u{2800}'))
+            .collect();
+    }
+    labels
+}
+
+fn print_matrix(results: &[RomResult]) {
+    let presets = PRESETS.iter().map(|(name, _)| *name).collect::<Vec<&str>>();
+    print!("{:<24}", "test");
+    for preset in &presets {
+        print!("{:>9}", preset);
+    }
+    println!();
+
+    for result in results {
+        println!("{}", result.rom);
+        // the drawn labels come from the run that found the most marks
+        let drawn = result
+            .runs
+            .iter()
+            .filter_map(|run| run.as_ref().ok())
+            .max_by_key(|marks| marks.len())
+            .map(|marks| marks.as_slice())
+            .unwrap_or(&[]);
+
+        for row in 0..drawn.len() {
+            let label = match &result.labels {
+                Some(labels) if row < labels.len() => vec![labels[row].clone()],
+                _ if !drawn[row].1.is_empty() => drawn[row].1.clone(),
+                _ => vec![format!("#{}", row + 1)],
+            };
+            print!("  {:<22}", label[0]);
+            for run in &result.runs {
+                let cell = match run {
+                    Ok(marks) => match marks.get(row) {
+                        Some((Mark::Pass, _)) => "ok",
+                        Some((Mark::Fail, _)) => "FAIL",
+                        None => "?",
+                    },
+                    Err(_) => "crash",
+                };
+                print!("{:>9}", cell);
+            }
+            println!();
+            for line in &label[1..] {
+                println!("  {}", line);
+            }
+        }
+
+        for (preset, run) in presets.iter().zip(&result.runs) {
+            match run {
+                Err(e) => println!("  {}: {}", preset, e),
+                Ok(marks) if marks.is_empty() => {
+                    println!("  {}: no pass/fail marks found on screen", preset)
+                }
+                Ok(_) => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// which bytes of the loaded program were executed as instructions, read as data through I
+// (sprites and FX65) or written (FX33 and FX55) during a run. accesses are worked out from the
+// instruction and the registers right before it runs, like the profiler does.
+// the report is an annotated listing, or an lcov tracefile when the path ends in .info or .lcov.
+// lcov only knows lines, so line N there is the word at load address + 2 * (N - 1), and only words
+// that were executed or never touched at all count as code
+use std::{
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::Path,
+};
+
+use crate::{cpu::Cpu, opcode::Op};
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+const WRITTEN: u8 = 4;
+
+// untouched runs longer than this are collapsed into one line of the listing
+const COLLAPSE: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    // the rom, named in lcov reports
+    rom: String,
+    start: usize,
+    // flags for every byte from start to the end of the program
+    flags: Vec<u8>,
+    // how often an instruction started at each byte
+    executions: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub bytes: usize,
+    pub executed: usize,
+    pub read: usize,
+    pub written: usize,
+    // executed and also read or written, either data that ran as code or self modifying code
+    pub mixed: usize,
+    pub untouched: usize,
+}
+
+impl Coverage {
+    pub fn new(rom: &str, start: usize, end: usize) -> Coverage {
+        let len = end.saturating_sub(start);
+        Coverage {
+            rom: rom.to_string(),
+            start,
+            flags: vec![0; len],
+            executions: vec![0; len],
+        }
+    }
+
+    fn mark(&mut self, addr: usize, len: usize, flag: u8) {
+        let from = addr.saturating_sub(self.start).min(self.flags.len());
+        let to = (addr + len)
+            .saturating_sub(self.start)
+            .min(self.flags.len());
+        if addr + len > self.start {
+            for flags in &mut self.flags[from..to] {
+                *flags |= flag;
+            }
+        }
+    }
+
+    // called with every instruction right before it is executed
+    pub fn record(&mut self, cpu: &Cpu, op: Op) {
+        if let Some(count) = cpu
+            .pc
+            .checked_sub(self.start)
+            .and_then(|offset| self.executions.get_mut(offset))
+        {
+            *count += 1;
+        }
+        self.mark(cpu.pc, 2, EXECUTED);
+
+        let i = cpu.i as usize;
+        match op {
+            Op::Draw(_, _, n) => self.mark(i, n as usize, READ),
+            Op::Load(x) => self.mark(i, x as usize + 1, READ),
+            Op::Store(x) => self.mark(i, x as usize + 1, WRITTEN),
+            Op::Bcd(_) => self.mark(i, 3, WRITTEN),
+            _ => {}
+        }
+    }
+
+    pub fn summary(&self) -> Summary {
+        let count = |f: &dyn Fn(u8) -> bool| self.flags.iter().filter(|&&flags| f(flags)).count();
+        Summary {
+            bytes: self.flags.len(),
+            executed: count(&|flags| flags & EXECUTED != 0),
+            read: count(&|flags| flags & READ != 0),
+            written: count(&|flags| flags & WRITTEN != 0),
+            mixed: count(&|flags| flags & EXECUTED != 0 && flags & (READ | WRITTEN) != 0),
+            untouched: count(&|flags| flags == 0),
+        }
+    }
+
+    pub fn save(&self, path: &Path, cpu: &Cpu) -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("info") | Some("lcov") => self.write_lcov(&mut out)?,
+            _ => self.write_listing(&mut out, cpu)?,
+        }
+        out.flush()
+    }
+
+    pub fn write_listing(&self, out: &mut impl Write, cpu: &Cpu) -> Result<(), Error> {
+        let summary = self.summary();
+        writeln!(
+            out,
+            "; {} bytes: {} executed, {} read, {} written, {} both executed and accessed, {} untouched",
+            summary.bytes,
+            summary.executed,
+            summary.read,
+            summary.written,
+            summary.mixed,
+            summary.untouched
+        )?;
+        writeln!(
+            out,
+            "; x executed, r read through I, w written, ! executed and accessed"
+        )?;
+
+        let mut offset = 0;
+        while offset < self.flags.len() {
+            let addr = self.start + offset;
+            if self.executions[offset] > 0 {
+                let instruction = cpu.instruction_at(addr).unwrap_or(0);
+                let both = self.flags[offset] | self.flags.get(offset + 1).copied().unwrap_or(0);
+                writeln!(
+                    out,
+                    "{:#05x}  {:04X}  {:<3}  {:<8} {}",
+                    addr,
+                    instruction,
+                    marks(both),
+                    Op::decode(instruction).class(),
+                    self.executions[offset]
+                )?;
+                offset += 2;
+                continue;
+            }
+
+            // data: a run of bytes with the same flags, at most 8 per line unless untouched
+            let flags = self.flags[offset];
+            let run = self.flags[offset..]
+                .iter()
+                .zip(&self.executions[offset..])
+                .take_while(|&(&f, &count)| f == flags && count == 0)
+                .count();
+            if flags == 0 && run > COLLAPSE {
+                writeln!(
+                    out,
+                    "{:#05x}  {} untouched bytes up to {:#05x}",
+                    addr,
+                    run,
+                    addr + run - 1
+                )?;
+                offset += run;
+                continue;
+            }
+            let len = run.min(8);
+            let bytes = cpu.mem[addr..addr + len]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+            writeln!(out, "{:#05x}  {:<23}  {}", addr, bytes, marks(flags))?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    pub fn write_lcov(&self, out: &mut impl Write) -> Result<(), Error> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{}", self.rom)?;
+        let (mut found, mut hit) = (0, 0);
+        for (line, offset) in (0..self.flags.len()).step_by(2).enumerate() {
+            let flags = self.flags[offset] | self.flags.get(offset + 1).copied().unwrap_or(0);
+            let count = self.executions[offset];
+            // data that was only read or written isn't code that was missed
+            if count == 0 && flags != 0 {
+                continue;
+            }
+            writeln!(out, "DA:{},{}", line + 1, count)?;
+            found += 1;
+            if count > 0 {
+                hit += 1;
+            }
+        }
+        writeln!(out, "LF:{}", found)?;
+        writeln!(out, "LH:{}", hit)?;
+        writeln!(out, "end_of_record")
+    }
+}
+
+fn marks(flags: u8) -> String {
+    let mut marks = String::new();
+    if flags & EXECUTED != 0 && flags & (READ | WRITTEN) != 0 {
+        marks.push('!');
+    }
+    for (flag, mark) in [(EXECUTED, 'x'), (READ, 'r'), (WRITTEN, 'w')] {
+        if flags & flag != 0 {
+            marks.push(mark);
+        }
+    }
+    if marks.is_empty() {
+        marks.push('-');
+    }
+    marks
+}
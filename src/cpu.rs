@@ -1,52 +1,46 @@
-use std::{
-    cell::RefCell,
-    fmt::Debug,
-    rc::Rc,
-    time::{Duration, Instant},
-};
+use std::fmt::{Debug, Display};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::keyboard::KeyBoard;
+use crate::{
+    blocks::{self, MAX_BLOCK_LEN},
+    display,
+    font::{Font, BIG_FONT_SIZE, BIG_GLYPH_SIZE, SMALL_FONT_SIZE, SMALL_GLYPH_SIZE},
+    keyboard::KeyBoard,
+    memory_map::MemoryMap,
+    opcode::Op,
+    quirks::Quirks,
+};
 
-// 0x2000 - 0x200 = 0x1EB4 = 7860 bytes
-pub const MAX_PROGRAM_SIZE: usize = 7860usize;
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
-const ZERO: &[u8] = &[0xf0, 0x90, 0x90, 0x90, 0xf0];
-const ONE: &[u8] = &[0x20, 0x60, 0x20, 0x20, 0x70];
-const TWO: &[u8] = &[0xf0, 0x10, 0xf0, 0x80, 0xf0];
-const THREE: &[u8] = &[0xf0, 0x10, 0xf0, 0x10, 0xf0];
-const FOUR: &[u8] = &[0xf0, 0x80, 0xf0, 0x10, 0xf0];
-const FIVE: &[u8] = &[0xf0, 0x80, 0xf0, 0x90, 0xf0];
-const SIX: &[u8] = &[0xf0, 0x80, 0xf0, 0x90, 0xf0];
-const SEVEN: &[u8] = &[0xf0, 0x10, 0x20, 0x40, 0x40];
-const EIGHT: &[u8] = &[0xf0, 0x90, 0xf0, 0x10, 0xf0];
-const NINE: &[u8] = &[0xf0, 0x90, 0xf0, 0x10, 0xf0];
-const A: &[u8] = &[0xf0, 0x90, 0xf0, 0x90, 0x90];
-const B: &[u8] = &[0xe0, 0x90, 0xe0, 0x90, 0xe0];
-const C: &[u8] = &[0xf0, 0x80, 0x80, 0x80, 0xf0];
-const D: &[u8] = &[0xe0, 0x90, 0x90, 0x90, 0xe0];
-const E: &[u8] = &[0xf0, 0x80, 0xf0, 0x80, 0xf0];
-const F: &[u8] = &[0xf0, 0x80, 0xf0, 0x80, 0x80];
-const HEX_SPRITE_SIZE: u16 = 0x5;
-
 pub struct Cpu {
+    // use write_mem to change it while a program is loaded
     pub mem: Vec<u8>,
-    pub d_buffer: Rc<RefCell<Vec<u8>>>,
+    pub d_buffer: display::Display,
     // general purpose registers V0 to VF, 8bits wide
     pub gp_registers: [u8; 16],
-    // address register 'I', 16bit wide but addresses are only 12bit wide
-    // only addresses in the range 0x200 - 0xE8F are available for programs
-    // first 0x200 bytes are reserved for the interpreter, and final 352 bytes are reserved for
-    // "variables and display refresh"
+    // address register 'I', 16bit wide. how much of that is usable depends on the memory map
     pub i: u16,
     pub pc: usize,
     pub sp: u8,
     pub stack: Vec<usize>,
-    pub delay_timer: (u8, Instant),
-    pub sound_timer: (u8, Instant),
+    // both timers count down once per emulated frame, see tick_timers
+    pub delay_timer: u8,
+    pub sound_timer: u8,
     pub program_end_addr: usize,
     pub debug: bool,
+    // source for 0xCXNN, seed it to make runs reproducible
+    pub rng: StdRng,
+    pub quirks: Quirks,
+    pub memory_map: MemoryMap,
+    // whether the font has the big SCHIP digits for 0xFX30
+    big_font: bool,
+    // decoded instruction for every address, see fetch
+    instruction_cache: Vec<Option<(u16, Op)>>,
+    // compiled block for every address when the block backend is on, see step_block
+    blocks: Vec<Option<Box<[Op]>>>,
 }
 impl Debug for Cpu {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -70,66 +64,95 @@ pub enum ExecuteError {
     MaxCallDepthReached(u16),
     BadReturn(u16),
     BadJumpAddr(u16),
+    // an instruction tried to use memory past the end of ram
+    BadMemoryAccess(usize),
 }
-impl ToString for ExecuteError {
-    fn to_string(&self) -> String {
+impl Display for ExecuteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ExecuteError::FailedToReadInstruction => String::from("Failed to read instructions"),
-            ExecuteError::BadInstruction(i) => format!("Bad instruction: {:#04}", i),
-            ExecuteError::BadJumpAddr(addr) => format!("Bad Jump Address: {:#04}", addr),
-            ExecuteError::BadReturn(addr) => format!("Bad return Address: {:#04}", addr),
-            ExecuteError::MaxCallDepthReached(i) => format!("Max call depth reached: {:#04}", i),
+            ExecuteError::FailedToReadInstruction => write!(f, "Failed to read instructions"),
+            ExecuteError::BadInstruction(i) => write!(f, "Bad instruction: {:#04}", i),
+            ExecuteError::BadJumpAddr(addr) => write!(f, "Bad Jump Address: {:#04}", addr),
+            ExecuteError::BadReturn(addr) => write!(f, "Bad return Address: {:#04}", addr),
+            ExecuteError::MaxCallDepthReached(i) => write!(f, "Max call depth reached: {:#04}", i),
+            ExecuteError::BadMemoryAccess(addr) => {
+                write!(f, "Memory access out of bounds: {:#06x}", addr)
+            }
         }
     }
 }
 
 impl Cpu {
     pub fn init(debug: bool) -> Self {
-        let sprites = vec![
-            ZERO, ONE, TWO, THREE, FOUR, FIVE, SIX, SEVEN, EIGHT, NINE, A, B, C, D, E, F,
-        ]
-        .concat();
+        Cpu::with_memory_map(debug, MemoryMap::default())
+    }
+
+    // the memory map has to be valid, see MemoryMap::validate
+    pub fn with_memory_map(debug: bool, memory_map: MemoryMap) -> Self {
         let mut cpu = Cpu {
-            mem: vec![0; 8192],
-            d_buffer: Rc::new(RefCell::new(vec![0u8; WIDTH * HEIGHT])),
+            mem: vec![0; memory_map.size],
+            d_buffer: display::Display::new(),
             gp_registers: [0u8; 16],
             i: 0,
-            pc: 0x200,
+            pc: memory_map.load_address,
             sp: 0,
             stack: Vec::new(),
-            delay_timer: (0, Instant::now()),
-            sound_timer: (0, Instant::now()),
+            delay_timer: 0,
+            sound_timer: 0,
             program_end_addr: 0,
-            debug: debug,
+            debug,
+            rng: StdRng::from_entropy(),
+            quirks: Quirks::default(),
+            memory_map,
+            big_font: false,
+            instruction_cache: vec![None; memory_map.size],
+            blocks: Vec::new(),
         };
-        // add sprites to the start of the memory
-        cpu.mem[0..sprites.len()].copy_from_slice(&sprites);
+        cpu.load_font(&Font::default());
         cpu
     }
+    pub fn load_font(&mut self, font: &Font) {
+        let start = self.memory_map.font_address;
+        self.write_mem(start, &font.small);
+        match &font.big {
+            Some(glyphs) => self.write_mem(start + SMALL_FONT_SIZE, glyphs),
+            None => self.write_mem(start + SMALL_FONT_SIZE, &[0; BIG_FONT_SIZE]),
+        }
+        self.big_font = font.big.is_some();
+    }
+
     pub fn add_program(&mut self, program: &[u8]) -> Result<(), std::io::Error> {
-        if program.len() > MAX_PROGRAM_SIZE {
+        let max_size = self.memory_map.max_program_size();
+        if program.len() > max_size {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
                 format!(
                     "Max supported program size if {} bytes. Received {} bytes",
-                    MAX_PROGRAM_SIZE,
+                    max_size,
                     program.len()
                 ),
             ));
         }
-        self.mem[512..(program.len() + 512)].copy_from_slice(&program[..]);
-        self.program_end_addr = 0x200 + program.len();
+        let start = self.memory_map.load_address;
+        self.write_mem(start, program);
+        self.program_end_addr = start + program.len();
 
         Ok(())
     }
+    #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.i = 0;
-        self.pc = 0x0200;
+        self.pc = self.memory_map.load_address;
         self.sp = 0;
         self.stack.clear();
-        self.delay_timer = (0, Instant::now());
-        self.sound_timer = (0, Instant::now());
-        self.mem[0x200..=self.program_end_addr].fill(0);
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        let program = vec![0; self.program_end_addr - self.memory_map.load_address];
+        self.write_mem(self.memory_map.load_address, &program);
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
     pub fn dump(&self, dump_d_buffer: bool, program_bytes: usize) {
@@ -137,97 +160,74 @@ impl Cpu {
 
         if dump_d_buffer {
             println!("\nDisplay buffer: ");
-            let d_buffer = self.d_buffer.borrow();
-            for (i, e) in d_buffer.iter().enumerate() {
-                print!("{}", e & 1);
-                if i != 0 && (i + 1) % WIDTH == 0 {
-                    println!();
-                }
+            for row in self.d_buffer.rows() {
+                println!("{:064b}", row);
             }
         }
 
         if program_bytes > 0 {
             println!(
                 "\n Loaded Program: (total program length {} bytes)",
-                self.program_end_addr - 0x200
+                self.program_end_addr - self.memory_map.load_address
             );
-            for i in &self.mem[0x200..program_bytes + 0x200] {
+            let start = self.memory_map.load_address;
+            for i in &self.mem[start..start + program_bytes] {
                 println!("{:#04x}", i);
             }
         }
     }
 
     pub fn dump_everything(&self) {
-        self.dump(true, self.program_end_addr - 0x200);
+        self.dump(true, self.program_end_addr - self.memory_map.load_address);
     }
     // Returns Ok(true) if d_buffer was updated
     pub fn step(&mut self, keyboard: &KeyBoard) -> Result<bool, ExecuteError> {
-        let instruction = self.get_next_instruction()?;
-        let nnn = (instruction & 0x0fff) as usize;
-        // x only contains 4 bits so can access gp_registers without bound check
-        let x = ((instruction & 0x0f00) >> 8) as usize;
-        let y = ((instruction & 0x00f0) >> 4) as usize;
-        let nn = (instruction & 0x00ff) as u8;
-        let n = (instruction & 0x000f) as u8;
-
-        let changed = Cpu::handle_timer(&mut self.sound_timer);
+        let (instruction, op) = self.fetch()?;
 
-        let _ = Cpu::handle_timer(&mut self.delay_timer);
-
-        match instruction & 0xf000 {
-            0x0000 => {
-                if ((instruction & 0x0f00) >> 8) != 0 {
-                    // instruction == 0x0NNN
-                    // execute machine language subroutine at addr NNN
-                    // this instruction is only on RCA COSMAC VIP (the original implementation of chip8)
-                    // so ignore this instruction
-                    self.pc += 2;
-                } else {
-                    // instruction == 0x00E(0|E)
-                    match instruction & 0x00ff {
-                        0xe0 => {
-                            if self.debug {
-                                println!("cls");
-                            }
-                            // instruction == 0x00E0
-                            // clear the screen
-                            self.d_buffer.borrow_mut().fill(0);
-                            self.pc += 2;
-                        }
-                        0xee => {
-                            if self.debug {
-                                println!("ret");
-                            }
-                            // instruction == 0x00EE
-                            // return from a subroutine
-                            if self.stack.is_empty() {
-                                return Err(ExecuteError::BadReturn(instruction));
-                            }
-                            let addr = self.stack.pop().unwrap();
-                            if !self.is_valid_program_addr(addr) {
-                                return Err(ExecuteError::BadJumpAddr(instruction));
-                            }
-                            // the returned address will be the instruction calling the subroutine so skip it
-                            self.pc = addr + 2;
-                        }
-                        _ => {
-                            return Err(ExecuteError::BadInstruction(instruction));
-                        }
-                    }
+        match op {
+            Op::Sys(_) => {
+                // instruction == 0x0NNN
+                // execute machine language subroutine at addr NNN
+                // this instruction is only on RCA COSMAC VIP (the original implementation of chip8)
+                // so ignore this instruction
+                self.pc += 2;
+            }
+            Op::Cls => {
+                if self.debug {
+                    println!("cls");
                 }
+                // instruction == 0x00E0
+                // clear the screen
+                self.d_buffer.clear();
+                self.pc += 2;
             }
-            0x1000 => {
+            Op::Ret => {
+                if self.debug {
+                    println!("ret");
+                }
+                // instruction == 0x00EE
+                // return from a subroutine
+                let Some(addr) = self.stack.pop() else {
+                    return Err(ExecuteError::BadReturn(instruction));
+                };
+                if !self.is_valid_program_addr(addr) {
+                    return Err(ExecuteError::BadJumpAddr(instruction));
+                }
+                // the returned address will be the instruction calling the subroutine so skip it
+                self.pc = addr + 2;
+            }
+            Op::Jump(nnn) => {
                 // instruction == 0x1NNN
                 // jump to address NNN
                 if self.debug {
                     println!("jmp {:x}", nnn);
                 }
-                if !self.is_valid_program_addr(nnn) {
+                if !self.is_valid_program_addr(nnn as usize) {
                     return Err(ExecuteError::BadJumpAddr(instruction));
                 }
-                self.pc = nnn;
+                self.pc = nnn as usize;
             }
-            0x2000 => {
+            Op::Call(nnn) => {
                 // instruction == 0x2NNN
                 // execute subroutine starting at address NNN
                 if self.debug {
@@ -237,11 +237,12 @@ impl Cpu {
                     return Err(ExecuteError::MaxCallDepthReached(instruction));
                 }
                 self.stack.push(self.pc);
-                self.pc = nnn;
+                self.pc = nnn as usize;
             }
-            0x3000 => {
+            Op::SkipEqImm(x, nn) => {
                 // instruction == 0x3XNN
                 // skip the following instruction if the value of VX == NN
+                let x = x as usize;
                 if self.debug {
                     println!("se v{:x} {:x} (vx = {:x})", x, nn, self.gp_registers[x]);
                 }
@@ -250,9 +251,10 @@ impl Cpu {
                 }
                 self.pc += 2;
             }
-            0x4000 => {
+            Op::SkipNeImm(x, nn) => {
                 // instruction == 0x4XNN
                 // skip the following instruction if the value of VX != NN
+                let x = x as usize;
                 if self.debug {
                     println!("sne v{:x} {:x}", x, nn);
                 }
@@ -261,12 +263,10 @@ impl Cpu {
                 }
                 self.pc += 2;
             }
-            0x5000 => {
-                if instruction & 0x000f != 0 {
-                    return Err(ExecuteError::BadInstruction(instruction));
-                }
+            Op::SkipEqReg(x, y) => {
                 // instruction === 0x5XY0
                 // skip the following instruction if the value of VX == VY
+                let (x, y) = (x as usize, y as usize);
                 if self.debug {
                     println!(
                         "se v{:x} v{:x} (vx = {:x} vy = {:x})",
@@ -278,208 +278,199 @@ impl Cpu {
                 }
                 self.pc += 2;
             }
-            0x6000 => {
+            Op::SetImm(x, nn) => {
                 // instruction == 0x6XNN
                 // store number nn in register VX
                 if self.debug {
                     println!("ld v{:x} {:x}", x, nn);
                 }
-                self.gp_registers[x] = nn;
+                self.gp_registers[x as usize] = nn;
                 self.pc += 2;
             }
-            0x7000 => {
+            Op::AddImm(x, nn) => {
                 // instruction == 0x7XNN
                 // add value NN to register VX (wrapping addition)
+                let x = x as usize;
                 if self.debug {
                     println!("add v{:x} {:x} (vx = {:x})", x, nn, self.gp_registers[x]);
                 }
                 self.gp_registers[x] = self.gp_registers[x].wrapping_add(nn);
                 self.pc += 2;
             }
-            0x8000 => {
-                match n {
-                    0x0 => {
-                        // instruction == 0x8XY0
-                        // store value of VY in VX
-                        if self.debug {
-                            println!("ld v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[x] = self.gp_registers[y];
-                        self.pc += 2;
-                    }
-                    0x1 => {
-                        // instruction == 0x8XY1
-                        // set VX = VX | VY
-                        if self.debug {
-                            println!("or v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[x] |= self.gp_registers[y];
-                        self.pc += 2;
-                    }
-                    0x2 => {
-                        // instruction == 0x8XY2
-                        // set VX = VX & VY
-                        if self.debug {
-                            println!("and v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[x] &= self.gp_registers[y];
-                        self.pc += 2;
-                    }
-                    0x3 => {
-                        // instruction == 0x8XY3
-                        // set VX = VX ^ VY
-                        if self.debug {
-                            println!("xor v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[x] ^= self.gp_registers[y];
-                        self.pc += 2;
-                    }
-                    0x4 => {
-                        // instruction == 0x8XY4
-                        // set VX = VX + VY. set VF = 0x01 if carry occurs, otherwise set VF = 0x00
-                        if self.debug {
-                            print!(
-                                "add v{:x} v{:x} (vx = {:x} vy = {:x})",
-                                x, y, self.gp_registers[x], self.gp_registers[y]
-                            );
-                        }
-                        let t = self.gp_registers[x].checked_add(self.gp_registers[y]);
-                        match t {
-                            Some(val) => {
-                                self.gp_registers[x] = val;
-                                self.gp_registers[0xf] = 0;
-                                if self.debug {
-                                    println!(
-                                        "    vf after add {:x} vx after add {:x}",
-                                        self.gp_registers[0xf], self.gp_registers[x]
-                                    );
-                                }
-                            }
-                            None => {
-                                // addition overflowed
-                                let sum = self.gp_registers[x] as u16 + self.gp_registers[y] as u16;
-                                self.gp_registers[x] = (sum & 0xFF) as u8; // wrap around correctly
-                                self.gp_registers[0xf] = 1;
-                                if self.debug {
-                                    println!(
-                                        "    vf after add {:x} vx after add {:x} overflowed",
-                                        self.gp_registers[0xf], self.gp_registers[x]
-                                    );
-                                }
-                            }
-                        }
-                        self.pc += 2;
-                    }
-                    0x5 => {
-                        // instruction == 0x8XY5
-                        // set VX = VX - VY. set VF = 0x00 if borrow occurs, otherwise set VF = 0x01
-                        if self.debug {
-                            print!("sub v{:x} v{:x}", x, y);
-                        }
+            Op::Set(x, y) => {
+                // instruction == 0x8XY0
+                // store value of VY in VX
+                if self.debug {
+                    println!("ld v{:x} v{:x}", x, y);
+                }
+                self.gp_registers[x as usize] = self.gp_registers[y as usize];
+                self.pc += 2;
+            }
+            Op::Or(x, y) => {
+                // instruction == 0x8XY1
+                // set VX = VX | VY
+                if self.debug {
+                    println!("or v{:x} v{:x}", x, y);
+                }
+                self.gp_registers[x as usize] |= self.gp_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.gp_registers[0xf] = 0;
+                }
+                self.pc += 2;
+            }
+            Op::And(x, y) => {
+                // instruction == 0x8XY2
+                // set VX = VX & VY
+                if self.debug {
+                    println!("and v{:x} v{:x}", x, y);
+                }
+                self.gp_registers[x as usize] &= self.gp_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.gp_registers[0xf] = 0;
+                }
+                self.pc += 2;
+            }
+            Op::Xor(x, y) => {
+                // instruction == 0x8XY3
+                // set VX = VX ^ VY
+                if self.debug {
+                    println!("xor v{:x} v{:x}", x, y);
+                }
+                self.gp_registers[x as usize] ^= self.gp_registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.gp_registers[0xf] = 0;
+                }
+                self.pc += 2;
+            }
+            Op::Add(x, y) => {
+                // instruction == 0x8XY4
+                // set VX = VX + VY. set VF = 0x01 if carry occurs, otherwise set VF = 0x00
+                let (x, y) = (x as usize, y as usize);
+                if self.debug {
+                    print!(
+                        "add v{:x} v{:x} (vx = {:x} vy = {:x})",
+                        x, y, self.gp_registers[x], self.gp_registers[y]
+                    );
+                }
+                let (sum, carry) = self.gp_registers[x].overflowing_add(self.gp_registers[y]);
+                self.gp_registers[x] = sum;
+                self.gp_registers[0xf] = carry as u8;
+                if self.debug {
+                    println!(
+                        "    vf after add {:x} vx after add {:x}{}",
+                        self.gp_registers[0xf],
+                        self.gp_registers[x],
+                        if carry { " overflowed" } else { "" }
+                    );
+                }
+                self.pc += 2;
+            }
+            Op::Sub(x, y) => {
+                // instruction == 0x8XY5
+                // set VX = VX - VY. set VF = 0x00 if borrow occurs, otherwise set VF = 0x01
+                let (x, y) = (x as usize, y as usize);
+                if self.debug {
+                    print!("sub v{:x} v{:x}", x, y);
+                }
 
-                        if self.gp_registers[y] <= self.gp_registers[x] {
-                            self.gp_registers[0xf] = 0x1;
-                        } else {
-                            self.gp_registers[0xf] = 0x0;
-                        }
-                        if self.debug {
-                            println!("    vf after sub {:x}", self.gp_registers[0xf]);
-                        }
-                        self.gp_registers[x] =
-                            self.gp_registers[x].wrapping_sub(self.gp_registers[y]);
+                if self.gp_registers[y] <= self.gp_registers[x] {
+                    self.gp_registers[0xf] = 0x1;
+                } else {
+                    self.gp_registers[0xf] = 0x0;
+                }
+                if self.debug {
+                    println!("    vf after sub {:x}", self.gp_registers[0xf]);
+                }
+                self.gp_registers[x] = self.gp_registers[x].wrapping_sub(self.gp_registers[y]);
 
-                        self.pc += 2;
-                    }
-                    0x6 => {
-                        // instruction == 0x8XY6
-                        // set VX = VY >> 1, set VF to the least significant bit of VY before shift. VY is unchanged
-                        if self.debug {
-                            println!("shr v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[0xf] = self.gp_registers[y] & 0x1;
-                        self.gp_registers[x] = self.gp_registers[y] >> 1;
-                        self.pc += 2;
-                    }
-                    0x7 => {
-                        // instruction == 0x8XY7
-                        // set VX = VY - VX. set VF = 0x00 if borrow occcurs, otherwise set VF = 0x01
-                        if self.debug {
-                            print!("subn v{:x} v{:x}", x, y);
-                        }
-                        self.gp_registers[x] =
-                            self.gp_registers[y].wrapping_sub(self.gp_registers[x]);
+                self.pc += 2;
+            }
+            Op::Shr(x, y) => {
+                // instruction == 0x8XY6
+                // set VX = VY >> 1, set VF to the least significant bit of VY before shift. VY is unchanged
+                if self.debug {
+                    println!("shr v{:x} v{:x}", x, y);
+                }
+                // with the shift quirk VX is shifted in place and VY is ignored
+                let src = if self.quirks.shift_in_place { x } else { y } as usize;
+                self.gp_registers[0xf] = self.gp_registers[src] & 0x1;
+                self.gp_registers[x as usize] = self.gp_registers[src] >> 1;
+                self.pc += 2;
+            }
+            Op::SubN(x, y) => {
+                // instruction == 0x8XY7
+                // set VX = VY - VX. set VF = 0x00 if borrow occcurs, otherwise set VF = 0x01
+                let (x, y) = (x as usize, y as usize);
+                if self.debug {
+                    print!("subn v{:x} v{:x}", x, y);
+                }
+                self.gp_registers[x] = self.gp_registers[y].wrapping_sub(self.gp_registers[x]);
 
-                        if self.gp_registers[x] <= self.gp_registers[y] {
-                            self.gp_registers[0xf] = 0x1;
-                        } else {
-                            self.gp_registers[0xf] = 0x0;
-                        }
-                        if self.debug {
-                            println!("    vf after subn {:x}", self.gp_registers[0xf]);
-                        }
-                        self.pc += 2;
-                    }
-                    0xE => {
-                        // instruction == 0x8XYE
-                        // set VX = VY << 1, set VF to the most significant bit of VY before shift. VY is unchanged
-                        if self.debug {
-                            println!(
-                                "shl v{:x} v{:x} (vx = {:x}, vy = {:x})",
-                                x, y, self.gp_registers[x], self.gp_registers[y]
-                            );
-                        }
-                        self.gp_registers[0xf] = (self.gp_registers[y] & 0x80) >> 7;
-                        self.gp_registers[x] = self.gp_registers[y] << 1;
-                        self.pc += 2;
-                    }
-                    _ => {
-                        return Err(ExecuteError::BadInstruction(instruction));
-                    }
+                if self.gp_registers[x] <= self.gp_registers[y] {
+                    self.gp_registers[0xf] = 0x1;
+                } else {
+                    self.gp_registers[0xf] = 0x0;
+                }
+                if self.debug {
+                    println!("    vf after subn {:x}", self.gp_registers[0xf]);
                 }
+                self.pc += 2;
             }
-            0x9000 => {
-                if (instruction & 0x000f) != 0 {
-                    return Err(ExecuteError::BadInstruction(instruction));
+            Op::Shl(x, y) => {
+                // instruction == 0x8XYE
+                // set VX = VY << 1, set VF to the most significant bit of VY before shift. VY is unchanged
+                if self.debug {
+                    println!(
+                        "shl v{:x} v{:x} (vx = {:x}, vy = {:x})",
+                        x, y, self.gp_registers[x as usize], self.gp_registers[y as usize]
+                    );
                 }
+                let src = if self.quirks.shift_in_place { x } else { y } as usize;
+                self.gp_registers[0xf] = (self.gp_registers[src] & 0x80) >> 7;
+                self.gp_registers[x as usize] = self.gp_registers[src] << 1;
+                self.pc += 2;
+            }
+            Op::SkipNeReg(x, y) => {
                 // instruction == 0x9XY0
                 // skip the following instruction if VX != VY
                 if self.debug {
                     println!("sne v{:x} v{:x}", x, y);
                 }
-                if self.gp_registers[x] != self.gp_registers[y] {
+                if self.gp_registers[x as usize] != self.gp_registers[y as usize] {
                     self.pc += 2;
                 }
                 self.pc += 2;
             }
-            0xa000 => {
+            Op::SetI(nnn) => {
                 // instruction == 0xANNN
                 // store memory address NNN in I
                 if self.debug {
                     println!("ld I, {:x}", nnn);
                 }
-                self.i = nnn as u16;
+                self.i = nnn;
                 self.pc += 2;
             }
-            0xb000 => {
+            Op::JumpOffset(x, nnn) => {
                 // instruction == 0xBNNN
                 // jump to address V0 + NNN
+                // with the jump quirk it's 0xBXNN and jumps to XNN + VX
+                let offset = if self.quirks.jump_uses_vx { x } else { 0 } as usize;
                 if self.debug {
-                    println!("jmp V0 {:x}", nnn);
+                    println!("jmp V{:x} {:x}", offset, nnn);
                 }
-                self.pc = self.gp_registers[0] as usize + nnn;
-                self.pc += 2;
+                self.pc = self.gp_registers[offset] as usize + nnn as usize;
             }
-            0xc000 => {
+            Op::Rand(x, nn) => {
                 // instruction == 0xCXNN
                 // set VX to random number with the mask NN
                 if self.debug {
                     println!("rnd v{:x}, {:x}", x, nn);
                 }
-                let random = rand::random::<u8>();
-                self.gp_registers[x] = random & nn;
+                let random = self.rng.gen::<u8>();
+                self.gp_registers[x as usize] = random & nn;
                 self.pc += 2;
             }
-            0xd000 => {
+            Op::Draw(x, y, n) => {
                 // instruction == 0xDXYN
                 // draw a sprite at position VX and VY with N bytes of sprite data starting at
                 // address stored in I.
@@ -488,7 +479,8 @@ impl Cpu {
                     print!("drw v{:x} v{:x} {:x}", x, y, n);
                 }
 
-                if self.draw_sprite(n, self.gp_registers[x], self.gp_registers[y])? {
+                let (vx, vy) = (self.gp_registers[x as usize], self.gp_registers[y as usize]);
+                if self.draw_sprite(n, vx, vy)? {
                     self.gp_registers[0xf] = 0x01;
                 } else {
                     self.gp_registers[0xf] = 0x00;
@@ -498,219 +490,342 @@ impl Cpu {
                 }
                 self.pc += 2;
             }
-            0xe000 => {
-                match instruction & 0xff {
-                    0x9e => {
-                        // instruction == 0xEX9E
-                        // skip the following instruction if the key corresponding to the hex value in VX
-                        // is pressed. do not wait for input
-                        if self.debug {
-                            println!("skp v{:x}", x);
-                        }
-                        match keyboard.get_current_key() {
-                            Some(key) => {
-                                if key == self.gp_registers[x] {
-                                    self.pc += 4
-                                }
-                            }
-                            None => self.pc += 2,
-                        }
-                    }
-                    0xa1 => {
-                        // instruction == 0xEXA1
-                        // skip the following instruction if the key corresponding to the hex value in VX
-                        // is not pressed. do not wait for input
-                        if self.debug {
-                            println!("sknp v{:x} (vx = {:x})", x, self.gp_registers[x]);
-                        }
-                        match keyboard.get_current_key() {
-                            Some(key) => {
-                                if self.debug {
-                                    println!("pressed key = {:x}", key);
-                                }
-                                if key != self.gp_registers[x] {
-                                    self.pc += 4
-                                }
-                            }
-                            None => self.pc += 2,
+            Op::SkipKey(x) => {
+                // instruction == 0xEX9E
+                // skip the following instruction if the key corresponding to the hex value in VX
+                // is pressed. do not wait for input
+                if self.debug {
+                    println!("skp v{:x}", x);
+                }
+                match keyboard.get_current_key() {
+                    Some(key) => {
+                        if key == self.gp_registers[x as usize] {
+                            self.pc += 4
                         }
                     }
-                    _ => {
-                        return Err(ExecuteError::BadInstruction(instruction));
-                    }
+                    None => self.pc += 2,
                 }
             }
-            0xf000 => {
-                match instruction & 0xff {
-                    0x07 => {
-                        // instruction == 0xFX07
-                        // store current value of delay timer in VX
-                        if self.debug {
-                            println!("ld v{:x} dt {:x}", x, self.delay_timer.0);
-                        }
-                        self.gp_registers[x] = self.delay_timer.0;
-                        self.pc += 2;
-                    }
-                    0x0A => {
-                        // instruction == 0xFX0A
-                        // wait for keypress and store the value of key in VX
-                        if self.debug {
-                            println!("ld v{:x} K", x);
-                        }
-                        // dont increment pc is there is no keypress
-                        match keyboard.get_current_key() {
-                            Some(k) => {
-                                self.gp_registers[x] = k;
-                                self.pc += 2;
-                            }
-                            None => {}
-                        }
-                    }
-                    0x15 => {
-                        // instruction == 0xFX15
-                        // set the delay timer to the value of VX
-                        if self.debug {
-                            println!("ld dt v{:x}", x);
-                        }
-                        self.delay_timer = (self.gp_registers[x], Instant::now());
-                        self.pc += 2;
-                    }
-                    0x18 => {
-                        // instruction == 0xFX18
-                        // set the sound timer to the value of VX
-                        if self.debug {
-                            println!("ld st v{:x}", x);
-                        }
-                        self.sound_timer = (self.gp_registers[x], Instant::now());
-                        self.pc += 2;
-                    }
-                    0x1e => {
-                        // instruction == 0xFX1E
-                        // Add the value stored in VX to I
-                        if self.debug {
-                            println!("add I v{:x}", x);
-                        }
-                        self.i += self.gp_registers[x] as u16;
-                        self.pc += 2;
-                    }
-                    0x29 => {
-                        // instruction == 0xFX29
-                        // set I to memory address of sprite data corresponding to the digit stored in register VX
-                        if self.debug {
-                            println!("ld f v{:x}", x);
-                        }
-                        if self.gp_registers[x] > 0xf {
-                            return Err(ExecuteError::BadInstruction(instruction));
-                        }
-                        self.i = self.gp_registers[x] as u16 * HEX_SPRITE_SIZE;
-                        self.pc += 2;
-                    }
-                    0x33 => {
-                        // instruction == 0xFX33
-                        // store the binary coded decimal equivalent of value in VX at addr I, I+1, I+2
-                        // https://en.wikipedia.org/wiki/Binary-coded_decimal
-                        if self.debug {
-                            println!("ld b v{:x}", x);
-                        }
-                        let vx = self.gp_registers[x];
-                        // TODO: bound check
-                        let addr = self.i as usize;
-                        self.mem[addr] = vx / 100;
-                        self.mem[addr + 1] = (vx % 100) / 10;
-                        self.mem[addr + 2] = vx % 10;
-                        self.pc += 2;
-                    }
-                    0x55 => {
-                        // instruction == 0xFX55
-                        // store the values of registers V0 to VX inclusive to memory starting at address I.
-                        // set I = I + X + 1 after saving.
+            Op::SkipNotKey(x) => {
+                // instruction == 0xEXA1
+                // skip the following instruction if the key corresponding to the hex value in VX
+                // is not pressed. do not wait for input
+                let x = x as usize;
+                if self.debug {
+                    println!("sknp v{:x} (vx = {:x})", x, self.gp_registers[x]);
+                }
+                match keyboard.get_current_key() {
+                    Some(key) => {
                         if self.debug {
-                            println!("ld [I] v{:x}", x);
+                            println!("pressed key = {:x}", key);
                         }
-                        let addr = self.i as usize;
-                        self.mem[addr..=addr + x].copy_from_slice(&self.gp_registers[0..=x]);
-                        self.i += (x + 1) as u16;
-                        self.pc += 2;
-                    }
-                    0x65 => {
-                        // instruction == 0xFX65
-                        // fill V0 to VX inclusive with values stored at memory starting at address I.
-                        // set I = I + X + 1 after filling.
-                        if self.debug {
-                            println!("ld v{:x} I", x);
+                        if key != self.gp_registers[x] {
+                            self.pc += 4
                         }
-                        let addr = self.i as usize;
-                        self.gp_registers[0..=x].copy_from_slice(&self.mem[addr..=addr + x]);
-                        self.i += (x + 1) as u16;
-                        self.pc += 2;
-                    }
-                    _ => {
-                        return Err(ExecuteError::BadInstruction(instruction));
                     }
+                    None => self.pc += 2,
+                }
+            }
+            Op::GetDelay(x) => {
+                // instruction == 0xFX07
+                // store current value of delay timer in VX
+                if self.debug {
+                    println!("ld v{:x} dt {:x}", x, self.delay_timer);
                 }
+                self.gp_registers[x as usize] = self.delay_timer;
+                self.pc += 2;
+            }
+            Op::WaitKey(x) => {
+                // instruction == 0xFX0A
+                // wait for keypress and store the value of key in VX
+                if self.debug {
+                    println!("ld v{:x} K", x);
+                }
+                // dont increment pc is there is no keypress
+                if let Some(k) = keyboard.get_current_key() {
+                    self.gp_registers[x as usize] = k;
+                    self.pc += 2;
+                }
+            }
+            Op::SetDelay(x) => {
+                // instruction == 0xFX15
+                // set the delay timer to the value of VX
+                if self.debug {
+                    println!("ld dt v{:x}", x);
+                }
+                self.delay_timer = self.gp_registers[x as usize];
+                self.pc += 2;
+            }
+            Op::SetSound(x) => {
+                // instruction == 0xFX18
+                // set the sound timer to the value of VX
+                if self.debug {
+                    println!("ld st v{:x}", x);
+                }
+                self.sound_timer = self.gp_registers[x as usize];
+                self.pc += 2;
             }
-            _ => {
+            Op::AddI(x) => {
+                // instruction == 0xFX1E
+                // Add the value stored in VX to I
+                if self.debug {
+                    println!("add I v{:x}", x);
+                }
+                self.i = self.i.wrapping_add(self.gp_registers[x as usize] as u16);
+                self.pc += 2;
+            }
+            Op::Font(x) => {
+                // instruction == 0xFX29
+                // set I to memory address of sprite data corresponding to the digit stored in register VX
+                if self.debug {
+                    println!("ld f v{:x}", x);
+                }
+                let digit = self.gp_registers[x as usize] as usize;
+                if digit > 0xf {
+                    return Err(ExecuteError::BadInstruction(instruction));
+                }
+                self.i = (self.memory_map.font_address + digit * SMALL_GLYPH_SIZE) as u16;
+                self.pc += 2;
+            }
+            Op::BigFont(x) => {
+                // instruction == 0xFX30 (SCHIP)
+                // set I to the big 8x10 sprite for the digit stored in VX
+                if self.debug {
+                    println!("ld hf v{:x}", x);
+                }
+                let digit = self.gp_registers[x as usize] as usize;
+                if !self.big_font || digit > 0xf {
+                    return Err(ExecuteError::BadInstruction(instruction));
+                }
+                self.i = (self.memory_map.font_address + SMALL_FONT_SIZE + digit * BIG_GLYPH_SIZE)
+                    as u16;
+                self.pc += 2;
+            }
+            Op::Bcd(x) => {
+                // instruction == 0xFX33
+                // store the binary coded decimal equivalent of value in VX at addr I, I+1, I+2
+                // https://en.wikipedia.org/wiki/Binary-coded_decimal
+                if self.debug {
+                    println!("ld b v{:x}", x);
+                }
+                let vx = self.gp_registers[x as usize];
+                let range = self.mem_range(3)?;
+                self.write_mem(range.start, &[vx / 100, (vx % 100) / 10, vx % 10]);
+                self.pc += 2;
+            }
+            Op::Store(x) => {
+                // instruction == 0xFX55
+                // store the values of registers V0 to VX inclusive to memory starting at address I.
+                // set I = I + X + 1 after saving.
+                let x = x as usize;
+                if self.debug {
+                    println!("ld [I] v{:x}", x);
+                }
+                let range = self.mem_range(x + 1)?;
+                let registers = self.gp_registers;
+                self.write_mem(range.start, &registers[0..=x]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add((x + 1) as u16);
+                }
+                self.pc += 2;
+            }
+            Op::Load(x) => {
+                // instruction == 0xFX65
+                // fill V0 to VX inclusive with values stored at memory starting at address I.
+                // set I = I + X + 1 after filling.
+                let x = x as usize;
+                if self.debug {
+                    println!("ld v{:x} I", x);
+                }
+                let range = self.mem_range(x + 1)?;
+                self.gp_registers[0..=x].copy_from_slice(&self.mem[range]);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add((x + 1) as u16);
+                }
+                self.pc += 2;
+            }
+            Op::Invalid(_) => {
                 return Err(ExecuteError::BadInstruction(instruction));
             }
         }
 
-        Ok(instruction & 0xd000 == 0xd000)
+        Ok(matches!(op, Op::Cls | Op::Draw(..)))
     }
     #[inline]
     fn is_valid_program_addr(&self, addr: usize) -> bool {
-        addr >= 0x200 && addr <= self.program_end_addr
+        addr >= self.memory_map.load_address && addr <= self.program_end_addr
+    }
+    // len bytes of memory starting at I
+    fn mem_range(&self, len: usize) -> Result<std::ops::Range<usize>, ExecuteError> {
+        let start = self.i as usize;
+        if start + len > self.mem.len() {
+            return Err(ExecuteError::BadMemoryAccess(start + len - 1));
+        }
+        Ok(start..start + len)
+    }
+    // the raw instruction at addr, None past the end of ram
+    pub fn instruction_at(&self, addr: usize) -> Option<u16> {
+        match (self.mem.get(addr), self.mem.get(addr + 1)) {
+            (Some(&high), Some(&low)) => Some((high as u16) << 8 | low as u16),
+            _ => None,
+        }
     }
-    fn get_next_instruction(&self) -> Result<u16, ExecuteError> {
-        let byte_1 = self.mem.get(self.pc);
-        let byte_2 = self.mem.get(self.pc + 1);
 
-        if byte_1.is_none() || byte_2.is_none() {
+    // the instruction at pc, decoded. decoded instructions are kept in the cache until the memory
+    // they came from is written to
+    fn fetch(&mut self) -> Result<(u16, Op), ExecuteError> {
+        if let Some(Some(cached)) = self.instruction_cache.get(self.pc) {
+            return Ok(*cached);
+        }
+        let Some(instruction) = self.instruction_at(self.pc) else {
             return Err(ExecuteError::FailedToReadInstruction);
+        };
+        let decoded = (instruction, Op::decode(instruction));
+        if let Some(entry) = self.instruction_cache.get_mut(self.pc) {
+            *entry = Some(decoded);
         }
-        let instruction = (*byte_1.unwrap() as u16) << 8 | *byte_2.unwrap() as u16;
-        Ok(instruction)
+        Ok(decoded)
     }
 
-    fn handle_timer(timer: &mut (u8, Instant)) -> bool {
-        let (ref mut s_timer, ref mut last_op) = timer;
-        if *s_timer == 0 {
-            return false;
+    // every write to memory has to go through here so the cache never runs stale instructions
+    pub fn write_mem(&mut self, addr: usize, data: &[u8]) {
+        self.mem[addr..addr + data.len()].copy_from_slice(data);
+        // the instruction starting one byte earlier overlaps the first byte written
+        let start = addr.saturating_sub(1);
+        let end = (addr + data.len()).min(self.instruction_cache.len());
+        if start < end {
+            self.instruction_cache[start..end].fill(None);
+        }
+        // any block starting up to MAX_BLOCK_LEN instructions earlier can contain the written bytes
+        let start = addr.saturating_sub(MAX_BLOCK_LEN * 2 - 1);
+        let end = (addr + data.len()).min(self.blocks.len());
+        if start < end {
+            self.blocks[start..end].fill(None);
         }
+    }
+
+    // the cache is on by default, turning it off decodes every instruction every time it runs
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        let size = if enabled { self.mem.len() } else { 0 };
+        self.instruction_cache = vec![None; size];
+    }
 
-        if Instant::now().duration_since(*last_op) >= Duration::from_micros(1_000_000 / 60) {
-            *s_timer -= 1;
-            *last_op = Instant::now();
-            return true;
+    // the block backend is off by default
+    pub fn set_block_backend(&mut self, enabled: bool) {
+        let size = if enabled { self.mem.len() } else { 0 };
+        self.blocks = vec![None; size];
+    }
+
+    // runs the block at pc if the block backend is on and the whole block fits in max_steps,
+    // otherwise a single instruction through step. returns the number of instructions executed
+    // and whether d_buffer was updated
+    pub fn step_block(
+        &mut self,
+        keyboard: &KeyBoard,
+        max_steps: u32,
+    ) -> Result<(u32, bool), ExecuteError> {
+        if self.debug || self.pc >= self.blocks.len() {
+            return self.step(keyboard).map(|did_draw| (1, did_draw));
+        }
+        let start = self.pc;
+        let block = match self.blocks[start].take() {
+            Some(block) => block,
+            None => blocks::compile(&self.mem, start),
+        };
+        let len = block.len() as u32;
+        if len == 0 || len > max_steps {
+            // put it back first, step might write over it
+            self.blocks[start] = Some(block);
+            return self.step(keyboard).map(|did_draw| (1, did_draw));
+        }
+        for &op in block.iter() {
+            self.execute_straight_line(op);
+        }
+        self.pc = start + block.len() * 2;
+        self.blocks[start] = Some(block);
+        Ok((len, false))
+    }
+
+    // same as the matching arms in step, minus the logging and moving pc. only ops accepted by
+    // blocks::is_straight_line get here
+    fn execute_straight_line(&mut self, op: Op) {
+        let r = &mut self.gp_registers;
+        match op {
+            Op::Sys(_) => {}
+            Op::SetImm(x, nn) => r[x as usize] = nn,
+            Op::AddImm(x, nn) => r[x as usize] = r[x as usize].wrapping_add(nn),
+            Op::Set(x, y) => r[x as usize] = r[y as usize],
+            Op::Or(x, y) | Op::And(x, y) | Op::Xor(x, y) => {
+                let (vx, vy) = (r[x as usize], r[y as usize]);
+                r[x as usize] = match op {
+                    Op::Or(..) => vx | vy,
+                    Op::And(..) => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if self.quirks.logic_resets_vf {
+                    r[0xf] = 0;
+                }
+            }
+            Op::Add(x, y) => {
+                let (sum, carry) = r[x as usize].overflowing_add(r[y as usize]);
+                r[x as usize] = sum;
+                r[0xf] = carry as u8;
+            }
+            // VF is written before the result here and in step, which matters when X or Y is F
+            Op::Sub(x, y) => {
+                r[0xf] = (r[y as usize] <= r[x as usize]) as u8;
+                r[x as usize] = r[x as usize].wrapping_sub(r[y as usize]);
+            }
+            Op::Shr(x, y) => {
+                let src = if self.quirks.shift_in_place { x } else { y } as usize;
+                r[0xf] = r[src] & 0x1;
+                r[x as usize] = r[src] >> 1;
+            }
+            Op::SubN(x, y) => {
+                let result = r[y as usize].wrapping_sub(r[x as usize]);
+                r[x as usize] = result;
+                r[0xf] = (result <= r[y as usize]) as u8;
+            }
+            Op::Shl(x, y) => {
+                let src = if self.quirks.shift_in_place { x } else { y } as usize;
+                r[0xf] = (r[src] & 0x80) >> 7;
+                r[x as usize] = r[src] << 1;
+            }
+            Op::SetI(nnn) => self.i = nnn,
+            Op::Rand(x, nn) => r[x as usize] = self.rng.gen::<u8>() & nn,
+            Op::GetDelay(x) => r[x as usize] = self.delay_timer,
+            Op::SetDelay(x) => self.delay_timer = r[x as usize],
+            Op::SetSound(x) => self.sound_timer = r[x as usize],
+            Op::AddI(x) => self.i = self.i.wrapping_add(r[x as usize] as u16),
+            _ => unreachable!("{:?} can't be part of a block", op),
         }
-        return false;
+    }
+
+    // timers run at 60hz, so this has to be called once for every emulated frame
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 
     fn draw_sprite(&mut self, n: u8, x: u8, y: u8) -> Result<bool, ExecuteError> {
         // flag is set if is any set pixels are set to unset
         let mut should_set_flag = false;
-        let sprite_start = self.i as usize;
-        let sprite_end = sprite_start + n as usize;
+        let sprites = self.mem_range(n as usize)?;
 
-        let mut coord_x = x as usize % WIDTH;
-        let mut coord_y = y as usize % HEIGHT;
+        // the starting position always wraps, the rest of the sprite is clipped or wrapped
+        // depending on the quirks
+        let start_x = x as usize % WIDTH;
+        let start_y = y as usize % HEIGHT;
 
-        // each byte in the display buffer corresponds to a pixel and a bit in the sprite
         // each sprite is always 1 byte wide and 1 to 15 pixels tall
-        let mut d_buffer = self.d_buffer.borrow_mut();
-        for byte in self.mem[sprite_start..sprite_end].iter_mut() {
-            let mut b = *byte;
-            for _ in 0..8 {
-                let index = coord_x + coord_y * WIDTH;
-                let prev_value = d_buffer[index];
-                // the sprite is drawn by xoring with the current value not by setting a new value
-                d_buffer[index] ^= (b & 0x80) >> 7;
-                should_set_flag |= prev_value == 1 && d_buffer[index] == 0;
-
-                coord_x += 1;
-                b <<= 1;
+        for (coord_y, byte) in (start_y..).zip(self.mem[sprites].iter()) {
+            if coord_y >= HEIGHT && self.quirks.clip_sprites {
+                break;
             }
-            coord_y += 1;
-            coord_x = x as usize % WIDTH;
+            // the sprite is drawn by xoring with the current value not by setting a new value
+            should_set_flag |=
+                self.d_buffer
+                    .draw_byte(start_x, coord_y % HEIGHT, *byte, self.quirks.clip_sprites);
         }
 
         Ok(should_set_flag)
@@ -0,0 +1,813 @@
+// lifts the code of a rom into structured octo or c like pseudocode. the control flow graph gives
+// the subroutines, then the instructions of each are matched against the shapes compilers and
+// people write conditionals and loops in:
+//   skip; instruction                 -> if ... then instruction
+//   skip; jump a ... a:                -> if ... begin ... end
+//   skip; jump a ... jump b a: ... b:  -> if ... begin ... else ... end
+//   a: ... jump a                      -> loop ... again
+//   skip; jump past the loop           -> while ...
+// anything else is left as a jump to a label. bytes that aren't reachable code are printed as
+// data. the octo output compiles again as long as the rom doesn't compute addresses at runtime or
+// point into the middle of its instructions
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Error,
+};
+
+use crate::{cfg::Cfg, loader, opcode::Op};
+
+// data bytes per line
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Octo,
+    C,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Key,
+    NotKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Condition {
+    x: u8,
+    comparison: Comparison,
+    rhs: Operand,
+}
+
+impl Condition {
+    // the condition under which a skip instruction skips
+    fn of_skip(op: Op) -> Option<Condition> {
+        let (x, comparison, rhs) = match op {
+            Op::SkipEqImm(x, nn) => (x, Comparison::Eq, Operand::Byte(nn)),
+            Op::SkipNeImm(x, nn) => (x, Comparison::Ne, Operand::Byte(nn)),
+            Op::SkipEqReg(x, y) => (x, Comparison::Eq, Operand::Register(y)),
+            Op::SkipNeReg(x, y) => (x, Comparison::Ne, Operand::Register(y)),
+            Op::SkipKey(x) => (x, Comparison::Key, Operand::Byte(0)),
+            Op::SkipNotKey(x) => (x, Comparison::NotKey, Operand::Byte(0)),
+            _ => return None,
+        };
+        Some(Condition { x, comparison, rhs })
+    }
+
+    fn negate(self) -> Condition {
+        let comparison = match self.comparison {
+            Comparison::Eq => Comparison::Ne,
+            Comparison::Ne => Comparison::Eq,
+            Comparison::Key => Comparison::NotKey,
+            Comparison::NotKey => Comparison::Key,
+        };
+        Condition { comparison, ..self }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stmt {
+    Op(usize, Op),
+    IfThen(usize, Condition, Box<Stmt>),
+    If {
+        addr: usize,
+        condition: Condition,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    Loop(usize, Vec<Stmt>),
+    // leave the loop unless the condition holds
+    While(usize, Condition),
+    // jump back to the start of the loop
+    Continue(usize, usize),
+    // jump to right after the loop
+    Break(usize, usize),
+    Goto(usize, usize),
+    // a jump to itself
+    Halt(usize),
+}
+
+impl Stmt {
+    fn addr(&self) -> usize {
+        match self {
+            Stmt::Op(addr, _)
+            | Stmt::IfThen(addr, ..)
+            | Stmt::If { addr, .. }
+            | Stmt::Loop(addr, _)
+            | Stmt::While(addr, _)
+            | Stmt::Continue(addr, _)
+            | Stmt::Break(addr, _)
+            | Stmt::Goto(addr, _)
+            | Stmt::Halt(addr) => *addr,
+        }
+    }
+}
+
+// addresses a label can be printed at
+fn collect_addrs(stmts: &[Stmt], addrs: &mut BTreeSet<usize>) {
+    for stmt in stmts {
+        addrs.insert(stmt.addr());
+        match stmt {
+            Stmt::If {
+                then, otherwise, ..
+            } => {
+                collect_addrs(then, addrs);
+                collect_addrs(otherwise, addrs);
+            }
+            Stmt::Loop(_, body) => collect_addrs(body, addrs),
+            _ => {}
+        }
+    }
+}
+
+// the loop being structured: where it starts and the address right after its jump back
+#[derive(Debug, Clone, Copy)]
+struct LoopContext {
+    head: usize,
+    exit: usize,
+}
+
+// the instructions of one subroutine in address order
+struct Structurer {
+    code: Vec<(usize, Op)>,
+    index: BTreeMap<usize, usize>,
+    // addresses of gotos, and of loops left or restarted from the middle
+    gotos: BTreeSet<usize>,
+    loop_jumps: BTreeSet<usize>,
+}
+
+impl Structurer {
+    fn new(code: Vec<(usize, Op)>) -> Structurer {
+        let index = code
+            .iter()
+            .enumerate()
+            .map(|(n, &(addr, _))| (addr, n))
+            .collect();
+        Structurer {
+            code,
+            index,
+            gotos: BTreeSet::new(),
+            loop_jumps: BTreeSet::new(),
+        }
+    }
+
+    // the position of addr within lo..=hi, hi being the end of the range
+    fn position(&self, addr: usize, lo: usize, hi: usize) -> Option<usize> {
+        if hi > 0 && addr == self.code[hi - 1].0 + 2 {
+            return Some(hi);
+        }
+        self.index
+            .get(&addr)
+            .copied()
+            .filter(|&n| n >= lo && n <= hi)
+    }
+
+    // a jump back to the instruction at k from further down in k..hi, the furthest one wins
+    fn back_edge(&self, k: usize, hi: usize) -> Option<usize> {
+        let head = self.code[k].0;
+        (k + 1..hi)
+            .rev()
+            .find(|&j| self.code[j].1 == Op::Jump(head as u16))
+    }
+
+    fn jump(&mut self, addr: usize, target: usize, context: Option<LoopContext>) -> Stmt {
+        match context {
+            _ if target == addr => Stmt::Halt(addr),
+            Some(context) if target == context.head => {
+                self.loop_jumps.insert(target);
+                Stmt::Continue(addr, target)
+            }
+            Some(context) if target == context.exit => {
+                self.loop_jumps.insert(target);
+                Stmt::Break(addr, target)
+            }
+            _ => {
+                self.gotos.insert(target);
+                Stmt::Goto(addr, target)
+            }
+        }
+    }
+
+    fn structure(
+        &mut self,
+        lo: usize,
+        hi: usize,
+        context: Option<LoopContext>,
+        // the loop starting at lo is already being structured
+        in_loop: bool,
+    ) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        let mut k = lo;
+        while k < hi {
+            let (addr, op) = self.code[k];
+
+            if !(in_loop && k == lo) {
+                if let Some(j) = self.back_edge(k, hi) {
+                    let inner = LoopContext {
+                        head: addr,
+                        exit: self.code[j].0 + 2,
+                    };
+                    let mut body = self.structure(k, j, Some(inner), true);
+                    // a skip over the jump back ends the loop unless its condition holds
+                    if let Some(&Stmt::Op(skip_addr, skip_op)) = body.last() {
+                        if let Some(skip) = Condition::of_skip(skip_op) {
+                            if skip_addr + 2 == self.code[j].0 {
+                                *body.last_mut().unwrap() = Stmt::While(skip_addr, skip.negate());
+                            }
+                        }
+                    }
+                    stmts.push(Stmt::Loop(addr, body));
+                    k = j + 1;
+                    continue;
+                }
+            }
+
+            let next = self
+                .code
+                .get(k + 1)
+                .copied()
+                .filter(|&(a, _)| a == addr + 2);
+            if let (Some(skip), Some((next_addr, next_op))) = (Condition::of_skip(op), next) {
+                if k + 1 < hi {
+                    if let Op::Jump(target) = next_op {
+                        let target = target as usize;
+                        if context.is_some_and(|c| c.exit == target) {
+                            stmts.push(Stmt::While(addr, skip));
+                            k += 2;
+                            continue;
+                        }
+                        if let Some(end) = self
+                            .position(target, k + 2, hi)
+                            .filter(|_| target > next_addr)
+                        {
+                            // an unconditional jump right before the target skips the else branch
+                            let otherwise = match self.code.get(end - 1) {
+                                Some(&(jump_addr, Op::Jump(past)))
+                                    if end > k + 2 && past as usize > target =>
+                                {
+                                    self.position(past as usize, end, hi)
+                                        .filter(|_| jump_addr != target)
+                                }
+                                _ => None,
+                            };
+                            let stmt = match otherwise {
+                                Some(past) => Stmt::If {
+                                    addr,
+                                    condition: skip,
+                                    then: self.structure(k + 2, end - 1, context, false),
+                                    otherwise: self.structure(end, past, context, false),
+                                },
+                                None => Stmt::If {
+                                    addr,
+                                    condition: skip,
+                                    then: self.structure(k + 2, end, context, false),
+                                    otherwise: Vec::new(),
+                                },
+                            };
+                            stmts.push(stmt);
+                            k = otherwise.unwrap_or(end);
+                            continue;
+                        }
+                        // octo can't put a loop after then, so a jump to itself stays a jump
+                        let jump = match self.jump(next_addr, target, context) {
+                            Stmt::Halt(addr) => {
+                                self.gotos.insert(addr);
+                                Stmt::Goto(addr, addr)
+                            }
+                            jump => jump,
+                        };
+                        stmts.push(Stmt::IfThen(addr, skip.negate(), Box::new(jump)));
+                        k += 2;
+                        continue;
+                    }
+                    if Condition::of_skip(next_op).is_none() {
+                        let then = Stmt::Op(next_addr, next_op);
+                        stmts.push(Stmt::IfThen(addr, skip.negate(), Box::new(then)));
+                        k += 2;
+                        continue;
+                    }
+                }
+            }
+
+            stmts.push(match op {
+                Op::Jump(target) => self.jump(addr, target as usize, context),
+                _ => Stmt::Op(addr, op),
+            });
+            k += 1;
+        }
+        stmts
+    }
+}
+
+// registers a subroutine reads before writing them and the ones it writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub inputs: u16,
+    pub writes: u16,
+    pub reads_i: bool,
+    pub writes_i: bool,
+}
+
+// (read, written) registers as bit masks
+fn registers(op: Op) -> (u16, u16) {
+    let bit = |r: u8| 1u16 << r;
+    // v0 to vx
+    let range = |x: u8| u16::MAX >> (15 - x);
+    match op {
+        Op::SkipEqImm(x, _) | Op::SkipNeImm(x, _) | Op::SkipKey(x) | Op::SkipNotKey(x) => {
+            (bit(x), 0)
+        }
+        Op::SkipEqReg(x, y) | Op::SkipNeReg(x, y) => (bit(x) | bit(y), 0),
+        Op::SetImm(x, _) | Op::Rand(x, _) | Op::GetDelay(x) | Op::WaitKey(x) => (0, bit(x)),
+        Op::AddImm(x, _) => (bit(x), bit(x)),
+        Op::Set(x, y) => (bit(y), bit(x)),
+        Op::Or(x, y) | Op::And(x, y) | Op::Xor(x, y) => (bit(x) | bit(y), bit(x) | bit(0xf)),
+        Op::Add(x, y) | Op::Sub(x, y) | Op::SubN(x, y) | Op::Shr(x, y) | Op::Shl(x, y) => {
+            (bit(x) | bit(y), bit(x) | bit(0xf))
+        }
+        Op::JumpOffset(x, _) => (bit(0) | bit(x), 0),
+        Op::Draw(x, y, _) => (bit(x) | bit(y), bit(0xf)),
+        Op::SetDelay(x) | Op::SetSound(x) | Op::AddI(x) | Op::Font(x) | Op::BigFont(x) => {
+            (bit(x), 0)
+        }
+        Op::Bcd(x) => (bit(x), 0),
+        Op::Store(x) => (range(x), 0),
+        Op::Load(x) => (0, range(x)),
+        _ => (0, 0),
+    }
+}
+
+// what the code reads and writes, including through the subroutines it calls. the code is taken in
+// address order, so a register only counts as an input if it is read before any write to it
+pub fn usage(code: &[(usize, Op)], callees: &BTreeMap<usize, Usage>) -> Usage {
+    let mut usage = Usage::default();
+    for &(_, op) in code {
+        if let Some(callee) = callees.get(&match op {
+            Op::Call(nnn) => nnn as usize,
+            _ => usize::MAX,
+        }) {
+            usage.inputs |= callee.inputs & !usage.writes;
+            usage.writes |= callee.writes;
+            usage.reads_i |= callee.reads_i && !usage.writes_i;
+            usage.writes_i |= callee.writes_i;
+            continue;
+        }
+        let (reads, writes) = registers(op);
+        usage.inputs |= reads & !usage.writes;
+        usage.writes |= writes;
+        let (reads_i, writes_i) = match op {
+            Op::Draw(..) | Op::Bcd(_) | Op::Store(_) | Op::Load(_) => (true, false),
+            Op::AddI(_) => (true, true),
+            Op::SetI(_) | Op::Font(_) | Op::BigFont(_) => (false, true),
+            _ => (false, false),
+        };
+        usage.reads_i |= reads_i && !usage.writes_i;
+        usage.writes_i |= writes_i;
+    }
+    usage
+}
+
+fn register_list(mask: u16, i: bool) -> String {
+    let registers = (0..16)
+        .filter(|r| mask & (1 << r) != 0)
+        .map(|r| format!("v{:x}", r))
+        .chain(i.then(|| String::from("i")))
+        .collect::<Vec<String>>();
+    match registers.is_empty() {
+        true => String::from("none"),
+        false => registers.join(" "),
+    }
+}
+
+struct Printer<'a> {
+    format: Format,
+    out: String,
+    depth: usize,
+    // names of subroutines, labels and data
+    names: &'a BTreeMap<usize, String>,
+    // addresses of gotos, c only needs labels for these
+    targets: &'a BTreeSet<usize>,
+    printed: BTreeSet<usize>,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(&"\t".repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn comment(&self, text: &str) -> String {
+        match self.format {
+            Format::Octo => format!("# {}", text),
+            Format::C => format!("// {}", text),
+        }
+    }
+
+    fn name(&self, addr: usize) -> String {
+        match self.names.get(&addr) {
+            Some(name) => name.clone(),
+            None => format!("{:#05x}", addr),
+        }
+    }
+
+    fn condition(&self, condition: Condition) -> String {
+        let rhs = match condition.rhs {
+            Operand::Register(y) => format!("v{:x}", y),
+            Operand::Byte(nn) => format!("{:#04x}", nn),
+        };
+        let x = condition.x;
+        match (self.format, condition.comparison) {
+            (Format::Octo, Comparison::Eq) => format!("v{:x} == {}", x, rhs),
+            (Format::Octo, Comparison::Ne) => format!("v{:x} != {}", x, rhs),
+            (Format::Octo, Comparison::Key) => format!("v{:x} key", x),
+            (Format::Octo, Comparison::NotKey) => format!("v{:x} -key", x),
+            (Format::C, Comparison::Eq) => format!("v{:x} == {}", x, rhs),
+            (Format::C, Comparison::Ne) => format!("v{:x} != {}", x, rhs),
+            (Format::C, Comparison::Key) => format!("key_pressed(v{:x})", x),
+            (Format::C, Comparison::NotKey) => format!("!key_pressed(v{:x})", x),
+        }
+    }
+
+    fn op(&self, op: Op) -> String {
+        match self.format {
+            Format::Octo => self.octo_op(op),
+            Format::C => self.c_op(op),
+        }
+    }
+
+    fn octo_op(&self, op: Op) -> String {
+        match op {
+            Op::Sys(nnn) => format!("native {:#05x}", nnn),
+            Op::Cls => String::from("clear"),
+            Op::Ret => String::from("return"),
+            Op::Jump(nnn) => format!("jump {}", self.name(nnn as usize)),
+            Op::Call(nnn) => match self.names.get(&(nnn as usize)) {
+                Some(name) => name.clone(),
+                None => format!(":call {:#05x}", nnn),
+            },
+            Op::SetImm(x, nn) => format!("v{:x} := {:#04x}", x, nn),
+            Op::AddImm(x, nn) => format!("v{:x} += {:#04x}", x, nn),
+            Op::Set(x, y) => format!("v{:x} := v{:x}", x, y),
+            Op::Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+            Op::And(x, y) => format!("v{:x} &= v{:x}", x, y),
+            Op::Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+            Op::Add(x, y) => format!("v{:x} += v{:x}", x, y),
+            Op::Sub(x, y) => format!("v{:x} -= v{:x}", x, y),
+            Op::Shr(x, y) => format!("v{:x} >>= v{:x}", x, y),
+            Op::SubN(x, y) => format!("v{:x} =- v{:x}", x, y),
+            Op::Shl(x, y) => format!("v{:x} <<= v{:x}", x, y),
+            Op::SetI(nnn) => format!("i := {}", self.name(nnn as usize)),
+            Op::JumpOffset(_, nnn) => format!("jump0 {}", self.name(nnn as usize)),
+            Op::Rand(x, nn) => format!("v{:x} := random {:#04x}", x, nn),
+            Op::Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            Op::GetDelay(x) => format!("v{:x} := delay", x),
+            Op::WaitKey(x) => format!("v{:x} := key", x),
+            Op::SetDelay(x) => format!("delay := v{:x}", x),
+            Op::SetSound(x) => format!("buzzer := v{:x}", x),
+            Op::AddI(x) => format!("i += v{:x}", x),
+            Op::Font(x) => format!("i := hex v{:x}", x),
+            Op::BigFont(x) => format!("i := bighex v{:x}", x),
+            Op::Bcd(x) => format!("bcd v{:x}", x),
+            Op::Store(x) => format!("save v{:x}", x),
+            Op::Load(x) => format!("load v{:x}", x),
+            // skips only show up here in chains of skips
+            Op::SkipEqImm(..)
+            | Op::SkipNeImm(..)
+            | Op::SkipEqReg(..)
+            | Op::SkipNeReg(..)
+            | Op::SkipKey(_)
+            | Op::SkipNotKey(_) => format!(
+                "if {} then",
+                self.condition(Condition::of_skip(op).map(Condition::negate).unwrap())
+            ),
+            Op::Invalid(instruction) => format!(
+                "{:#04x} {:#04x} {}",
+                instruction >> 8,
+                instruction & 0xff,
+                self.comment("not an instruction")
+            ),
+        }
+    }
+
+    fn c_op(&self, op: Op) -> String {
+        match op {
+            Op::Sys(nnn) => format!("native({:#05x});", nnn),
+            Op::Cls => String::from("clear();"),
+            Op::Ret => String::from("return;"),
+            Op::Jump(nnn) => format!("goto {};", self.name(nnn as usize)),
+            Op::Call(nnn) => format!("{}();", self.name(nnn as usize)),
+            Op::SetImm(x, nn) => format!("v{:x} = {:#04x};", x, nn),
+            Op::AddImm(x, nn) => format!("v{:x} += {:#04x};", x, nn),
+            Op::Set(x, y) => format!("v{:x} = v{:x};", x, y),
+            Op::Or(x, y) => format!("v{:x} |= v{:x};", x, y),
+            Op::And(x, y) => format!("v{:x} &= v{:x};", x, y),
+            Op::Xor(x, y) => format!("v{:x} ^= v{:x};", x, y),
+            Op::Add(x, y) => format!("v{:x} += v{:x}; // vf = carry", x, y),
+            Op::Sub(x, y) => format!("v{:x} -= v{:x}; // vf = no borrow", x, y),
+            Op::Shr(x, y) => format!("v{:x} = v{:x} >> 1; // vf = bit shifted out", x, y),
+            Op::SubN(x, y) => format!("v{:x} = v{:x} - v{:x}; // vf = no borrow", x, y, x),
+            Op::Shl(x, y) => format!("v{:x} = v{:x} << 1; // vf = bit shifted out", x, y),
+            Op::SetI(nnn) => match self.names.get(&(nnn as usize)) {
+                Some(name) => format!("i = {};", name),
+                None => format!("i = {:#05x};", nnn),
+            },
+            Op::JumpOffset(_, nnn) => format!("goto *(v0 + {:#05x});", nnn),
+            Op::Rand(x, nn) => format!("v{:x} = rand() & {:#04x};", x, nn),
+            Op::Draw(x, y, n) => format!("vf = draw(v{:x}, v{:x}, i, {});", x, y, n),
+            Op::GetDelay(x) => format!("v{:x} = delay;", x),
+            Op::WaitKey(x) => format!("v{:x} = wait_key();", x),
+            Op::SetDelay(x) => format!("delay = v{:x};", x),
+            Op::SetSound(x) => format!("sound = v{:x};", x),
+            Op::AddI(x) => format!("i += v{:x};", x),
+            Op::Font(x) => format!("i = font(v{:x});", x),
+            Op::BigFont(x) => format!("i = big_font(v{:x});", x),
+            Op::Bcd(x) => format!("bcd(v{:x}, i);", x),
+            Op::Store(x) => format!("save(v0..=v{:x}, i);", x),
+            Op::Load(x) => format!("load(v0..=v{:x}, i);", x),
+            Op::SkipEqImm(..)
+            | Op::SkipNeImm(..)
+            | Op::SkipEqReg(..)
+            | Op::SkipNeReg(..)
+            | Op::SkipKey(_)
+            | Op::SkipNotKey(_) => format!(
+                "if ({}) skip_next();",
+                self.condition(Condition::of_skip(op).unwrap())
+            ),
+            Op::Invalid(instruction) => format!("invalid({:#06x});", instruction),
+        }
+    }
+
+    fn label(&mut self, addr: usize) {
+        if self.format == Format::C && !self.targets.contains(&addr) {
+            return;
+        }
+        if !self.printed.insert(addr) {
+            return;
+        }
+        if let Some(name) = self.names.get(&addr).cloned() {
+            let depth = std::mem::replace(&mut self.depth, 0);
+            match self.format {
+                Format::Octo => self.line(&format!(": {}", name)),
+                Format::C => self.line(&format!("{}:", name)),
+            }
+            self.depth = depth;
+        }
+    }
+
+    // a statement that doesn't open a block
+    fn simple(&self, stmt: &Stmt) -> String {
+        match (self.format, stmt) {
+            (_, Stmt::Op(_, op)) => self.op(*op),
+            (Format::Octo, Stmt::Continue(_, target) | Stmt::Break(_, target))
+            | (Format::Octo, Stmt::Goto(_, target)) => format!("jump {}", self.name(*target)),
+            (Format::C, Stmt::Continue(..)) => String::from("continue;"),
+            (Format::C, Stmt::Break(..)) => String::from("break;"),
+            (Format::C, Stmt::Goto(_, target)) => format!("goto {};", self.name(*target)),
+            (Format::Octo, Stmt::Halt(_)) => String::from("loop again"),
+            (Format::C, Stmt::Halt(_)) => String::from("for (;;) {}"),
+            _ => String::new(),
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.label(stmt.addr());
+            match stmt {
+                Stmt::IfThen(_, condition, then) => {
+                    let line = match self.format {
+                        Format::Octo => format!(
+                            "if {} then {}",
+                            self.condition(*condition),
+                            self.simple(then)
+                        ),
+                        Format::C => {
+                            format!("if ({}) {}", self.condition(*condition), self.simple(then))
+                        }
+                    };
+                    self.line(&line);
+                }
+                Stmt::If {
+                    condition,
+                    then,
+                    otherwise,
+                    ..
+                } => {
+                    let (open, middle, close) = match self.format {
+                        Format::Octo => (
+                            format!("if {} begin", self.condition(*condition)),
+                            "else",
+                            "end",
+                        ),
+                        Format::C => (
+                            format!("if ({}) {{", self.condition(*condition)),
+                            "} else {",
+                            "}",
+                        ),
+                    };
+                    self.line(&open);
+                    self.depth += 1;
+                    self.stmts(then);
+                    self.depth -= 1;
+                    if !otherwise.is_empty() {
+                        self.line(middle);
+                        self.depth += 1;
+                        self.stmts(otherwise);
+                        self.depth -= 1;
+                    }
+                    self.line(close);
+                }
+                Stmt::Loop(_, body) => {
+                    let (open, close) = match self.format {
+                        Format::Octo => ("loop", "again"),
+                        Format::C => ("for (;;) {", "}"),
+                    };
+                    self.line(open);
+                    self.depth += 1;
+                    self.stmts(body);
+                    self.depth -= 1;
+                    self.line(close);
+                }
+                Stmt::While(_, condition) => {
+                    let line = match self.format {
+                        Format::Octo => format!("while {}", self.condition(*condition)),
+                        Format::C => {
+                            format!("if ({}) break;", self.condition(condition.negate()))
+                        }
+                    };
+                    self.line(&line);
+                }
+                _ => {
+                    let line = self.simple(stmt);
+                    self.line(&line);
+                }
+            }
+        }
+    }
+}
+
+pub fn decompile(program: &[u8], start: usize, format: Format) -> String {
+    let cfg = Cfg::build(program, start);
+    let code_bytes = cfg.code_bytes();
+
+    // structure every subroutine first to learn which labels are needed
+    let mut structured = Vec::new();
+    let mut gotos = BTreeSet::new();
+    let mut loop_jumps = BTreeSet::new();
+    let mut data_pointers = BTreeSet::new();
+    for subroutine in cfg.subroutines.values() {
+        let mut code = subroutine
+            .blocks
+            .iter()
+            .flat_map(|addr| &cfg.blocks[addr].instructions)
+            .map(|&(addr, instruction)| (addr, Op::decode(instruction)))
+            .collect::<Vec<(usize, Op)>>();
+        code.sort_by_key(|&(addr, _)| addr);
+        code.dedup_by_key(|&mut (addr, _)| addr);
+        for &(_, op) in &code {
+            if let Op::SetI(nnn) | Op::JumpOffset(_, nnn) = op {
+                data_pointers.insert(nnn as usize);
+            }
+        }
+        let mut structurer = Structurer::new(code);
+        let stmts = structurer.structure(0, structurer.code.len(), None, false);
+        gotos.extend(&structurer.gotos);
+        loop_jumps.extend(&structurer.loop_jumps);
+        structured.push((subroutine.entry, structurer.code, stmts));
+    }
+
+    // a summary can only be as deep as the call chain under it, so this settles unless the rom
+    // recurses, which is cut off after as many rounds as there are subroutines
+    let mut usages = BTreeMap::new();
+    for _ in 0..=structured.len() {
+        let before = usages.clone();
+        for (entry, code, _) in &structured {
+            let found = usage(code, &usages);
+            usages.insert(*entry, found);
+        }
+        if usages == before {
+            break;
+        }
+    }
+
+    // code can only be labelled where a statement starts, anything pointing elsewhere into it
+    // keeps its address
+    let mut statements = BTreeSet::new();
+    for (_, _, stmts) in &structured {
+        collect_addrs(stmts, &mut statements);
+    }
+    let mut names = BTreeMap::new();
+    let labels = gotos.iter().chain(&loop_jumps).chain(&data_pointers);
+    for &addr in labels.filter(|&&a| a >= start && a < cfg.end) {
+        if !code_bytes.contains(&addr) {
+            names.insert(addr, format!("data_{:03x}", addr));
+        } else if statements.contains(&addr) {
+            names.insert(addr, format!("label_{:03x}", addr));
+        }
+    }
+    for &entry in cfg.subroutines.keys() {
+        let name = match entry == start {
+            true => String::from("main"),
+            false => format!("sub_{:03x}", entry),
+        };
+        names.insert(entry, name);
+    }
+
+    let mut printer = Printer {
+        format,
+        out: String::new(),
+        depth: 0,
+        names: &names,
+        targets: &gotos,
+        printed: BTreeSet::new(),
+    };
+    let header = printer.comment(&format!(
+        "decompiled from {} bytes loaded at {:#05x}",
+        program.len(),
+        start
+    ));
+    printer.line(&header);
+    for (entry, _, stmts) in &structured {
+        let usage = usages[entry];
+        printer.line("");
+        let summary = printer.comment(&format!(
+            "inputs: {}, writes: {}",
+            register_list(usage.inputs, usage.reads_i),
+            register_list(usage.writes, usage.writes_i),
+        ));
+        printer.line(&summary);
+        match format {
+            Format::Octo => {
+                printer.label(*entry);
+                printer.depth = 1;
+                printer.stmts(stmts);
+                printer.depth = 0;
+            }
+            Format::C => {
+                printer.line(&format!("void {}(void) {{", printer.name(*entry)));
+                printer.depth = 1;
+                printer.stmts(stmts);
+                printer.depth = 0;
+                printer.line("}");
+            }
+        }
+    }
+
+    // everything that isn't code, split where it is pointed at
+    let mut addr = start;
+    while addr < cfg.end {
+        if code_bytes.contains(&addr) {
+            addr += 1;
+            continue;
+        }
+        let run_end = (addr + 1..cfg.end)
+            .find(|a| code_bytes.contains(a) || names.contains_key(a))
+            .unwrap_or(cfg.end);
+        printer.line("");
+        let name = names
+            .get(&addr)
+            .cloned()
+            .unwrap_or_else(|| format!("data_{:03x}", addr));
+        let bytes = &program[addr - start..run_end - start];
+        match format {
+            Format::Octo => {
+                printer.line(&format!(": {}", name));
+                for chunk in bytes.chunks(DATA_PER_LINE) {
+                    let line = chunk
+                        .iter()
+                        .map(|b| format!("{:#04x}", b))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    printer.line(&format!("\t{}", line));
+                }
+            }
+            Format::C => {
+                printer.line(&format!("const uint8_t {}[] = {{", name));
+                for chunk in bytes.chunks(DATA_PER_LINE) {
+                    let line = chunk
+                        .iter()
+                        .map(|b| format!("{:#04x},", b))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    printer.line(&format!("\t{}", line));
+                }
+                printer.line("};");
+            }
+        }
+        addr = run_end;
+    }
+    printer.out
+}
+
+pub fn run(src: &str, load_address: usize, format: Format) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    print!("{}", decompile(&program, load_address, format));
+    Ok(())
+}
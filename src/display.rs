@@ -0,0 +1,83 @@
+// the 64x32 monochrome display, one u64 per row with the leftmost pixel in the most significant
+// bit, so a sprite byte can be drawn into a row with a single shift and xor.
+// rows that changed are remembered until take_dirty so the scaler can skip the rest
+use crate::cpu::{HEIGHT, WIDTH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    rows: [u64; HEIGHT],
+    // bit y is set when row y changed
+    dirty: u64,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display::new()
+    }
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            rows: [0; HEIGHT],
+            // everything has to be drawn once
+            dirty: (1 << HEIGHT) - 1,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for (y, row) in self.rows.iter_mut().enumerate() {
+            if *row != 0 {
+                *row = 0;
+                self.dirty |= 1 << y;
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << (WIDTH - 1 - x)) != 0
+    }
+
+    #[cfg(test)]
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let bit = 1 << (WIDTH - 1 - x);
+        if on {
+            self.rows[y] |= bit;
+        } else {
+            self.rows[y] &= !bit;
+        }
+        self.dirty |= 1 << y;
+    }
+
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    // xors one byte of sprite data into row y starting at column x (both already on screen).
+    // the part past the right edge wraps around unless clip is set.
+    // returns true if any pixel was turned off
+    pub fn draw_byte(&mut self, x: usize, y: usize, byte: u8, clip: bool) -> bool {
+        let bits = (byte as u64) << (WIDTH - 8);
+        let bits = if clip {
+            bits >> x
+        } else {
+            bits.rotate_right(x as u32)
+        };
+        if bits == 0 {
+            return false;
+        }
+        let collision = self.rows[y] & bits != 0;
+        self.rows[y] ^= bits;
+        self.dirty |= 1 << y;
+        collision
+    }
+
+    pub fn mark_dirty(&mut self, rows: u64) {
+        self.dirty |= rows;
+    }
+
+    // the rows changed since the last call, bit y for row y
+    pub fn take_dirty(&mut self) -> u64 {
+        std::mem::take(&mut self.dirty)
+    }
+}
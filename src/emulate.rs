@@ -1,19 +1,102 @@
-use crate::chip8::Chip8;
-use std::fs::read;
+use crate::{
+    chip8::{Chip8, Config},
+    font::Font,
+    heuristics,
+    loader::{self, RomOptions},
+    romdb::RomDb,
+};
 
-fn read_bytecode(path: &String) -> Result<Vec<u8>, std::io::Error> {
-    let data = read(path)?;
+pub fn emulate(
+    src: String,
+    mut config: Config,
+    explicit: &[String],
+    rom_db: Option<&RomDb>,
+) -> Result<(), std::io::Error> {
+    let rom = loader::load_rom(&src)?;
 
-    if data.len() % 2 != 0 {
-        dbg!("Program file has odd number of bytes");
+    if rom.program.len() % 2 != 0 {
+        eprintln!(
+            "Warning: {} has an odd number of bytes ({}), the last instruction is incomplete",
+            src,
+            rom.program.len()
+        );
+    }
+    // settings from the database come first so a cartridge can override them
+    if let Some(info) = rom_db.and_then(|db| db.lookup(&rom.program)) {
+        println!(
+            "Found {} ({}) in the rom database",
+            info.title,
+            info.platform.as_deref().unwrap_or("unknown platform")
+        );
+        for (action, key) in &info.key_hints {
+            println!("  {} -> chip8 key {:X}", action, key);
+        }
+        for quirk in &info.unsupported_quirks {
+            eprintln!(
+                "Warning: the rom database asks for the {} quirk, which isn't supported",
+                quirk
+            );
+        }
+        config.title = Some(info.title.clone());
+        apply_options(&mut config, info.options.clone(), explicit);
+    } else if rom.options.as_ref().is_none_or(|o| o.quirks.is_none()) {
+        guess_quirks(&mut config, &rom.program, explicit);
+    }
+    if let Some(options) = rom.options {
+        apply_options(&mut config, options, explicit);
+    }
+    if config.debug {
+        println!(
+            "running with tickrate {}, quirks {:?}, palette {:?}",
+            config.tickrate, config.quirks, config.palette
+        );
     }
 
-    Ok(data)
-}
-
-pub fn emulate(src: String, chip8: &mut Chip8) -> Result<(), std::io::Error> {
-    let data = read_bytecode(&src)?;
-    chip8.add_program(&data)?;
+    let mut chip8 = Chip8::new(config)?;
+    chip8.add_named_program(&src, &rom.program)?;
     chip8.run();
     Ok(())
 }
+
+fn guess_quirks(config: &mut Config, program: &[u8], explicit: &[String]) {
+    let detection = heuristics::detect(program, config.memory_map.load_address);
+    if config.debug {
+        println!("quirk detection:");
+        for reason in &detection.reasons {
+            println!("  {}", reason);
+        }
+    }
+    if detection.quirks.is_some() && !explicit.iter().any(|e| e == "quirks") {
+        println!(
+            "Unknown rom, guessed {} quirks (use --quirks to override)",
+            detection.platform.unwrap_or("chip8")
+        );
+    }
+    let options = RomOptions {
+        quirks: detection.quirks,
+        font: detection.platform.and_then(Font::for_platform),
+        ..RomOptions::default()
+    };
+    apply_options(config, options, explicit);
+}
+
+// options from the rom replace the defaults but not flags passed on the command line
+fn apply_options(config: &mut Config, options: RomOptions, explicit: &[String]) {
+    let is_explicit = |id: &str| explicit.iter().any(|e| e == id);
+
+    if let Some(tickrate) = options.tickrate.filter(|_| !is_explicit("tickrate")) {
+        config.tickrate = tickrate;
+    }
+    if let Some(quirks) = options.quirks.filter(|_| !is_explicit("quirks")) {
+        config.quirks = quirks;
+    }
+    if let Some(palette) = options.palette.filter(|_| !is_explicit("palette")) {
+        config.palette = palette;
+    }
+    if let Some(keymap) = options.keymap {
+        config.keymap = keymap;
+    }
+    if let Some(font) = options.font.filter(|_| !is_explicit("font")) {
+        config.font = font;
+    }
+}
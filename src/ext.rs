@@ -1,13 +1,15 @@
+use crate::palette::Palette;
+
 pub trait ToARGB {
-    fn to_argb(&self) -> u32;
+    fn to_argb(&self, palette: &Palette) -> u32;
 }
 
 impl ToARGB for u8 {
     #[inline]
-    fn to_argb(&self) -> u32 {
+    fn to_argb(&self, palette: &Palette) -> u32 {
         match self {
-            1u8 => u32::MAX,
-            _ => 0u32,
+            1u8 => palette.foreground,
+            _ => palette.background,
         }
     }
 }
@@ -0,0 +1,217 @@
+// the hex digit sprites interpreters keep in memory for 0xFX29 (and 0xFX30 for the big SCHIP
+// digits). every interpreter drew them a little differently and some roms depend on the exact
+// shapes, eg to measure text or to reuse the font bytes as data
+use std::{fs, io::Error, io::ErrorKind, path::Path};
+
+pub const SMALL_GLYPH_SIZE: usize = 5;
+pub const BIG_GLYPH_SIZE: usize = 10;
+pub const SMALL_FONT_SIZE: usize = 16 * SMALL_GLYPH_SIZE;
+pub const BIG_FONT_SIZE: usize = 16 * BIG_GLYPH_SIZE;
+// memory kept free for a font, the big digits follow the small ones
+pub const FONT_SIZE: usize = SMALL_FONT_SIZE + BIG_FONT_SIZE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    // 16 glyphs of 5 bytes
+    pub small: Vec<u8>,
+    // 16 glyphs of 10 bytes, only SCHIP and later interpreters have them
+    pub big: Option<Vec<u8>>,
+}
+
+// the COSMAC VIP interpreter
+const VIP: [u8; SMALL_FONT_SIZE] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0xa0, 0xa0, 0xf0, 0x20, 0x20, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xf0, 0x50, 0x70, 0x50, 0xf0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xf0, 0x50, 0x50, 0x50, 0xf0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
+// CHIP-48 on the HP48, SCHIP and octo kept the same small digits
+const CHIP48: [u8; SMALL_FONT_SIZE] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0x90, 0x90, 0xf0, 0x10, 0x10, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xe0, 0x90, 0xe0, 0x90, 0xe0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xe0, 0x90, 0x90, 0x90, 0xe0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
+// SCHIP 1.1 only has big digits for 0-9, A-F are left empty
+const SCHIP_BIG: [u8; BIG_FONT_SIZE] = [
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, // 1
+    0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, // 2
+    0x3c, 0x7e, 0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, // 3
+    0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff, 0x06, 0x06, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, // 5
+    0x3e, 0x7c, 0xc0, 0xc0, 0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, // 6
+    0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, // 8
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f, 0x03, 0x03, 0x3e, 0x7c, // 9
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // A
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // B
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // C
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // D
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // E
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // F
+];
+
+const OCTO_BIG: [u8; BIG_FONT_SIZE] = [
+    0xff, 0xff, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xff, 0xff, // 1
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // 2
+    0xff, 0xff, 0x03, 0x03, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 3
+    0xc3, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0x03, 0x03, // 4
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 5
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 6
+    0xff, 0xff, 0x03, 0x03, 0x06, 0x0c, 0x18, 0x18, 0x18, 0x18, // 7
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, // 8
+    0xff, 0xff, 0xc3, 0xc3, 0xff, 0xff, 0x03, 0x03, 0xff, 0xff, // 9
+    0x7e, 0xff, 0xc3, 0xc3, 0xc3, 0xff, 0xff, 0xc3, 0xc3, 0xc3, // A
+    0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, 0xc3, 0xc3, 0xfc, 0xfc, // B
+    0x3c, 0xff, 0xc3, 0xc0, 0xc0, 0xc0, 0xc0, 0xc3, 0xff, 0x3c, // C
+    0xfc, 0xfe, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xc3, 0xfe, 0xfc, // D
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, // E
+    0xff, 0xff, 0xc0, 0xc0, 0xff, 0xff, 0xc0, 0xc0, 0xc0, 0xc0, // F
+];
+
+pub const PRESET_NAMES: [&str; 4] = ["vip", "chip48", "schip", "octo"];
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::preset("chip48").expect("chip48 is a preset")
+    }
+}
+
+impl Font {
+    pub fn preset(name: &str) -> Option<Font> {
+        let (small, big) = match name.to_ascii_lowercase().as_str() {
+            "vip" => (VIP, None),
+            "chip48" => (CHIP48, None),
+            "schip" => (CHIP48, Some(SCHIP_BIG)),
+            "octo" => (CHIP48, Some(OCTO_BIG)),
+            _ => return None,
+        };
+        Some(Font {
+            small: small.to_vec(),
+            big: big.map(|big| big.to_vec()),
+        })
+    }
+
+    // the font the interpreter a quirks preset or database platform stands for shipped with
+    pub fn for_platform(platform: &str) -> Option<Font> {
+        match platform {
+            "chip8" | "originalChip8" | "hybridVIP" => Font::preset("vip"),
+            "chip48" => Font::preset("chip48"),
+            "schip" | "superchip1" | "superchip" => Font::preset("schip"),
+            "xochip" | "modernChip8" => Font::preset("octo"),
+            _ => None,
+        }
+    }
+
+    // a preset name or the path to a custom font
+    pub fn parse(value: &str) -> Result<Font, String> {
+        match Font::preset(value) {
+            Some(font) => Ok(font),
+            None => Font::load(Path::new(value)).map_err(|e| {
+                format!(
+                    "\"{}\" is not one of {} or a font file: {}",
+                    value,
+                    PRESET_NAMES.join(", "),
+                    e
+                )
+            }),
+        }
+    }
+
+    // binary fonts are the 80 small bytes, optionally followed by the 160 big ones.
+    // png fonts are 16 glyphs side by side, 8 pixels per glyph: 5 rows tall for a small font or 15
+    // for a small font with the big glyphs (which may be 8 pixels wide) in the 10 rows under it
+    pub fn load(path: &Path) -> Result<Font, Error> {
+        let data = fs::read(path)?;
+        if data.starts_with(b"\x89PNG") {
+            return from_png(&data);
+        }
+        match data.len() {
+            SMALL_FONT_SIZE => Ok(Font {
+                small: data,
+                big: None,
+            }),
+            FONT_SIZE => Ok(Font {
+                small: data[..SMALL_FONT_SIZE].to_vec(),
+                big: Some(data[SMALL_FONT_SIZE..].to_vec()),
+            }),
+            len => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "binary fonts have to be {} or {} bytes, this one is {}",
+                    SMALL_FONT_SIZE, FONT_SIZE, len
+                ),
+            )),
+        }
+    }
+}
+
+fn from_png(data: &[u8]) -> Result<Font, Error> {
+    let invalid = |reason: String| Error::new(ErrorKind::InvalidData, reason);
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| invalid(format!("corrupt png: {}", e)))?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| invalid(format!("corrupt png: {}", e)))?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    if width != 16 * 8 || !matches!(height, 5 | 15) {
+        return Err(invalid(format!(
+            "png fonts have to be 128x5 or 128x15 pixels, this one is {}x{}",
+            width, height
+        )));
+    }
+    let channels = info.color_type.samples();
+    // anything brighter than half is a lit pixel, alpha is ignored
+    let lit = |x: usize, y: usize| {
+        let pixel = &buf[y * info.line_size + x * channels..];
+        let brightness = match channels {
+            1 | 2 => pixel[0] as u32,
+            _ => (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3,
+        };
+        brightness > 127
+    };
+    let glyphs = |rows: std::ops::Range<usize>| {
+        (0..16)
+            .flat_map(|glyph| rows.clone().map(move |y| (glyph, y)))
+            .map(|(glyph, y)| (0..8).fold(0u8, |byte, x| byte << 1 | lit(glyph * 8 + x, y) as u8))
+            .collect::<Vec<u8>>()
+    };
+
+    Ok(Font {
+        small: glyphs(0..5),
+        big: (height == 15).then(|| glyphs(5..15)),
+    })
+}
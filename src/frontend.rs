@@ -0,0 +1,119 @@
+use minifb::{Key, Window, WindowOptions};
+
+use crate::{
+    cpu::{HEIGHT, WIDTH},
+    display::Display,
+    machine::Snapshot,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontendKind {
+    Window,
+    Terminal,
+    Headless,
+}
+
+// something that can show the display and collect key presses for Chip8::run. realtime frontends
+// are used from a different thread than the one running the cpu, so they only see snapshots.
+// keys are reported as minifb keys so the keypad mapping in keyboard.rs and the hotkeys work the
+// same regardless of where they came from
+pub trait Frontend {
+    // called after the display buffer changed. scaled is the window sized 0RGB buffer
+    fn draw(&mut self, display: &Display, scaled: &[u32]);
+    // called instead of draw when nothing changed, so a window keeps handling its events
+    fn idle(&mut self) {}
+    // called once at the end of every emulated frame
+    fn end_frame(&mut self, _state: &Snapshot) {}
+    fn keys_pressed(&mut self) -> Vec<Key>;
+    fn is_open(&self) -> bool;
+    // status messages like saved screenshots, printed unless the frontend has the terminal
+    fn message(&mut self, text: String) {
+        println!("{}", text);
+    }
+    fn error(&mut self, text: String) {
+        eprintln!("{}", text);
+    }
+    // frames are paced to 60 per second when true, otherwise they run as fast as possible
+    fn realtime(&self) -> bool {
+        true
+    }
+}
+
+pub struct WindowFrontend {
+    window: Window,
+    scale: usize,
+}
+
+impl WindowFrontend {
+    pub fn new(scale: usize, title: Option<&str>) -> WindowFrontend {
+        let title = match title {
+            Some(title) => format!("CHIP8 - {}", title),
+            None => String::from("CHIP8"),
+        };
+        let window = Window::new(
+            &title,
+            WIDTH * scale,
+            HEIGHT * scale,
+            WindowOptions::default(),
+        )
+        .expect("failed to create a window");
+        WindowFrontend { window, scale }
+    }
+}
+
+impl Frontend for WindowFrontend {
+    fn draw(&mut self, _display: &Display, scaled: &[u32]) {
+        self.window
+            .update_with_buffer(scaled, WIDTH * self.scale, HEIGHT * self.scale)
+            .expect("Failed to draw window");
+    }
+
+    fn idle(&mut self) {
+        self.window.update();
+    }
+
+    fn keys_pressed(&mut self) -> Vec<Key> {
+        self.window.get_keys_pressed(minifb::KeyRepeat::Yes)
+    }
+
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+}
+
+// shows nothing and replays scripted key presses, used for tests and runs without a display
+pub struct HeadlessFrontend {
+    // (frame, key) pairs, each key is reported as pressed during that frame
+    script: Vec<(u64, Key)>,
+    frame: u64,
+}
+
+impl HeadlessFrontend {
+    pub fn new(script: Vec<(u64, Key)>) -> HeadlessFrontend {
+        HeadlessFrontend { script, frame: 0 }
+    }
+}
+
+impl Frontend for HeadlessFrontend {
+    fn draw(&mut self, _display: &Display, _scaled: &[u32]) {}
+
+    fn end_frame(&mut self, _state: &Snapshot) {
+        self.frame += 1;
+    }
+
+    fn keys_pressed(&mut self) -> Vec<Key> {
+        self.script
+            .iter()
+            .filter(|(frame, _)| *frame == self.frame)
+            .map(|(_, key)| *key)
+            .collect()
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    fn realtime(&self) -> bool {
+        false
+    }
+}
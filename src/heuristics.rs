@@ -0,0 +1,256 @@
+// guesses the platform and quirks of roms that aren't in the rom database by looking at the
+// instructions in the program. nothing is executed, only instructions that can be reached by
+// following jumps, calls and skips from the load address are looked at so sprite data isn't mistaken for
+// code. jumps through BNNN can't be followed, so this is still a best guess
+use crate::quirks::{self, Quirks};
+
+// how far after an instruction to look for the code that depends on it
+const WINDOW: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    pub platform: Option<&'static str>,
+    // None when nothing in the program hints at a quirk
+    pub quirks: Option<Quirks>,
+    // why the platform and quirks were chosen, printed with --debug
+    pub reasons: Vec<String>,
+}
+
+pub fn detect(program: &[u8], load_address: usize) -> Detection {
+    let words = program
+        .chunks_exact(2)
+        .map(|w| (w[0] as u16) << 8 | w[1] as u16)
+        .collect::<Vec<u16>>();
+    let addr = |index: usize| load_address + index * 2;
+    let mut reasons = Vec::new();
+    let reachable = reachable(&words, load_address);
+    // only reachable instructions, the rest is replaced with 0x0000 which means nothing here
+    let words = words
+        .iter()
+        .zip(&reachable)
+        .map(|(&op, &reachable)| if reachable { op } else { 0 })
+        .collect::<Vec<u16>>();
+
+    let xochip = words.iter().enumerate().find(|(_, &op)| is_xochip_only(op));
+    let schip = words.iter().enumerate().find(|(_, &op)| is_schip_only(op));
+    let platform = if let Some((index, op)) = xochip {
+        reasons.push(format!(
+            "{:04X} at {:#05x} only exists on XO-CHIP",
+            op,
+            addr(index)
+        ));
+        Some("xochip")
+    } else if let Some((index, op)) = schip {
+        reasons.push(format!(
+            "{:04X} at {:#05x} only exists on SUPER-CHIP",
+            op,
+            addr(index)
+        ));
+        Some("schip")
+    } else {
+        None
+    };
+
+    let mut quirks = match platform {
+        Some("xochip") => Some(quirks::XOCHIP),
+        Some("schip") => Some(quirks::SCHIP),
+        _ => None,
+    };
+
+    if let Some(increments) = load_store_evidence(&words, load_address, &mut reasons) {
+        quirks
+            .get_or_insert(quirks::DEFAULT)
+            .load_store_increments_i = increments;
+    }
+    if let Some(uses_vx) = jump_evidence(&words, load_address, &mut reasons) {
+        quirks.get_or_insert(quirks::DEFAULT).jump_uses_vx = uses_vx;
+    }
+    if quirks.is_none() {
+        reasons.push(String::from(
+            "nothing platform specific found, keeping the default quirks",
+        ));
+    }
+
+    Detection {
+        platform,
+        quirks,
+        reasons,
+    }
+}
+
+// marks every word that execution can get to from the start of the program
+fn reachable(words: &[u16], load_address: usize) -> Vec<bool> {
+    let mut seen = vec![false; words.len()];
+    let mut pending = vec![0usize];
+    let index_of = |addr: u16| {
+        (addr as usize)
+            .checked_sub(load_address)
+            .map(|offset| offset / 2)
+    };
+
+    while let Some(index) = pending.pop() {
+        if index >= words.len() || seen[index] {
+            continue;
+        }
+        seen[index] = true;
+        let op = words[index];
+        let target = index_of(op & 0xfff).filter(|_| op & 1 == 0);
+        match op & 0xf000 {
+            0x1000 => pending.extend(target),
+            0x2000 => {
+                pending.extend(target);
+                pending.push(index + 1);
+            }
+            // the target depends on a register
+            0xb000 => {}
+            0x3000 | 0x4000 | 0x5000 | 0x9000 => pending.extend([index + 1, index + 2]),
+            0xe000 if matches!(op & 0xff, 0x9e | 0xa1) => pending.extend([index + 1, index + 2]),
+            // F000 NNNN is 4 bytes long
+            0xf000 if op == 0xf000 => pending.push(index + 2),
+            _ if op == 0x00ee || op == 0x00fd => {}
+            _ => pending.push(index + 1),
+        }
+    }
+    seen
+}
+
+fn is_schip_only(op: u16) -> bool {
+    matches!(op, 0x00fb..=0x00ff)
+        || op & 0xfff0 == 0x00c0 && op != 0x00c0
+        || op & 0xf00f == 0xd000
+        || matches!(op & 0xf0ff, 0xf030 | 0xf075 | 0xf085)
+}
+
+fn is_xochip_only(op: u16) -> bool {
+    op == 0xf000
+        || op & 0xfff0 == 0x00d0 && op != 0x00d0
+        || matches!(op & 0xf00f, 0x5002 | 0x5003)
+        || matches!(op & 0xf0ff, 0xf001 | 0xf002 | 0xf03a)
+}
+
+fn is_load_store(op: u16) -> bool {
+    matches!(op & 0xf0ff, 0xf055 | 0xf065)
+}
+
+// instructions that read or write memory through I
+fn uses_i(op: u16) -> bool {
+    op & 0xf000 == 0xd000 || matches!(op & 0xf0ff, 0xf033 | 0xf055 | 0xf065)
+}
+
+fn sets_i(op: u16) -> bool {
+    op & 0xf000 == 0xa000 || matches!(op & 0xf0ff, 0xf029 | 0xf030)
+}
+
+// stops looking ahead at anything that changes the flow of the program
+fn ends_block(op: u16) -> bool {
+    op == 0x00ee || matches!(op & 0xf000, 0x1000 | 0x2000 | 0xb000)
+}
+
+// a program that keeps using I right after FX55/FX65 expects it to have moved past the saved
+// registers. one that adds to I with FX1E first expects it to stay where it was
+fn load_store_evidence(
+    words: &[u16],
+    load_address: usize,
+    reasons: &mut Vec<String>,
+) -> Option<bool> {
+    let (mut increments, mut unchanged) = (0, 0);
+    let mut first = [None, None];
+
+    for (index, &op) in words.iter().enumerate() {
+        if !is_load_store(op) {
+            continue;
+        }
+        for (offset, &next) in words[index + 1..].iter().take(WINDOW).enumerate() {
+            if sets_i(next) || ends_block(next) {
+                break;
+            }
+            let evidence = if next & 0xf0ff == 0xf01e {
+                1
+            } else if uses_i(next) {
+                0
+            } else {
+                continue;
+            };
+            if evidence == 0 {
+                increments += 1;
+            } else {
+                unchanged += 1;
+            }
+            first[evidence].get_or_insert((index, index + 1 + offset, op, next));
+            break;
+        }
+    }
+
+    let describe = |(index, next_index, op, next): (usize, usize, u16, u16)| {
+        format!(
+            "{:04X} at {:#05x} is followed by {:04X} at {:#05x}",
+            op,
+            load_address + index * 2,
+            next,
+            load_address + next_index * 2
+        )
+    };
+    match (increments, unchanged) {
+        (0, 0) => None,
+        (increments, unchanged) if increments >= unchanged => {
+            reasons.push(format!(
+                "{} without setting I again, so FX55/FX65 should leave I after the registers (seen {} times)",
+                describe(first[0]?),
+                increments
+            ));
+            Some(true)
+        }
+        (_, unchanged) => {
+            reasons.push(format!(
+                "{} which moves I by hand, so FX55/FX65 should leave I unchanged (seen {} times)",
+                describe(first[1]?),
+                unchanged
+            ));
+            Some(false)
+        }
+    }
+}
+
+// BNNN adds V0, BXNN with the quirk adds VX. whichever register was set just before the jump
+// tells which one the program expects
+fn jump_evidence(words: &[u16], load_address: usize, reasons: &mut Vec<String>) -> Option<bool> {
+    for (index, &op) in words.iter().enumerate() {
+        if op & 0xf000 != 0xb000 {
+            continue;
+        }
+        let x = (op >> 8 & 0xf) as usize;
+        let set = words[index.saturating_sub(WINDOW)..index]
+            .iter()
+            .rev()
+            .find_map(|&prev| written_register(prev));
+        let verdict = match set {
+            Some(0) => Some((false, 0)),
+            Some(reg) if reg == x => Some((true, reg)),
+            _ => None,
+        };
+        if let Some((uses_vx, reg)) = verdict {
+            reasons.push(format!(
+                "V{:X} is set right before {:04X} at {:#05x}, so it jumps {}",
+                reg,
+                op,
+                load_address + index * 2,
+                if uses_vx {
+                    "relative to VX"
+                } else {
+                    "relative to V0"
+                }
+            ));
+            return Some(uses_vx);
+        }
+    }
+    None
+}
+
+fn written_register(op: u16) -> Option<usize> {
+    let x = (op >> 8 & 0xf) as usize;
+    match op & 0xf000 {
+        0x6000 | 0x7000 | 0x8000 | 0xc000 => Some(x),
+        0xf000 if matches!(op & 0xff, 0x07 | 0x0a) => Some(x),
+        _ => None,
+    }
+}
@@ -0,0 +1,216 @@
+// what can be learned about a rom without running it: checksums, whether the rom database knows
+// it, the platform the heuristics guess, which instructions its reachable code uses, how it starts
+// and the text and sprites it seems to contain. sprites are found by following ANNN into DXYN
+// inside a block, strings are runs of printable ascii outside the reachable code
+use std::{collections::BTreeMap, io::Error};
+
+use flate2::Crc;
+
+use crate::{
+    cfg::{Cfg, Known},
+    heuristics, loader,
+    memory_map::{MemoryMap, PRESETS},
+    opcode::Op,
+    romdb::{self, RomDb},
+};
+
+// instructions of the entry point that are disassembled
+const ENTRY_INSTRUCTIONS: usize = 16;
+
+// shortest run of printable characters reported as a string
+const MIN_STRING_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    pub address: usize,
+    // 8 or 16 for the 16x16 sprites drawn by DXY0
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Sprite {
+    // bytes of sprite data
+    pub fn bytes(&self) -> usize {
+        self.width / 8 * self.height
+    }
+}
+
+// everything drawn from an address known at the time of the DXYN, by address. the biggest draw
+// from an address decides the size of its sprite
+pub fn find_sprites(cfg: &Cfg, memory_map: &MemoryMap) -> Vec<Sprite> {
+    let mut sprites: BTreeMap<usize, Sprite> = BTreeMap::new();
+    for block in cfg.blocks.values() {
+        let mut known = Known::new();
+        for &(_, instruction) in &block.instructions {
+            let op = Op::decode(instruction);
+            if let (Op::Draw(_, _, n), Some(address)) = (op, known.i) {
+                let (width, height) = if n == 0 { (16, 16) } else { (8, n as usize) };
+                let sprite = Sprite {
+                    address,
+                    width,
+                    height,
+                };
+                if address >= cfg.start && address + sprite.bytes() <= cfg.end {
+                    let found = sprites.entry(address).or_insert(sprite);
+                    if sprite.bytes() > found.bytes() {
+                        *found = sprite;
+                    }
+                }
+            }
+            known.apply(op, memory_map);
+        }
+    }
+    sprites.into_values().collect()
+}
+
+// (address, text) of printable ascii runs that aren't instructions
+pub fn find_strings(program: &[u8], cfg: &Cfg) -> Vec<(usize, String)> {
+    let code = cfg.code_bytes();
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    for (offset, &byte) in program.iter().enumerate().chain([(program.len(), &0)]) {
+        let addr = cfg.start + offset;
+        if (0x20..0x7f).contains(&byte) && !code.contains(&addr) {
+            current.push(byte as char);
+            continue;
+        }
+        if current.trim().len() >= MIN_STRING_LEN {
+            strings.push((addr - current.len(), current.clone()));
+        }
+        current.clear();
+    }
+    strings
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+pub fn run(src: &str, memory_map: &MemoryMap) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    let start = memory_map.load_address;
+    let cfg = Cfg::build(&program, start);
+
+    println!("rom:       {}", src);
+    println!(
+        "size:      {} bytes{}",
+        program.len(),
+        if program.len() % 2 != 0 {
+            ", odd so the last instruction is incomplete"
+        } else {
+            ""
+        }
+    );
+    println!("sha1:      {}", romdb::sha1_hex(&program));
+    println!("crc32:     {:08x}", crc32(&program));
+    match RomDb::builtin().lookup(&program) {
+        Some(info) => println!(
+            "database:  {} ({})",
+            info.title,
+            info.platform.as_deref().unwrap_or("unknown platform")
+        ),
+        None => println!("database:  not in the built in rom database"),
+    }
+    let detection = heuristics::detect(&program, start);
+    println!(
+        "platform:  {}",
+        detection
+            .platform
+            .unwrap_or("chip8, nothing hints at another platform")
+    );
+    for reason in &detection.reasons {
+        println!("           {}", reason);
+    }
+    let fits = PRESETS
+        .iter()
+        .map(|(name, map)| {
+            let max = map.max_program_size();
+            if program.len() <= max {
+                format!("{} ({} bytes free)", name, max - program.len())
+            } else {
+                format!("not {} ({} bytes too big)", name, program.len() - max)
+            }
+        })
+        .collect::<Vec<String>>();
+    println!("fits:      {}", fits.join(", "));
+    println!(
+        "code:      {} reachable bytes in {} blocks and {} subroutines{}",
+        cfg.code_bytes().len(),
+        cfg.blocks.len(),
+        cfg.subroutines.len().saturating_sub(1),
+        if cfg.indirect_jumps().is_empty() {
+            ""
+        } else {
+            ", more behind indirect jumps"
+        }
+    );
+
+    println!("\nentry point:");
+    let mut addr = start;
+    for _ in 0..ENTRY_INSTRUCTIONS {
+        let Some(bytes) = program.get(addr - start..addr - start + 2) else {
+            break;
+        };
+        let instruction = (bytes[0] as u16) << 8 | bytes[1] as u16;
+        let op = Op::decode(instruction);
+        println!("  {:#05x}  {:04X}  {}", addr, instruction, op);
+        if matches!(
+            op,
+            Op::Jump(_) | Op::Ret | Op::JumpOffset(..) | Op::Invalid(_)
+        ) {
+            break;
+        }
+        addr += 2;
+    }
+
+    let mut classes: BTreeMap<&str, usize> = BTreeMap::new();
+    for block in cfg.blocks.values() {
+        for &(_, instruction) in &block.instructions {
+            *classes.entry(Op::decode(instruction).class()).or_default() += 1;
+        }
+    }
+    let total = classes.values().sum::<usize>().max(1);
+    let mut classes = classes.into_iter().collect::<Vec<(&str, usize)>>();
+    classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    println!("\nreachable instructions:");
+    for (class, count) in classes {
+        println!(
+            "  {:<8} {:>5} {}",
+            class,
+            count,
+            "#".repeat((count * 40).div_ceil(total))
+        );
+    }
+
+    let strings = find_strings(&program, &cfg);
+    if !strings.is_empty() {
+        println!("\nstrings:");
+        for (addr, text) in strings {
+            println!("  {:#05x}  {:?}", addr, text);
+        }
+    }
+
+    let sprites = find_sprites(&cfg, memory_map);
+    if !sprites.is_empty() {
+        println!("\nsprites:");
+        for sprite in sprites {
+            println!(
+                "  {:#05x}  {}x{}",
+                sprite.address, sprite.width, sprite.height
+            );
+            let bytes = &program[sprite.address - start..sprite.address - start + sprite.bytes()];
+            for row in bytes.chunks(sprite.width / 8) {
+                let pixels = row
+                    .iter()
+                    .map(|byte| format!("{:08b}", byte))
+                    .collect::<String>()
+                    .replace('0', ".")
+                    .replace('1', "#");
+                println!("    {}", pixels);
+            }
+        }
+    }
+    Ok(())
+}
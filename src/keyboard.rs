@@ -1,12 +1,44 @@
 use minifb::Key;
 
+// https://github.com/mattmikolay/chip-8/wiki/CHIP%E2%80%908-Technical-Reference#keypad-input
+pub const DEFAULT_KEYMAP: [(Key, u8); 16] = [
+    (Key::Key1, 0x1),
+    (Key::Key2, 0x2),
+    (Key::Key3, 0x3),
+    (Key::Key4, 0xc),
+    (Key::Q, 0x4),
+    (Key::W, 0x5),
+    (Key::E, 0x6),
+    (Key::R, 0xd),
+    (Key::A, 0x7),
+    (Key::S, 0x8),
+    (Key::D, 0x9),
+    (Key::F, 0xe),
+    (Key::Z, 0xa),
+    (Key::X, 0x0),
+    (Key::C, 0xb),
+    (Key::V, 0xf),
+];
+
 pub struct KeyBoard {
     pub key_pressed: Option<u8>,
+    // keyboard key -> chip8 key, several keys can map to the same chip8 key
+    pub keymap: Vec<(Key, u8)>,
+    // print the keys that are pressed, off while the terminal frontend has the screen
+    pub echo: bool,
 }
 
 impl KeyBoard {
     pub fn new() -> KeyBoard {
-        KeyBoard { key_pressed: None }
+        KeyBoard::with_keymap(DEFAULT_KEYMAP.to_vec())
+    }
+
+    pub fn with_keymap(keymap: Vec<(Key, u8)>) -> KeyBoard {
+        KeyBoard {
+            key_pressed: None,
+            keymap,
+            echo: true,
+        }
     }
 
     pub fn get_current_key(&self) -> Option<u8> {
@@ -14,35 +46,103 @@ impl KeyBoard {
     }
 
     pub fn set_key_pressed(&mut self, key: Option<&Key>) {
-        if key.is_none() {
+        let Some(key) = key else {
             return;
-        }
-        // https://github.com/mattmikolay/chip-8/wiki/CHIP%E2%80%908-Technical-Reference#keypad-input
-        let key_value: u8 = match key.unwrap() {
-            Key::Key1 => 0x1,
-            Key::Key2 => 0x2,
-            Key::Key3 => 0x3,
-            Key::Key4 => 0xc,
-            Key::Q => 0x4,
-            Key::W => 0x5,
-            Key::E => 0x6,
-            Key::R => 0xd,
-            Key::A => 0x7,
-            Key::S => 0x8,
-            Key::D => 0x9,
-            Key::F => 0xe,
-            Key::Z => 0xa,
-            Key::X => 0x0,
-            Key::C => 0xb,
-            Key::V => 0xf,
-            _ => 0xff,
         };
 
-        if key_value == 0xff {
-            self.key_pressed = None;
-        } else {
-            println!("Key pressed: {:?}", key);
-            self.key_pressed = Some(key_value);
+        match self.keymap.iter().find(|(k, _)| k == key) {
+            Some((_, value)) => {
+                if self.echo {
+                    println!("Key pressed: {:?}", key);
+                }
+                self.key_pressed = Some(*value);
+            }
+            None => self.key_pressed = None,
         }
     }
 }
+
+// accepts plain names ("x", "1", "up", "space"), browser KeyboardEvent.code names ("KeyX",
+// "Digit1", "ArrowUp") and the old numeric keyCodes, which is what octo stores in its keymaps
+pub fn parse_key(name: &str) -> Option<Key> {
+    if let Ok(code) = name.parse::<u32>() {
+        return key_from_code(code);
+    }
+    let name = name.to_ascii_lowercase();
+    let name = name
+        .strip_prefix("key")
+        .or_else(|| name.strip_prefix("digit"))
+        .or_else(|| name.strip_prefix("arrow"))
+        .filter(|rest| !rest.is_empty())
+        .unwrap_or(&name);
+
+    if let [c] = name.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            return key_from_code(c.to_ascii_uppercase() as u32);
+        }
+    }
+    match name {
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "space" => Some(Key::Space),
+        "enter" => Some(Key::Enter),
+        "tab" => Some(Key::Tab),
+        _ => None,
+    }
+}
+
+fn key_from_code(code: u32) -> Option<Key> {
+    const DIGITS: [Key; 10] = [
+        Key::Key0,
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::Key4,
+        Key::Key5,
+        Key::Key6,
+        Key::Key7,
+        Key::Key8,
+        Key::Key9,
+    ];
+    const LETTERS: [Key; 26] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    match code {
+        13 => Some(Key::Enter),
+        32 => Some(Key::Space),
+        37 => Some(Key::Left),
+        38 => Some(Key::Up),
+        39 => Some(Key::Right),
+        40 => Some(Key::Down),
+        48..=57 => Some(DIGITS[(code - 48) as usize]),
+        65..=90 => Some(LETTERS[(code - 65) as usize]),
+        _ => None,
+    }
+}
@@ -0,0 +1,245 @@
+// looks for likely bugs in a rom without running it. only code the control flow graph can reach
+// is checked, and register values are only known inside a block from constants loaded into them
+// (6XNN, ANNN and friends), so anything computed at runtime gets the benefit of the doubt.
+// errors are what would stop the interpreter, warnings are suspicious but may be intended
+use std::{collections::BTreeSet, io::Error, ops::Range};
+
+use serde_json::{json, Value};
+
+use crate::{
+    cfg::{Cfg, Known},
+    loader,
+    memory_map::MemoryMap,
+    opcode::Op,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub address: usize,
+    // short id of the check, e.g. "invalid-instruction"
+    pub check: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+pub fn lint(program: &[u8], memory_map: &MemoryMap) -> Vec<Finding> {
+    let start = memory_map.load_address;
+    let end = start + program.len();
+    let cfg = Cfg::build(program, start);
+    let mut findings = Vec::new();
+    let mut error = |address: usize, check: &'static str, message: String| {
+        findings.push(Finding {
+            severity: Severity::Error,
+            address,
+            check,
+            message,
+        })
+    };
+    let mut warnings = Vec::new();
+    let mut warn = |address: usize, check: &'static str, message: String| {
+        warnings.push(Finding {
+            severity: Severity::Warning,
+            address,
+            check,
+            message,
+        })
+    };
+
+    for block in cfg.blocks.values() {
+        let mut known = Known::new();
+        for &(addr, instruction) in &block.instructions {
+            let op = Op::decode(instruction);
+            match op {
+                Op::Invalid(_) => error(
+                    addr,
+                    "invalid-instruction",
+                    format!("{:04X} is not an instruction", instruction),
+                ),
+                Op::Jump(nnn) | Op::Call(nnn) => {
+                    let target = nnn as usize;
+                    let kind = if matches!(op, Op::Jump(_)) {
+                        "jump"
+                    } else {
+                        "call"
+                    };
+                    // the same rule the interpreter uses
+                    if target < start || target > end {
+                        error(
+                            addr,
+                            "target-outside-program",
+                            format!(
+                                "{} to {:#05x} is outside the program ({:#05x}-{:#05x})",
+                                kind,
+                                target,
+                                start,
+                                end - 1
+                            ),
+                        );
+                    } else if !target.is_multiple_of(2) {
+                        warn(
+                            addr,
+                            "odd-target",
+                            format!("{} to the odd address {:#05x}", kind, target),
+                        );
+                    }
+                }
+                Op::Font(x) | Op::BigFont(x) => {
+                    if let Some(digit) = known.v[x as usize].filter(|&digit| digit > 0xf) {
+                        error(
+                            addr,
+                            "font-digit",
+                            format!("V{:X} is {} here, the font only has digits 0-F", x, digit),
+                        );
+                    }
+                }
+                Op::Draw(_, _, n) | Op::Store(n) | Op::Load(n) => {
+                    let len = match op {
+                        Op::Draw(..) => n as usize,
+                        _ => n as usize + 1,
+                    };
+                    if let Some(i) = known.i.filter(|&i| i + len > memory_map.size) {
+                        error(
+                            addr,
+                            "memory-access",
+                            format!(
+                                "I is {:#05x} here, {} bytes from it go past the end of memory ({:#05x})",
+                                i, len, memory_map.size
+                            ),
+                        );
+                    }
+                }
+                _ => {}
+            }
+            known.apply(op, memory_map);
+        }
+    }
+
+    let indirect = !cfg.indirect_jumps().is_empty();
+    for code in unreachable_code(program, &cfg) {
+        warn(
+            code.start,
+            "unreachable-code",
+            format!(
+                "{} bytes of what looks like code up to {:#05x} can't be reached{}",
+                code.len(),
+                code.end - 1,
+                if indirect {
+                    ", unless through an indirect jump"
+                } else {
+                    ""
+                }
+            ),
+        );
+    }
+
+    findings.append(&mut warnings);
+    findings.sort_by(|a, b| a.address.cmp(&b.address).then(b.severity.cmp(&a.severity)));
+    findings
+}
+
+// unreached bytes are usually sprites and other data. they only count as code when they come
+// before anything I is pointed at and decode as sensible instructions
+pub fn unreachable_code(program: &[u8], cfg: &Cfg) -> Vec<Range<usize>> {
+    let reached = cfg.code_bytes();
+    let data_pointers = cfg
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|&(_, instruction)| match Op::decode(instruction) {
+            Op::SetI(nnn) => Some(nnn as usize),
+            _ => None,
+        })
+        .collect::<BTreeSet<usize>>();
+    let mut code = Vec::new();
+    let mut addr = cfg.start;
+    while addr < cfg.end {
+        if reached.contains(&addr) {
+            addr += 1;
+            continue;
+        }
+        let run_end = (addr..cfg.end)
+            .find(|a| reached.contains(a))
+            .unwrap_or(cfg.end);
+        let code_end = data_pointers
+            .range(addr..run_end)
+            .next()
+            .copied()
+            .unwrap_or(run_end);
+        let words = program[addr - cfg.start..code_end - cfg.start]
+            .chunks_exact(2)
+            .map(|w| (w[0] as u16) << 8 | w[1] as u16)
+            .collect::<Vec<u16>>();
+        if !words.is_empty()
+            && words
+                .iter()
+                .all(|&w| w != 0 && !matches!(Op::decode(w), Op::Invalid(_)))
+        {
+            code.push(addr..addr + words.len() * 2);
+        }
+        addr = run_end;
+    }
+    code
+}
+
+// prints the findings, returns whether there were no errors
+pub fn run(src: &str, memory_map: &MemoryMap, format: Format) -> Result<bool, Error> {
+    let program = loader::load(src)?;
+    let findings = lint(&program, memory_map);
+    let count = |severity: Severity| findings.iter().filter(|f| f.severity == severity).count();
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+
+    match format {
+        Format::Text => {
+            for finding in &findings {
+                println!(
+                    "{}:{:#05x}: {}: {} [{}]",
+                    src,
+                    finding.address,
+                    finding.severity.name(),
+                    finding.message,
+                    finding.check
+                );
+            }
+            println!("{} errors, {} warnings", errors, warnings);
+        }
+        Format::Json => {
+            let json = json!({
+                "rom": src,
+                "errors": errors,
+                "warnings": warnings,
+                "findings": findings.iter().map(|finding| json!({
+                    "severity": finding.severity.name(),
+                    "address": format!("{:#05x}", finding.address),
+                    "check": finding.check,
+                    "message": finding.message,
+                })).collect::<Vec<Value>>(),
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json).map_err(Error::other)?
+            );
+        }
+    }
+    Ok(errors == 0)
+}
@@ -0,0 +1,351 @@
+// reads roms from disk or stdin. besides raw binaries this understands gzip and zip archives,
+// intel hex, hex text dumps (like the byte lists in scripts/write_new_program.py), octo
+// cartridges and octo source code, which is compiled. the format is detected from the content
+// and falls back to the file extension
+use std::{
+    fs::read,
+    io::{Cursor, Error, ErrorKind, Read},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+
+use minifb::Key;
+
+use crate::{cartridge, font::Font, octo, palette::Palette, quirks::Quirks};
+
+// programs are loaded at 0x200, intel hex files usually use the real addresses
+const PROGRAM_START: usize = 0x200;
+// don't let a corrupt intel hex file allocate gigabytes
+const MAX_HEX_ADDRESS: usize = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    Raw,
+    Gzip,
+    Zip,
+    IntelHex,
+    HexText,
+    Cartridge,
+    Octo,
+}
+
+pub struct Rom {
+    pub program: Vec<u8>,
+    // only octo cartridges carry options
+    pub options: Option<RomOptions>,
+}
+
+// settings a rom asks to be run with, from an octo cartridge or the rom database.
+// anything that isn't known is None
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomOptions {
+    pub tickrate: Option<u32>,
+    pub quirks: Option<Quirks>,
+    pub palette: Option<Palette>,
+    pub keymap: Option<Vec<(Key, u8)>>,
+    pub font: Option<Font>,
+}
+
+pub fn load(src: &str) -> Result<Vec<u8>, Error> {
+    load_rom(src).map(|rom| rom.program)
+}
+
+// `-` reads from stdin
+pub fn load_rom(src: &str) -> Result<Rom, Error> {
+    let data = if src == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        data
+    } else {
+        read(src).map_err(|e| Error::new(e.kind(), format!("Failed to read {}: {}", src, e)))?
+    };
+    decode_rom(&data, src)
+}
+
+pub fn decode_rom(data: &[u8], name: &str) -> Result<Rom, Error> {
+    let program = match detect(data, name) {
+        RomFormat::Raw => data.to_vec(),
+        RomFormat::Gzip => {
+            let mut inflated = Vec::new();
+            GzDecoder::new(data)
+                .read_to_end(&mut inflated)
+                .map_err(|e| invalid(name, format!("corrupt gzip data: {}", e)))?;
+            // whatever is inside is detected again, so .hex.gz works too
+            let inner = name.strip_suffix(".gz").unwrap_or(name);
+            return decode_rom(&inflated, inner);
+        }
+        RomFormat::Zip => {
+            let (entry, inflated) = read_zip(data, name)?;
+            return decode_rom(&inflated, &entry);
+        }
+        RomFormat::Cartridge => {
+            let cartridge = cartridge::decode(data).map_err(|e| invalid(name, e.to_string()))?;
+            let program = octo::compile(&cartridge.program).map_err(|e| {
+                invalid(
+                    name,
+                    format!(
+                        "only cartridges of chip8 programs are supported, not SUPER-CHIP or XO-CHIP ones: {}",
+                        e
+                    ),
+                )
+            })?;
+            check_empty(&program, name)?;
+            return Ok(Rom {
+                program,
+                options: Some(cartridge.options),
+            });
+        }
+        RomFormat::IntelHex => parse_intel_hex(text(data, name)?).map_err(|e| invalid(name, e))?,
+        RomFormat::HexText => parse_hex_text(text(data, name)?).map_err(|e| invalid(name, e))?,
+        RomFormat::Octo => octo::compile(text(data, name)?).map_err(|e| invalid(name, e))?,
+    };
+
+    check_empty(&program, name)?;
+    Ok(Rom {
+        program,
+        options: None,
+    })
+}
+
+fn check_empty(program: &[u8], name: &str) -> Result<(), Error> {
+    if program.is_empty() {
+        return Err(invalid(name, String::from("the rom is empty")));
+    }
+    Ok(())
+}
+
+pub fn detect(data: &[u8], name: &str) -> RomFormat {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    // a rom can start with anything, e.g. 1F8B looks like gzip
+    if matches!(ext.as_deref(), Some("ch8") | Some("c8") | Some("bin")) {
+        return RomFormat::Raw;
+    }
+    if data.starts_with(&[0x1f, 0x8b]) {
+        return RomFormat::Gzip;
+    }
+    if data.starts_with(b"PK\x03\x04") {
+        return RomFormat::Zip;
+    }
+    if cartridge::is_cartridge(data) {
+        return RomFormat::Cartridge;
+    }
+
+    match ext.as_deref() {
+        Some("hex") | Some("ihx") => return RomFormat::IntelHex,
+        Some("txt") => return RomFormat::HexText,
+        Some("8o") => return RomFormat::Octo,
+        _ => {}
+    }
+
+    // a binary rom full of printable characters is very unlikely, real programs contain
+    // instructions like 0x00E0 or 0xF065
+    let is_text = !data.is_empty()
+        && data
+            .iter()
+            .all(|&b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+    if !is_text {
+        return RomFormat::Raw;
+    }
+    // but it's possible, so the text has to really be hex too
+    let src = std::str::from_utf8(data).unwrap_or_default();
+    match data.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b':') if parse_intel_hex(src).is_ok() => RomFormat::IntelHex,
+        _ if parse_hex_text(src).is_ok_and(|rom| !rom.is_empty()) => RomFormat::HexText,
+        _ => RomFormat::Raw,
+    }
+}
+
+fn invalid(name: &str, reason: String) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: {}", name, reason))
+}
+
+fn text<'a>(data: &'a [u8], name: &str) -> Result<&'a str, Error> {
+    std::str::from_utf8(data).map_err(|_| invalid(name, String::from("expected a text file")))
+}
+
+// returns the name and contents of the rom inside the archive
+fn read_zip(data: &[u8], name: &str) -> Result<(String, Vec<u8>), Error> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| invalid(name, format!("corrupt zip archive: {}", e)))?;
+    let files = archive
+        .file_names()
+        .filter(|f| !f.ends_with('/'))
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let is_rom = |f: &&String| {
+        let f = f.to_ascii_lowercase();
+        f.ends_with(".ch8") || f.ends_with(".c8")
+    };
+
+    let entry = match (files.len(), files.iter().filter(is_rom).count()) {
+        (0, _) => return Err(invalid(name, String::from("the zip archive is empty"))),
+        (1, _) => files[0].clone(),
+        (_, 1) => files.iter().find(is_rom).cloned().unwrap_or_default(),
+        _ => {
+            return Err(invalid(
+                name,
+                format!(
+                    "the zip archive contains {} files and it isn't clear which one is the rom: {}",
+                    files.len(),
+                    files.join(", ")
+                ),
+            ))
+        }
+    };
+
+    let mut file = archive
+        .by_name(&entry)
+        .map_err(|e| invalid(name, format!("failed to read {}: {}", entry, e)))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .map_err(|e| invalid(name, format!("failed to read {}: {}", entry, e)))?;
+    Ok((entry, contents))
+}
+
+// https://en.wikipedia.org/wiki/Intel_HEX
+// only data, end of file and the two extended address record types are meaningful for a rom.
+// if every byte is at or above 0x200 the file is assumed to use real chip8 addresses
+pub fn parse_intel_hex(src: &str) -> Result<Vec<u8>, String> {
+    let mut chunks: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut base = 0usize;
+    let mut ended = false;
+
+    for (index, line) in src.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if ended {
+            return Err(format!(
+                "line {}: data after the end of file record",
+                line_no
+            ));
+        }
+        let record = line
+            .strip_prefix(':')
+            .ok_or_else(|| format!("line {}: records have to start with ':'", line_no))?;
+        let bytes = hex_bytes(record)
+            .ok_or_else(|| format!("line {}: \"{}\" is not valid hex", line_no, record))?;
+        if bytes.len() < 5 {
+            return Err(format!("line {}: record is too short", line_no));
+        }
+        let len = bytes[0] as usize;
+        if bytes.len() != len + 5 {
+            return Err(format!(
+                "line {}: record says it has {} data bytes but has {}",
+                line_no,
+                len,
+                bytes.len() as isize - 5
+            ));
+        }
+        let checksum = bytes[..bytes.len() - 1]
+            .iter()
+            .fold(0u8, |acc, b| acc.wrapping_add(*b))
+            .wrapping_neg();
+        if checksum != bytes[bytes.len() - 1] {
+            return Err(format!(
+                "line {}: checksum mismatch (expected {:#04x}, found {:#04x})",
+                line_no,
+                checksum,
+                bytes[bytes.len() - 1]
+            ));
+        }
+
+        let offset = (bytes[1] as usize) << 8 | bytes[2] as usize;
+        let data = &bytes[4..4 + len];
+        match bytes[3] {
+            0x00 => chunks.push((base + offset, data.to_vec())),
+            0x01 => ended = true,
+            0x02 if len == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) * 16,
+            0x04 if len == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 16,
+            // start address records don't matter, chip8 always starts at 0x200
+            0x03 | 0x05 => {}
+            kind => {
+                return Err(format!(
+                    "line {}: unsupported record type {:#04x}",
+                    line_no, kind
+                ))
+            }
+        }
+    }
+
+    let start = chunks
+        .iter()
+        .map(|(addr, _)| *addr)
+        .min()
+        .ok_or("no data records")?;
+    let end = chunks
+        .iter()
+        .map(|(addr, data)| addr + data.len())
+        .max()
+        .unwrap_or(start);
+    if end > MAX_HEX_ADDRESS {
+        return Err(format!("data at {:#x} is outside of chip8 memory", end - 1));
+    }
+    let origin = if start >= PROGRAM_START {
+        PROGRAM_START
+    } else {
+        0
+    };
+    let mut rom = vec![0u8; end - origin];
+    for (addr, data) in chunks {
+        rom[addr - origin..addr - origin + data.len()].copy_from_slice(&data);
+    }
+    Ok(rom)
+}
+
+// whitespace/comma separated hex bytes or words, with or without 0x. comments starting with
+// #, ; or // run to the end of the line and [ ] are ignored so python/js lists can be pasted
+pub fn parse_hex_text(src: &str) -> Result<Vec<u8>, String> {
+    let mut rom = Vec::new();
+    for (index, line) in src.lines().enumerate() {
+        let line = ["#", ";", "//"]
+            .iter()
+            .filter_map(|c| line.find(c))
+            .min()
+            .map_or(line, |comment| &line[..comment]);
+
+        for token in line
+            .split(|c: char| c.is_whitespace() || matches!(c, ',' | '[' | ']'))
+            .filter(|t| !t.is_empty())
+        {
+            let (digits, prefixed) = match token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+            {
+                Some(digits) => (digits, true),
+                None => (token, false),
+            };
+            // 0xE is fine but a bare E is more likely a typo than half a byte
+            let digits = if prefixed && !digits.len().is_multiple_of(2) {
+                format!("0{}", digits)
+            } else {
+                digits.to_string()
+            };
+            let bytes = hex_bytes(&digits).ok_or_else(|| {
+                format!(
+                    "line {}: \"{}\" is not a hex byte or word",
+                    index + 1,
+                    token
+                )
+            })?;
+            rom.extend(bytes);
+        }
+    }
+    Ok(rom)
+}
+
+fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,255 @@
+// the emulated machine without any io: the cpu, its keypad and what happens once per frame.
+// Chip8 drives it directly when frames don't have to be paced, otherwise it runs on its own thread
+// (see spawn) so the window keeps handling events while a rom is busy and a slow window doesn't
+// make the emulation stutter. the window thread gets a snapshot of every frame and sends input back
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use minifb::Key;
+
+use crate::{
+    coverage::Coverage,
+    cpu::{Cpu, ExecuteError},
+    display::Display,
+    keyboard::KeyBoard,
+    opcode::Op,
+    profiler::Profiler,
+};
+
+pub const FRAME_TIME: Duration = Duration::from_micros(1_000_000 / 60);
+
+// frames the window thread can fall behind by before frames are dropped
+const FRAME_QUEUE: usize = 8;
+
+pub struct Machine {
+    pub cpu: Cpu,
+    pub keyboard: KeyBoard,
+    // instructions executed per frame
+    tickrate: u32,
+    // number of emulated frames so far
+    pub frame: u64,
+    // set to profile the rom, every instruction goes through Cpu::step then
+    pub profiler: Option<Profiler>,
+    // where to write the folded stacks of the profile
+    pub profile_folded: Option<PathBuf>,
+    // set to track which bytes of the rom were executed, read and written, also through Cpu::step
+    pub coverage: Option<Coverage>,
+    // where to write the coverage report
+    pub coverage_path: Option<PathBuf>,
+    // set while the window needs every frame, e.g. to record them. the machine waits for a full
+    // queue then instead of dropping frames
+    pub every_frame: bool,
+}
+
+// what the window needs to show a frame
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub frame: u64,
+    pub display: Display,
+    // rows of display that changed since the previous snapshot, bit y for row y
+    pub dirty: u64,
+    pub pc: usize,
+    pub i: u16,
+    pub stack_depth: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub gp_registers: [u8; 16],
+}
+
+// sent from the window to the machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    // pressed for the next frame
+    Key(Key),
+    Dump { everything: bool },
+    // sets Machine::every_frame
+    EveryFrame(bool),
+}
+
+// sent from the machine to the window
+pub enum Output {
+    Frame(Box<Snapshot>),
+    // the rom stopped or ran for the maximum number of frames
+    Stopped,
+}
+
+impl Machine {
+    pub fn new(cpu: Cpu, keyboard: KeyBoard, tickrate: u32) -> Machine {
+        Machine {
+            cpu,
+            keyboard,
+            tickrate: tickrate.max(1),
+            frame: 0,
+            profiler: None,
+            profile_folded: None,
+            coverage: None,
+            coverage_path: None,
+            every_frame: false,
+        }
+    }
+
+    // executes a frame worth of instructions and ticks the timers. the pressed key is only held
+    // for a single frame
+    pub fn run_frame(&mut self) -> Result<(), ExecuteError> {
+        let mut res = Ok(());
+        let mut steps = 0;
+        while steps < self.tickrate {
+            let step = if self.profiler.is_some() || self.coverage.is_some() {
+                let op = Op::decode(self.cpu.instruction_at(self.cpu.pc).unwrap_or(0));
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.record(self.cpu.pc, op);
+                }
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record(&self.cpu, op);
+                }
+                self.cpu.step(&self.keyboard).map(|_| 1)
+            } else {
+                self.cpu
+                    .step_block(&self.keyboard, self.tickrate - steps)
+                    .map(|(executed, _)| executed)
+            };
+            match step {
+                Ok(executed) => steps += executed,
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+
+        self.keyboard.key_pressed = None;
+        if res.is_ok() {
+            self.cpu.tick_timers();
+            self.frame += 1;
+        }
+        res
+    }
+
+    pub fn apply(&mut self, input: Input) {
+        match input {
+            Input::Key(key) => self.keyboard.set_key_pressed(Some(&key)),
+            Input::Dump { everything: false } => self.cpu.dump(true, 0),
+            Input::Dump { everything: true } => self.cpu.dump_everything(),
+            Input::EveryFrame(every_frame) => self.every_frame = every_frame,
+        }
+    }
+
+    // prints the profile and saves the coverage report, if there are any
+    pub fn finish(&self) {
+        if let Some(profiler) = &self.profiler {
+            print!("{}", profiler.report(&self.cpu));
+            if let Some(path) = &self.profile_folded {
+                match profiler.write_folded(path) {
+                    Ok(()) => println!("Saved folded stacks to {}", path.display()),
+                    Err(e) => {
+                        eprintln!("Failed to save folded stacks to {}: {}", path.display(), e)
+                    }
+                }
+            }
+        }
+        if let (Some(coverage), Some(path)) = (&self.coverage, &self.coverage_path) {
+            let summary = coverage.summary();
+            println!(
+                "coverage: {} of {} bytes executed, {} read, {} written, {} untouched",
+                summary.executed, summary.bytes, summary.read, summary.written, summary.untouched
+            );
+            match coverage.save(path, &self.cpu) {
+                Ok(()) => println!("Saved coverage to {}", path.display()),
+                Err(e) => eprintln!("Failed to save coverage to {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    // takes the changed rows, they are reported again by the next snapshot only if they change again
+    pub fn snapshot(&mut self) -> Snapshot {
+        let dirty = self.cpu.d_buffer.take_dirty();
+        Snapshot {
+            frame: self.frame,
+            display: self.cpu.d_buffer.clone(),
+            dirty,
+            pc: self.cpu.pc,
+            i: self.cpu.i,
+            stack_depth: self.cpu.stack.len(),
+            delay_timer: self.cpu.delay_timer,
+            sound_timer: self.cpu.sound_timer,
+            gp_registers: self.cpu.gp_registers,
+        }
+    }
+}
+
+// runs the machine at 60 frames per second on its own thread until it stops, max_frames have
+// been emulated or the input sender is dropped
+pub fn spawn(
+    mut machine: Machine,
+    max_frames: Option<u64>,
+) -> (Sender<Input>, Receiver<Output>, JoinHandle<()>) {
+    let (input_tx, input_rx) = mpsc::channel();
+    let (output_tx, output_rx) = mpsc::sync_channel(FRAME_QUEUE);
+
+    let handle = thread::spawn(move || {
+        run(&mut machine, max_frames, &input_rx, &output_tx);
+        machine.finish();
+    });
+    (input_tx, output_rx, handle)
+}
+
+fn run(
+    machine: &mut Machine,
+    max_frames: Option<u64>,
+    inputs: &Receiver<Input>,
+    outputs: &SyncSender<Output>,
+) {
+    let mut next_frame = Instant::now();
+    loop {
+        loop {
+            match inputs.try_recv() {
+                Ok(input) => machine.apply(input),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        if max_frames.is_some_and(|max| machine.frame >= max) {
+            let _ = outputs.send(Output::Stopped);
+            return;
+        }
+
+        match machine.run_frame() {
+            Ok(()) => {
+                let snapshot = Output::Frame(Box::new(machine.snapshot()));
+                let sent = if machine.every_frame {
+                    outputs
+                        .send(snapshot)
+                        .map_err(|e| TrySendError::Disconnected(e.0))
+                } else {
+                    outputs.try_send(snapshot)
+                };
+                match sent {
+                    Ok(()) => {}
+                    // the window is behind, it gets the rows that changed with the next frame
+                    Err(TrySendError::Full(Output::Frame(snapshot))) => {
+                        machine.cpu.d_buffer.mark_dirty(snapshot.dirty)
+                    }
+                    Err(_) => return,
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                let _ = outputs.send(Output::Stopped);
+                return;
+            }
+        }
+
+        // sleep until the next frame is due, without trying to catch up after falling behind
+        next_frame += FRAME_TIME;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    }
+}
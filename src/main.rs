@@ -1,18 +1,121 @@
 use std::error::Error;
 
+mod bench;
+mod blocks;
+mod cartridge;
+mod cfg;
 mod chip8;
 mod cli;
+mod conformance;
+mod coverage;
 mod cpu;
+mod decompile;
+mod display;
 mod emulate;
 mod ext;
+mod font;
+mod frontend;
+mod heuristics;
+mod info;
 mod keyboard;
+mod lint;
+mod loader;
+mod machine;
+mod memory_map;
+mod octo;
+mod opcode;
+mod optimize;
+mod palette;
+mod profiler;
+mod quirks;
+mod recording;
+mod romdb;
+mod screenshot;
+mod sprites;
+mod terminal;
 mod tests;
 fn main() -> Result<(), Box<dyn Error>> {
     if let Some(args) = cli::parse_args() {
         match args {
-            cli::Chip8Command::Emulate { src, debug, timing } => {
-                let mut chip8 = chip8::Chip8::new(debug, timing);
-                emulate::emulate(src, &mut chip8)?;
+            cli::Chip8Command::Emulate {
+                src,
+                config,
+                explicit,
+                rom_db,
+                use_rom_db,
+            } => {
+                let rom_db = if use_rom_db {
+                    let mut db = romdb::RomDb::builtin();
+                    if let Some(path) = rom_db {
+                        db.merge(romdb::RomDb::load(&path)?);
+                    }
+                    Some(db)
+                } else {
+                    None
+                };
+                emulate::emulate(src, *config, &explicit, rom_db.as_ref())?;
+            }
+            cli::Chip8Command::Conformance {
+                dir,
+                frames,
+                tickrate,
+            } => {
+                conformance::run(&dir, frames, tickrate)?;
+            }
+            cli::Chip8Command::Bench { src, options } => {
+                bench::run(&src, options)?;
+            }
+            cli::Chip8Command::Cfg {
+                src,
+                load_address,
+                format,
+            } => {
+                cfg::run(&src, load_address, format)?;
+            }
+            cli::Chip8Command::Lint {
+                src,
+                memory_map,
+                format,
+            } => {
+                if !lint::run(&src, &memory_map, format)? {
+                    std::process::exit(1);
+                }
+            }
+            cli::Chip8Command::Info { src, memory_map } => {
+                info::run(&src, &memory_map)?;
+            }
+            cli::Chip8Command::Build { src, output } => {
+                octo::build(&src, output)?;
+            }
+            cli::Chip8Command::Decompile {
+                src,
+                load_address,
+                format,
+            } => {
+                decompile::run(&src, load_address, format)?;
+            }
+            cli::Chip8Command::Optimize {
+                src,
+                output,
+                load_address,
+                verify,
+            } => {
+                optimize::run(&src, output, load_address, verify)?;
+            }
+            cli::Chip8Command::SpritesExtract {
+                src,
+                memory_map,
+                output,
+                scale,
+            } => {
+                sprites::extract(&src, &memory_map, output, scale)?;
+            }
+            cli::Chip8Command::SpritesImport {
+                image,
+                options,
+                patch,
+            } => {
+                sprites::import(&image, &options, patch)?;
             }
             cli::Chip8Command::PrintKeyMap => {
                 print!(
@@ -28,6 +131,14 @@ fn main() -> Result<(), Box<dyn Error>> {
                     ║ A -> Z ║ 0 -> X ║ B -> C ║ F -> V ║
                     ╚════════╩════════╩════════╩════════╝
 
+                    Hotkeys:
+                    NumPad1 -> dump cpu state and display
+                    NumPad2 -> dump everything
+                    NumPad3 -> quit
+                    NumPad4 -> save a screenshot
+                    NumPad5 -> start/stop recording a gif
+                    (F1-F5 in the terminal frontend, Esc quits)
+
                     "
                 )
             }
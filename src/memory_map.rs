@@ -0,0 +1,106 @@
+// where things live in chip8 memory. the original interpreter had 4K of ram with programs at
+// 0x200, the ETI-660 loads them at 0x600 and XO-CHIP has a full 64K address space
+use crate::font::FONT_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryMap {
+    // total ram in bytes
+    pub size: usize,
+    // programs are copied here and execution starts here
+    pub load_address: usize,
+    // the built in hex font (used by 0xFX29) starts here
+    pub font_address: usize,
+}
+
+// the layout this emulator always had
+pub const DEFAULT: MemoryMap = MemoryMap {
+    size: 8192,
+    load_address: 0x200,
+    font_address: 0x000,
+};
+
+pub const CHIP8: MemoryMap = MemoryMap {
+    size: 4096,
+    load_address: 0x200,
+    font_address: 0x050,
+};
+
+pub const ETI660: MemoryMap = MemoryMap {
+    size: 4096,
+    load_address: 0x600,
+    font_address: 0x050,
+};
+
+pub const XOCHIP: MemoryMap = MemoryMap {
+    size: 65536,
+    load_address: 0x200,
+    font_address: 0x050,
+};
+
+pub const PRESETS: [(&str, MemoryMap); 4] = [
+    ("default", DEFAULT),
+    ("chip8", CHIP8),
+    ("eti660", ETI660),
+    ("xochip", XOCHIP),
+];
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl MemoryMap {
+    pub fn preset(name: &str) -> Option<MemoryMap> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, map)| *map)
+    }
+
+    pub fn preset_names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn max_program_size(&self) -> usize {
+        self.size.saturating_sub(self.load_address)
+    }
+
+    // addresses are at most 16 bits wide and the font has to sit below the program
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size > 0x10000 {
+            return Err(format!(
+                "{} bytes of ram can't be addressed, the maximum is 65536",
+                self.size
+            ));
+        }
+        if self.load_address >= self.size {
+            return Err(format!(
+                "the load address {:#x} is outside of the {} bytes of ram",
+                self.load_address, self.size
+            ));
+        }
+        let font_end = self.font_address + FONT_SIZE;
+        if font_end > self.load_address {
+            return Err(format!(
+                "the font at {:#x}-{:#x} has to end before the program loaded at {:#x}",
+                self.font_address,
+                font_end - 1,
+                self.load_address
+            ));
+        }
+        Ok(())
+    }
+}
+
+// accepts decimal or 0x prefixed hex, eg 4096 or 0x600
+pub fn parse_number(value: &str) -> Result<usize, String> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse::<usize>(),
+    };
+    parsed.map_err(|_| format!("\"{}\" is not a number", value))
+}
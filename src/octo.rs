@@ -0,0 +1,868 @@
+// compiles octo source code (https://github.com/JohnEarnest/Octo) into a rom for the chip8
+// instruction set Cpu runs. supported are labels, the chip8 statements and their pseudo ops,
+// if/then, if/begin/else/end, loop/again with while, :const, :alias, :calc, :macro, :byte, :org,
+// :next, :unpack and :call. SUPER-CHIP and XO-CHIP statements are rejected.
+// like octo the program starts with a jump to main at 0x200, and :calc expressions have no
+// precedence, they are evaluated right to left
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+// where the rom is loaded
+const START: usize = 0x200;
+// macros expanding into themselves would never finish
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+// an operand referring to a label that wasn't defined yet
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    // the low 12 bits of the instruction at the address
+    Address,
+    // the two 6XNN of :unpack, with the nibble for the high byte
+    Unpack(u8),
+}
+
+#[derive(Debug, Clone)]
+struct Fixup {
+    at: usize,
+    kind: FixupKind,
+    label: String,
+    line: usize,
+}
+
+// open if/else and loop blocks
+#[derive(Debug, Clone)]
+enum Flow {
+    // the jump over the body, patched by else or end
+    If(usize),
+    // the jump over the else branch, patched by end
+    Else(usize),
+    // the start of the loop and the jumps out of it from while
+    Loop(usize, Vec<usize>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Eq(u8, Operand),
+    Ne(u8, Operand),
+    // vx < rhs, vx >= rhs and friends
+    Less(u8, Operand),
+    GreaterEq(u8, Operand),
+    Greater(u8, Operand),
+    LessEq(u8, Operand),
+    Key(u8),
+    NotKey(u8),
+}
+
+struct Compiler {
+    tokens: VecDeque<Token>,
+    // the rom from START on
+    rom: Vec<u8>,
+    here: usize,
+    line: usize,
+    labels: HashMap<String, usize>,
+    consts: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    flow: Vec<Flow>,
+    expansions: usize,
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (n, line) in source.lines().enumerate() {
+        for text in line.split_whitespace() {
+            if text.starts_with('#') {
+                break;
+            }
+            tokens.push_back(Token {
+                text: text.to_string(),
+                line: n + 1,
+            });
+        }
+    }
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse::<f64>().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn unsupported(statement: &str) -> bool {
+    matches!(
+        statement,
+        "hires"
+            | "lores"
+            | "scroll-down"
+            | "scroll-up"
+            | "scroll-left"
+            | "scroll-right"
+            | "exit"
+            | "saveflags"
+            | "loadflags"
+            | "plane"
+            | "audio"
+            | "pitch"
+            | ":stringmode"
+    )
+}
+
+impl Compiler {
+    fn new(source: &str) -> Compiler {
+        Compiler {
+            tokens: tokenize(source),
+            rom: Vec::new(),
+            here: START,
+            line: 0,
+            labels: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            flow: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line, message))
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token.text)
+            }
+            None => self.error(String::from("unexpected end of the program")),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("expected {} but found {}", expected, token));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), String> {
+        let offset = self.here - START;
+        if self.here > 0xfff {
+            return self.error(format!(
+                "the program doesn't fit below 0x1000 at {:#x}",
+                self.here
+            ));
+        }
+        if self.rom.len() <= offset {
+            self.rom.resize(offset + 1, 0);
+        }
+        self.rom[offset] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit_op(&mut self, op: u16) -> Result<(), String> {
+        self.emit((op >> 8) as u8)?;
+        self.emit(op as u8)
+    }
+
+    fn register_of(&self, name: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(name) {
+            return Some(register);
+        }
+        let digit = name.strip_prefix(['v', 'V'])?;
+        match digit.len() {
+            1 => u8::from_str_radix(digit, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        match self.register_of(&token) {
+            Some(register) => Ok(register),
+            None => self.error(format!("expected a register but found {}", token)),
+        }
+    }
+
+    // a number, a constant, a label defined earlier or a { calc expression }
+    fn value(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        self.value_of(&token)
+    }
+
+    fn value_of(&mut self, token: &str) -> Result<f64, String> {
+        if token == "{" {
+            return self.calc();
+        }
+        if let Some(value) = parse_number(token) {
+            return Ok(value);
+        }
+        if let Some(&value) = self.consts.get(token) {
+            return Ok(value);
+        }
+        if let Some(&addr) = self.labels.get(token) {
+            return Ok(addr as f64);
+        }
+        self.error(format!("{} is not a number or a known constant", token))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = self.value()? as i64;
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("{} doesn't fit in a byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let value = self.value()? as i64;
+        if !(0..=15).contains(&value) {
+            return self.error(format!("{} doesn't fit in a nibble", value));
+        }
+        Ok(value as u8)
+    }
+
+    // an address for the instruction about to be emitted at here, labels can be defined later
+    fn address(&mut self, kind: FixupKind) -> Result<u16, String> {
+        let token = self.next()?;
+        let known = token == "{"
+            || parse_number(&token).is_some()
+            || self.consts.contains_key(&token)
+            || self.labels.contains_key(&token);
+        if !known {
+            self.fixups.push(Fixup {
+                at: self.here,
+                kind,
+                label: token,
+                line: self.line,
+            });
+            return Ok(0);
+        }
+        let value = self.value_of(&token)? as i64;
+        if !(0..=0xfff).contains(&value) {
+            return self.error(format!("{:#x} is not a 12 bit address", value));
+        }
+        Ok(value as u16)
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        let token = self.next()?;
+        match self.register_of(&token) {
+            Some(register) => Ok(Operand::Register(register)),
+            None => {
+                let value = self.value_of(&token)? as i64;
+                if !(-128..=255).contains(&value) {
+                    return self.error(format!("{} doesn't fit in a byte", value));
+                }
+                Ok(Operand::Byte(value as u8))
+            }
+        }
+    }
+
+    fn define(&mut self, name: String, addr: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) || self.consts.contains_key(&name) {
+            return self.error(format!("{} is already defined", name));
+        }
+        if self.register_of(&name).is_some() {
+            return self.error(format!("{} is a register", name));
+        }
+        self.labels.insert(name, addr);
+        Ok(())
+    }
+
+    // the tokens up to the matching }, the { has been read already
+    fn braced(&mut self) -> Result<Vec<Token>, String> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let Some(token) = self.tokens.pop_front() else {
+                return self.error(String::from("missing }"));
+            };
+            self.line = token.line;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    // the expression up to the matching }, the { has been read already
+    fn calc(&mut self) -> Result<f64, String> {
+        let tokens = self.braced()?;
+        let texts = tokens
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<&str>>();
+        let mut pos = 0;
+        let value = self.expression(&texts, &mut pos)?;
+        if pos != texts.len() {
+            return self.error(format!("unexpected {} in expression", texts[pos]));
+        }
+        Ok(value)
+    }
+
+    // term (operator expression)?, so everything is evaluated right to left
+    fn expression(&mut self, tokens: &[&str], pos: &mut usize) -> Result<f64, String> {
+        let left = self.term(tokens, pos)?;
+        let Some(&op) = tokens.get(*pos) else {
+            return Ok(left);
+        };
+        if op == ")" {
+            return Ok(left);
+        }
+        *pos += 1;
+        let right = self.expression(tokens, pos)?;
+        let (l, r) = (left as i64, right as i64);
+        let bool = |b: bool| if b { 1.0 } else { 0.0 };
+        Ok(match op {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (l & r) as f64,
+            "|" => (l | r) as f64,
+            "^" => (l ^ r) as f64,
+            "<<" => (l << r) as f64,
+            ">>" => (l >> r) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => bool(left < right),
+            "<=" => bool(left <= right),
+            ">" => bool(left > right),
+            ">=" => bool(left >= right),
+            "==" => bool(left == right),
+            "!=" => bool(left != right),
+            _ => return self.error(format!("unknown operator {}", op)),
+        })
+    }
+
+    fn term(&mut self, tokens: &[&str], pos: &mut usize) -> Result<f64, String> {
+        let Some(&token) = tokens.get(*pos) else {
+            return self.error(String::from("incomplete expression"));
+        };
+        *pos += 1;
+        let unary = |op: fn(f64) -> f64, this: &mut Compiler, pos: &mut usize| {
+            this.term(tokens, pos).map(op)
+        };
+        match token {
+            "(" => {
+                let value = self.expression(tokens, pos)?;
+                if tokens.get(*pos) != Some(&")") {
+                    return self.error(String::from("missing )"));
+                }
+                *pos += 1;
+                Ok(value)
+            }
+            "-" => unary(|v| -v, self, pos),
+            "~" => unary(|v| !(v as i64) as f64, self, pos),
+            "!" => unary(|v| if v == 0.0 { 1.0 } else { 0.0 }, self, pos),
+            "abs" => unary(f64::abs, self, pos),
+            "sqrt" => unary(f64::sqrt, self, pos),
+            "floor" => unary(f64::floor, self, pos),
+            "ceil" => unary(f64::ceil, self, pos),
+            "sin" => unary(f64::sin, self, pos),
+            "cos" => unary(f64::cos, self, pos),
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => self.value_of(token),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.register()?;
+        let op = self.next()?;
+        Ok(match op.as_str() {
+            "key" => Condition::Key(x),
+            "-key" => Condition::NotKey(x),
+            "==" => Condition::Eq(x, self.operand()?),
+            "!=" => Condition::Ne(x, self.operand()?),
+            "<" => Condition::Less(x, self.operand()?),
+            ">=" => Condition::GreaterEq(x, self.operand()?),
+            ">" => Condition::Greater(x, self.operand()?),
+            "<=" => Condition::LessEq(x, self.operand()?),
+            _ => return self.error(format!("unknown comparison {}", op)),
+        })
+    }
+
+    // instructions that skip the next one when the condition is `when`. the ordering
+    // comparisons compute a flag in VF with 8XY7, which sets it the same way everywhere
+    fn skip(&mut self, condition: Condition, when: bool) -> Result<(), String> {
+        let x = condition_register(condition) as u16;
+        // skips when VF == flag if when, else when VF != flag
+        let flag_skip = |flag: u16| if when { 0x3f00 | flag } else { 0x4f00 | flag };
+        match condition {
+            Condition::Eq(_, rhs) | Condition::Ne(_, rhs) => {
+                let equal = matches!(condition, Condition::Eq(..)) == when;
+                self.emit_op(match (rhs, equal) {
+                    (Operand::Byte(nn), true) => 0x3000 | x << 8 | nn as u16,
+                    (Operand::Byte(nn), false) => 0x4000 | x << 8 | nn as u16,
+                    (Operand::Register(y), true) => 0x5000 | x << 8 | (y as u16) << 4,
+                    (Operand::Register(y), false) => 0x9000 | x << 8 | (y as u16) << 4,
+                })
+            }
+            Condition::Key(_) | Condition::NotKey(_) => {
+                let pressed = matches!(condition, Condition::Key(_)) == when;
+                self.emit_op(if pressed { 0xe09e } else { 0xe0a1 } | x << 8)
+            }
+            // VF = vx >= rhs
+            Condition::Less(_, rhs) | Condition::GreaterEq(_, rhs) => {
+                self.load_vf(rhs)?;
+                self.emit_op(0x8f07 | x << 4)?;
+                let flag = matches!(condition, Condition::GreaterEq(..)) as u16;
+                self.emit_op(flag_skip(flag))
+            }
+            // VF = rhs >= vx
+            Condition::Greater(_, rhs) | Condition::LessEq(_, rhs) => {
+                let flag = matches!(condition, Condition::LessEq(..)) as u16;
+                match rhs {
+                    Operand::Register(y) => {
+                        self.emit_op(0x8f00 | x << 4)?;
+                        self.emit_op(0x8f07 | (y as u16) << 4)?;
+                        self.emit_op(flag_skip(flag))
+                    }
+                    // vx > 255 is never true
+                    Operand::Byte(255) => {
+                        let always = (flag == 1) == when;
+                        self.emit_op(if always { 0x5000 } else { 0x9000 })
+                    }
+                    // rhs >= vx is vx < rhs + 1
+                    Operand::Byte(nn) => {
+                        self.load_vf(Operand::Byte(nn + 1))?;
+                        self.emit_op(0x8f07 | x << 4)?;
+                        self.emit_op(flag_skip(1 - flag))
+                    }
+                }
+            }
+        }
+    }
+
+    fn load_vf(&mut self, value: Operand) -> Result<(), String> {
+        self.emit_op(match value {
+            Operand::Byte(nn) => 0x6f00 | nn as u16,
+            Operand::Register(y) => 0x8f00 | (y as u16) << 4,
+        })
+    }
+
+    // a jump to be patched later, returns its address
+    fn placeholder_jump(&mut self) -> Result<usize, String> {
+        let at = self.here;
+        self.emit_op(0x1000)?;
+        Ok(at)
+    }
+
+    fn patch_jump(&mut self, at: usize, target: usize) {
+        let offset = at - START;
+        self.rom[offset] = 0x10 | (target >> 8) as u8;
+        self.rom[offset + 1] = target as u8;
+    }
+
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return self.error(format!("macro {} keeps expanding", name));
+        }
+        let definition = self.macros[name].clone();
+        let mut args = HashMap::new();
+        for param in &definition.params {
+            args.insert(param.clone(), self.next()?);
+        }
+        for token in definition.body.into_iter().rev() {
+            self.tokens.push_front(Token {
+                text: args.get(&token.text).cloned().unwrap_or(token.text),
+                line: self.line,
+            });
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        if unsupported(&token) {
+            return self.error(format!(
+                "{} is a SUPER-CHIP or XO-CHIP statement, only chip8 programs are supported",
+                token
+            ));
+        }
+        if let Some(x) = self.register_of(&token) {
+            return self.assignment(x as u16);
+        }
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define(name, self.here)?;
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.define(name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.consts.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.consts.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":macro" => {
+                let name = self.next()?;
+                let mut params = Vec::new();
+                loop {
+                    let param = self.next()?;
+                    if param == "{" {
+                        break;
+                    }
+                    params.push(param);
+                }
+                let body = self.braced()?;
+                self.macros.insert(name, Macro { params, body });
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit(byte)?;
+            }
+            ":org" => {
+                let addr = self.value()? as i64;
+                if !(START as i64..=0xfff).contains(&addr) {
+                    return self.error(format!("can't place code at {:#x}", addr));
+                }
+                self.here = addr as usize;
+            }
+            ":unpack" => {
+                let nibble = self.nibble()?;
+                let addr = self.address(FixupKind::Unpack(nibble))? as usize;
+                self.emit_op(0x6000 | (nibble as u16) << 4 | (addr >> 8) as u16)?;
+                self.emit_op(0x6100 | (addr & 0xff) as u16)?;
+            }
+            ":call" => {
+                let addr = self.address(FixupKind::Address)?;
+                self.emit_op(0x2000 | addr)?;
+            }
+            // debugger directives don't do anything in a rom
+            ":breakpoint" | ":proto" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit_op(0x00e0)?,
+            "return" | ";" => self.emit_op(0x00ee)?,
+            "jump" => {
+                let addr = self.address(FixupKind::Address)?;
+                self.emit_op(0x1000 | addr)?;
+            }
+            "jump0" => {
+                let addr = self.address(FixupKind::Address)?;
+                self.emit_op(0xb000 | addr)?;
+            }
+            "native" => {
+                let addr = self.address(FixupKind::Address)?;
+                self.emit_op(addr)?;
+            }
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.nibble()? as u16;
+                self.emit_op(0xd000 | x << 8 | y << 4 | n)?;
+            }
+            "bcd" => {
+                let x = self.register()? as u16;
+                self.emit_op(0xf033 | x << 8)?;
+            }
+            "save" => {
+                let x = self.register()? as u16;
+                self.emit_op(0xf055 | x << 8)?;
+            }
+            "load" => {
+                let x = self.register()? as u16;
+                self.emit_op(0xf065 | x << 8)?;
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                self.emit_op(if token == "delay" { 0xf015 } else { 0xf018 } | x << 8)?;
+            }
+            "i" => match self.next()?.as_str() {
+                ":=" => match self.peek() {
+                    Some("hex") | Some("bighex") => {
+                        let big = self.next()? == "bighex";
+                        let x = self.register()? as u16;
+                        self.emit_op(if big { 0xf030 } else { 0xf029 } | x << 8)?;
+                    }
+                    _ => {
+                        let addr = self.address(FixupKind::Address)?;
+                        self.emit_op(0xa000 | addr)?;
+                    }
+                },
+                "+=" => {
+                    let x = self.register()? as u16;
+                    self.emit_op(0xf01e | x << 8)?;
+                }
+                op => return self.error(format!("can't use {} with i", op)),
+            },
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => {
+                        self.skip(condition, false)?;
+                        let before = self.here;
+                        let nested = self.peek() == Some("if");
+                        self.statement()?;
+                        // like octo a skip may skip the skip of another if ... then
+                        let offset = before - START;
+                        let skips =
+                            nested && matches!(self.rom[offset] >> 4, 0x3 | 0x4 | 0x5 | 0x9 | 0xe);
+                        if self.here != before + 2 && !skips {
+                            return self.error(String::from(
+                                "if ... then has to be followed by a single instruction",
+                            ));
+                        }
+                    }
+                    "begin" => {
+                        self.skip(condition, true)?;
+                        let at = self.placeholder_jump()?;
+                        self.flow.push(Flow::If(at));
+                    }
+                    other => return self.error(format!("expected then or begin, found {}", other)),
+                }
+            }
+            "else" => match self.flow.pop() {
+                Some(Flow::If(at)) => {
+                    let jump = self.placeholder_jump()?;
+                    self.patch_jump(at, self.here);
+                    self.flow.push(Flow::Else(jump));
+                }
+                _ => return self.error(String::from("else without if ... begin")),
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::If(at)) | Some(Flow::Else(at)) => self.patch_jump(at, self.here),
+                _ => return self.error(String::from("end without if ... begin")),
+            },
+            "loop" => self.flow.push(Flow::Loop(self.here, Vec::new())),
+            "while" => {
+                let condition = self.condition()?;
+                self.skip(condition, true)?;
+                let at = self.placeholder_jump()?;
+                match self
+                    .flow
+                    .iter_mut()
+                    .rev()
+                    .find(|f| matches!(f, Flow::Loop(..)))
+                {
+                    Some(Flow::Loop(_, exits)) => exits.push(at),
+                    _ => return self.error(String::from("while outside of a loop")),
+                }
+            }
+            "again" => match self.flow.pop() {
+                Some(Flow::Loop(start, exits)) => {
+                    self.emit_op(0x1000 | start as u16)?;
+                    for exit in exits {
+                        self.patch_jump(exit, self.here);
+                    }
+                }
+                _ => return self.error(String::from("again without loop")),
+            },
+            _ if self.macros.contains_key(&token) => self.expand(&token)?,
+            // numbers are data, like sprites
+            _ if parse_number(&token).is_some()
+                || self.consts.contains_key(&token)
+                || token == "{" =>
+            {
+                let value = self.value_of(&token)? as i64;
+                if !(-128..=255).contains(&value) {
+                    return self.error(format!("{} doesn't fit in a byte", value));
+                }
+                self.emit(value as u8)?;
+            }
+            // anything else is a subroutine call
+            _ => {
+                self.tokens.push_front(Token {
+                    text: token,
+                    line: self.line,
+                });
+                let addr = self.address(FixupKind::Address)?;
+                self.emit_op(0x2000 | addr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, x: u16) -> Result<(), String> {
+        let op = self.next()?;
+        let rhs = match (op.as_str(), self.peek()) {
+            (":=", Some("key")) => {
+                self.next()?;
+                return self.emit_op(0xf00a | x << 8);
+            }
+            (":=", Some("delay")) => {
+                self.next()?;
+                return self.emit_op(0xf007 | x << 8);
+            }
+            (":=", Some("random")) => {
+                self.next()?;
+                let nn = self.byte()? as u16;
+                return self.emit_op(0xc000 | x << 8 | nn);
+            }
+            _ => self.operand()?,
+        };
+        let instruction = match (op.as_str(), rhs) {
+            (":=", Operand::Byte(nn)) => 0x6000 | nn as u16,
+            ("+=", Operand::Byte(nn)) => 0x7000 | nn as u16,
+            ("-=", Operand::Byte(nn)) => 0x7000 | nn.wrapping_neg() as u16,
+            (":=", Operand::Register(y)) => 0x8000 | (y as u16) << 4,
+            ("|=", Operand::Register(y)) => 0x8001 | (y as u16) << 4,
+            ("&=", Operand::Register(y)) => 0x8002 | (y as u16) << 4,
+            ("^=", Operand::Register(y)) => 0x8003 | (y as u16) << 4,
+            ("+=", Operand::Register(y)) => 0x8004 | (y as u16) << 4,
+            ("-=", Operand::Register(y)) => 0x8005 | (y as u16) << 4,
+            (">>=", Operand::Register(y)) => 0x8006 | (y as u16) << 4,
+            ("=-", Operand::Register(y)) => 0x8007 | (y as u16) << 4,
+            ("<<=", Operand::Register(y)) => 0x800e | (y as u16) << 4,
+            _ => return self.error(format!("can't use {} with these operands", op)),
+        };
+        self.emit_op(instruction | x << 8)
+    }
+
+    fn compile(mut self) -> Result<Vec<u8>, String> {
+        // the jump to main, filled in at the end
+        self.fixups.push(Fixup {
+            at: START,
+            kind: FixupKind::Address,
+            label: String::from("main"),
+            line: 0,
+        });
+        self.emit_op(0x1000)?;
+
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+        if let Some(flow) = self.flow.last() {
+            let open = match flow {
+                Flow::If(_) | Flow::Else(_) => "if ... begin without end",
+                Flow::Loop(..) => "loop without again",
+            };
+            return self.error(String::from(open));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(&fixup.label) else {
+                return Err(match fixup.line {
+                    0 => String::from("the program has no main label"),
+                    line => format!("line {}: {} is not defined", line, fixup.label),
+                });
+            };
+            if addr > 0xfff {
+                return Err(format!(
+                    "line {}: {} at {:#x} is past the 12 bit address space",
+                    fixup.line, fixup.label, addr
+                ));
+            }
+            let offset = fixup.at - START;
+            match fixup.kind {
+                FixupKind::Address => {
+                    self.rom[offset] |= (addr >> 8) as u8;
+                    self.rom[offset + 1] = addr as u8;
+                }
+                FixupKind::Unpack(nibble) => {
+                    self.rom[offset + 1] = nibble << 4 | (addr >> 8) as u8;
+                    self.rom[offset + 3] = addr as u8;
+                }
+            }
+        }
+        Ok(self.rom)
+    }
+}
+
+fn condition_register(condition: Condition) -> u8 {
+    match condition {
+        Condition::Eq(x, _)
+        | Condition::Ne(x, _)
+        | Condition::Less(x, _)
+        | Condition::GreaterEq(x, _)
+        | Condition::Greater(x, _)
+        | Condition::LessEq(x, _)
+        | Condition::Key(x)
+        | Condition::NotKey(x) => x,
+    }
+}
+
+// the rom for the program, loaded at 0x200
+pub fn compile(source: &str) -> Result<Vec<u8>, String> {
+    Compiler::new(source).compile()
+}
+
+// compiles the source file to output, or next to it with a .ch8 extension
+pub fn build(src: &Path, output: Option<PathBuf>) -> Result<(), Error> {
+    let source = fs::read_to_string(src)
+        .map_err(|e| Error::new(e.kind(), format!("Failed to read {}: {}", src.display(), e)))?;
+    let program = compile(&source)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", src.display(), e)))?;
+    let output = output.unwrap_or_else(|| src.with_extension("ch8"));
+    fs::write(&output, &program).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to write {}: {}", output.display(), e),
+        )
+    })?;
+    println!("Wrote {} bytes to {}", program.len(), output.display());
+    Ok(())
+}
@@ -0,0 +1,229 @@
+// instructions decoded into their operation and operands, so Cpu::step doesn't have to pick the
+// nibbles apart every time it runs the same instruction.
+// https://github.com/mattmikolay/chip-8/wiki/CHIP%E2%80%908-Instruction-Set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    // 0NNN, machine code routine on the COSMAC VIP, ignored
+    Sys(u16),
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipEqImm(u8, u8),
+    // 4XNN
+    SkipNeImm(u8, u8),
+    // 5XY0
+    SkipEqReg(u8, u8),
+    // 6XNN
+    SetImm(u8, u8),
+    // 7XNN
+    AddImm(u8, u8),
+    // 8XY0
+    Set(u8, u8),
+    // 8XY1
+    Or(u8, u8),
+    // 8XY2
+    And(u8, u8),
+    // 8XY3
+    Xor(u8, u8),
+    // 8XY4
+    Add(u8, u8),
+    // 8XY5
+    Sub(u8, u8),
+    // 8XY6
+    Shr(u8, u8),
+    // 8XY7
+    SubN(u8, u8),
+    // 8XYE
+    Shl(u8, u8),
+    // 9XY0
+    SkipNeReg(u8, u8),
+    // ANNN
+    SetI(u16),
+    // BNNN, X is only used with the jump quirk
+    JumpOffset(u8, u16),
+    // CXNN
+    Rand(u8, u8),
+    // DXYN
+    Draw(u8, u8, u8),
+    // EX9E
+    SkipKey(u8),
+    // EXA1
+    SkipNotKey(u8),
+    // FX07
+    GetDelay(u8),
+    // FX0A
+    WaitKey(u8),
+    // FX15
+    SetDelay(u8),
+    // FX18
+    SetSound(u8),
+    // FX1E
+    AddI(u8),
+    // FX29
+    Font(u8),
+    // FX30
+    BigFont(u8),
+    // FX33
+    Bcd(u8),
+    // FX55
+    Store(u8),
+    // FX65
+    Load(u8),
+    Invalid(u16),
+}
+
+impl Op {
+    pub fn decode(instruction: u16) -> Op {
+        let nnn = instruction & 0x0fff;
+        let x = ((instruction & 0x0f00) >> 8) as u8;
+        let y = ((instruction & 0x00f0) >> 4) as u8;
+        let nn = (instruction & 0x00ff) as u8;
+        let n = (instruction & 0x000f) as u8;
+
+        match instruction & 0xf000 {
+            0x0000 if x != 0 => Op::Sys(nnn),
+            0x0000 => match nn {
+                0xe0 => Op::Cls,
+                0xee => Op::Ret,
+                _ => Op::Invalid(instruction),
+            },
+            0x1000 => Op::Jump(nnn),
+            0x2000 => Op::Call(nnn),
+            0x3000 => Op::SkipEqImm(x, nn),
+            0x4000 => Op::SkipNeImm(x, nn),
+            0x5000 if n == 0 => Op::SkipEqReg(x, y),
+            0x6000 => Op::SetImm(x, nn),
+            0x7000 => Op::AddImm(x, nn),
+            0x8000 => match n {
+                0x0 => Op::Set(x, y),
+                0x1 => Op::Or(x, y),
+                0x2 => Op::And(x, y),
+                0x3 => Op::Xor(x, y),
+                0x4 => Op::Add(x, y),
+                0x5 => Op::Sub(x, y),
+                0x6 => Op::Shr(x, y),
+                0x7 => Op::SubN(x, y),
+                0xe => Op::Shl(x, y),
+                _ => Op::Invalid(instruction),
+            },
+            0x9000 if n == 0 => Op::SkipNeReg(x, y),
+            0xa000 => Op::SetI(nnn),
+            0xb000 => Op::JumpOffset(x, nnn),
+            0xc000 => Op::Rand(x, nn),
+            0xd000 => Op::Draw(x, y, n),
+            0xe000 => match nn {
+                0x9e => Op::SkipKey(x),
+                0xa1 => Op::SkipNotKey(x),
+                _ => Op::Invalid(instruction),
+            },
+            0xf000 => match nn {
+                0x07 => Op::GetDelay(x),
+                0x0a => Op::WaitKey(x),
+                0x15 => Op::SetDelay(x),
+                0x18 => Op::SetSound(x),
+                0x1e => Op::AddI(x),
+                0x29 => Op::Font(x),
+                0x30 => Op::BigFont(x),
+                0x33 => Op::Bcd(x),
+                0x55 => Op::Store(x),
+                0x65 => Op::Load(x),
+                _ => Op::Invalid(instruction),
+            },
+            _ => Op::Invalid(instruction),
+        }
+    }
+
+    // the instruction pattern, used to group instructions in reports
+    pub fn class(&self) -> &'static str {
+        match self {
+            Op::Sys(_) => "0NNN",
+            Op::Cls => "00E0",
+            Op::Ret => "00EE",
+            Op::Jump(_) => "1NNN",
+            Op::Call(_) => "2NNN",
+            Op::SkipEqImm(..) => "3XNN",
+            Op::SkipNeImm(..) => "4XNN",
+            Op::SkipEqReg(..) => "5XY0",
+            Op::SetImm(..) => "6XNN",
+            Op::AddImm(..) => "7XNN",
+            Op::Set(..) => "8XY0",
+            Op::Or(..) => "8XY1",
+            Op::And(..) => "8XY2",
+            Op::Xor(..) => "8XY3",
+            Op::Add(..) => "8XY4",
+            Op::Sub(..) => "8XY5",
+            Op::Shr(..) => "8XY6",
+            Op::SubN(..) => "8XY7",
+            Op::Shl(..) => "8XYE",
+            Op::SkipNeReg(..) => "9XY0",
+            Op::SetI(_) => "ANNN",
+            Op::JumpOffset(..) => "BNNN",
+            Op::Rand(..) => "CXNN",
+            Op::Draw(..) => "DXYN",
+            Op::SkipKey(_) => "EX9E",
+            Op::SkipNotKey(_) => "EXA1",
+            Op::GetDelay(_) => "FX07",
+            Op::WaitKey(_) => "FX0A",
+            Op::SetDelay(_) => "FX15",
+            Op::SetSound(_) => "FX18",
+            Op::AddI(_) => "FX1E",
+            Op::Font(_) => "FX29",
+            Op::BigFont(_) => "FX30",
+            Op::Bcd(_) => "FX33",
+            Op::Store(_) => "FX55",
+            Op::Load(_) => "FX65",
+            Op::Invalid(_) => "invalid",
+        }
+    }
+}
+
+// cowgod style mnemonics, like the ones printed by Cpu::step with debug on
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Op::Sys(nnn) => write!(f, "sys {:#05x}", nnn),
+            Op::Cls => write!(f, "cls"),
+            Op::Ret => write!(f, "ret"),
+            Op::Jump(nnn) => write!(f, "jp {:#05x}", nnn),
+            Op::Call(nnn) => write!(f, "call {:#05x}", nnn),
+            Op::SkipEqImm(x, nn) => write!(f, "se v{:x}, {:#04x}", x, nn),
+            Op::SkipNeImm(x, nn) => write!(f, "sne v{:x}, {:#04x}", x, nn),
+            Op::SkipEqReg(x, y) => write!(f, "se v{:x}, v{:x}", x, y),
+            Op::SetImm(x, nn) => write!(f, "ld v{:x}, {:#04x}", x, nn),
+            Op::AddImm(x, nn) => write!(f, "add v{:x}, {:#04x}", x, nn),
+            Op::Set(x, y) => write!(f, "ld v{:x}, v{:x}", x, y),
+            Op::Or(x, y) => write!(f, "or v{:x}, v{:x}", x, y),
+            Op::And(x, y) => write!(f, "and v{:x}, v{:x}", x, y),
+            Op::Xor(x, y) => write!(f, "xor v{:x}, v{:x}", x, y),
+            Op::Add(x, y) => write!(f, "add v{:x}, v{:x}", x, y),
+            Op::Sub(x, y) => write!(f, "sub v{:x}, v{:x}", x, y),
+            Op::Shr(x, y) => write!(f, "shr v{:x}, v{:x}", x, y),
+            Op::SubN(x, y) => write!(f, "subn v{:x}, v{:x}", x, y),
+            Op::Shl(x, y) => write!(f, "shl v{:x}, v{:x}", x, y),
+            Op::SkipNeReg(x, y) => write!(f, "sne v{:x}, v{:x}", x, y),
+            Op::SetI(nnn) => write!(f, "ld i, {:#05x}", nnn),
+            Op::JumpOffset(_, nnn) => write!(f, "jp v0, {:#05x}", nnn),
+            Op::Rand(x, nn) => write!(f, "rnd v{:x}, {:#04x}", x, nn),
+            Op::Draw(x, y, n) => write!(f, "drw v{:x}, v{:x}, {}", x, y, n),
+            Op::SkipKey(x) => write!(f, "skp v{:x}", x),
+            Op::SkipNotKey(x) => write!(f, "sknp v{:x}", x),
+            Op::GetDelay(x) => write!(f, "ld v{:x}, dt", x),
+            Op::WaitKey(x) => write!(f, "ld v{:x}, k", x),
+            Op::SetDelay(x) => write!(f, "ld dt, v{:x}", x),
+            Op::SetSound(x) => write!(f, "ld st, v{:x}", x),
+            Op::AddI(x) => write!(f, "add i, v{:x}", x),
+            Op::Font(x) => write!(f, "ld f, v{:x}", x),
+            Op::BigFont(x) => write!(f, "ld hf, v{:x}", x),
+            Op::Bcd(x) => write!(f, "ld b, v{:x}", x),
+            Op::Store(x) => write!(f, "ld [i], v{:x}", x),
+            Op::Load(x) => write!(f, "ld v{:x}, [i]", x),
+            Op::Invalid(instruction) => write!(f, "invalid {:04X}", instruction),
+        }
+    }
+}
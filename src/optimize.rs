@@ -0,0 +1,382 @@
+// rewrites a rom to be smaller and faster without changing what it does. only code the control
+// flow graph reaches is touched, and it goes in passes until nothing changes:
+//   jumps and calls to a jump go straight to where the chain ends
+//   jumps to the next instruction are removed
+//   7XNN adds following each other, or a 6XNN load, are folded into one instruction
+//   code that can't be reached is dropped (the same code lint warns about)
+// every removed byte moves what comes after it, so reachable jumps, calls and ANNN that point into
+// the program are relocated. nothing is removed from roms with indirect jumps, the tables they jump
+// through can't be relocated, and instructions I points into are left alone in case the rom reads
+// them as data. neither is unreachable code that a sprite or FX65 reads, or that sits right after
+// an ANNN target. --verify runs both roms with the same input to catch what this can't see
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{Error, ErrorKind},
+    mem,
+    path::{Path, PathBuf},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    cfg::{Cfg, Known},
+    cpu::{Cpu, ExecuteError},
+    keyboard::KeyBoard,
+    lint, loader,
+    memory_map::MemoryMap,
+    opcode::Op,
+};
+
+// a pass can enable more of the same, e.g. folding adds between a jump and its target
+const MAX_PASSES: usize = 16;
+
+// instructions per frame when verifying
+const VERIFY_TICKRATE: u32 = 10;
+
+// frames a key is held, or nothing is pressed, while verifying
+const VERIFY_KEY_FRAMES: u64 = 12;
+
+// the most a sprite reads, 16x16
+const MAX_SPRITE_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub threaded_jumps: usize,
+    pub removed_jumps: usize,
+    pub folded_adds: usize,
+    pub dropped_bytes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyOptions {
+    pub frames: u64,
+    pub seed: u64,
+}
+
+fn is_skip(op: Op) -> bool {
+    matches!(
+        op,
+        Op::SkipEqImm(..)
+            | Op::SkipNeImm(..)
+            | Op::SkipEqReg(..)
+            | Op::SkipNeReg(..)
+            | Op::SkipKey(_)
+            | Op::SkipNotKey(_)
+    )
+}
+
+// the address a jump, call or ANNN points at, it is always the low 12 bits
+fn target(instruction: u16) -> Option<usize> {
+    match Op::decode(instruction) {
+        Op::Jump(nnn) | Op::Call(nnn) | Op::SetI(nnn) => Some(nnn as usize),
+        _ => None,
+    }
+}
+
+// the bytes sprites, FX65 and friends read or write through an I that is known within the block
+fn read_through_i(cfg: &Cfg, start: usize) -> BTreeSet<usize> {
+    let memory_map = MemoryMap {
+        load_address: start,
+        ..MemoryMap::default()
+    };
+    let mut read = BTreeSet::new();
+    for block in cfg.blocks.values() {
+        let mut known = Known::new();
+        for &(_, instruction) in &block.instructions {
+            let op = Op::decode(instruction);
+            let len = match op {
+                // DXY0 draws a 16x16 sprite
+                Op::Draw(_, _, 0) => 32,
+                Op::Draw(_, _, n) => n as usize,
+                Op::Load(x) | Op::Store(x) => x as usize + 1,
+                Op::Bcd(_) => 3,
+                _ => 0,
+            };
+            if let Some(i) = known.i {
+                read.extend(i..i + len);
+            }
+            known.apply(op, &memory_map);
+        }
+    }
+    read
+}
+
+// one round of every optimization, None when nothing could be done
+fn pass(program: &[u8], start: usize, stats: &mut Stats) -> Option<Vec<u8>> {
+    let cfg = Cfg::build(program, start);
+    let instructions = cfg
+        .blocks
+        .values()
+        .flat_map(|block| block.instructions.iter().copied())
+        .collect::<BTreeMap<usize, u16>>();
+    let leaders = cfg.blocks.keys().copied().collect::<BTreeSet<usize>>();
+    let op = |addr: usize| instructions.get(&addr).map(|&i| Op::decode(i));
+    let shadowed = |addr: usize| addr >= 2 && op(addr - 2).is_some_and(is_skip);
+    let pointers = instructions
+        .values()
+        .filter_map(|&i| match Op::decode(i) {
+            Op::SetI(nnn) => Some(nnn as usize),
+            _ => None,
+        })
+        .collect::<BTreeSet<usize>>();
+    let read = read_through_i(&cfg, start);
+    let pinned = pointers
+        .iter()
+        .flat_map(|&target| [target, target.wrapping_sub(1)])
+        .chain(read.iter().flat_map(|&addr| [addr, addr.wrapping_sub(1)]))
+        .filter(|addr| instructions.contains_key(addr))
+        .collect::<BTreeSet<usize>>();
+    // instructions that overlap each other can't be moved apart
+    let overlapping = instructions
+        .keys()
+        .any(|&a| instructions.contains_key(&(a + 1)));
+    let removable = cfg.indirect_jumps().is_empty() && !overlapping;
+
+    let mut rewritten: BTreeMap<usize, u16> = BTreeMap::new();
+    let mut deleted: BTreeSet<usize> = BTreeSet::new();
+    let current = |rewritten: &BTreeMap<usize, u16>, addr: usize| {
+        rewritten.get(&addr).copied().unwrap_or(instructions[&addr])
+    };
+
+    for (&addr, &instruction) in &instructions {
+        let (Op::Jump(nnn) | Op::Call(nnn)) = Op::decode(instruction) else {
+            continue;
+        };
+        if pinned.contains(&addr) {
+            continue;
+        }
+        let mut end = nnn as usize;
+        let mut seen = BTreeSet::from([addr]);
+        while let Some(Op::Jump(next)) = op(end) {
+            if !seen.insert(end) {
+                break;
+            }
+            end = next as usize;
+        }
+        if end != nnn as usize && end != addr {
+            rewritten.insert(addr, instruction & 0xf000 | end as u16);
+            stats.threaded_jumps += 1;
+        }
+    }
+
+    if removable {
+        for &addr in instructions.keys() {
+            if pinned.contains(&addr) || shadowed(addr) {
+                continue;
+            }
+            if Op::decode(current(&rewritten, addr)) == Op::Jump(addr as u16 + 2) {
+                deleted.extend([addr, addr + 1]);
+                stats.removed_jumps += 1;
+            }
+        }
+
+        for &addr in instructions.keys() {
+            if deleted.contains(&addr) || pinned.contains(&addr) || shadowed(addr) {
+                continue;
+            }
+            let (x, mut nn, opcode) = match Op::decode(current(&rewritten, addr)) {
+                Op::SetImm(x, nn) => (x, nn, 0x6000),
+                Op::AddImm(x, nn) => (x, nn, 0x7000),
+                _ => continue,
+            };
+            let mut next = addr + 2;
+            while let Some(Op::AddImm(y, mm)) = op(next) {
+                if y != x || leaders.contains(&next) || pinned.contains(&next) {
+                    break;
+                }
+                nn = nn.wrapping_add(mm);
+                deleted.extend([next, next + 1]);
+                stats.folded_adds += 1;
+                next += 2;
+            }
+            if next != addr + 2 {
+                rewritten.insert(addr, opcode | (x as u16) << 8 | nn as u16);
+            }
+            // adding nothing doesn't touch VF either
+            if opcode == 0x7000 && nn == 0 {
+                deleted.extend([addr, addr + 1]);
+                stats.folded_adds += 1;
+            }
+        }
+
+        // where I is set from isn't always known, so a sprite's worth of bytes after every ANNN
+        // target stays too
+        let data = |addr: &usize| {
+            read.contains(addr)
+                || pointers
+                    .range(..=*addr)
+                    .next_back()
+                    .is_some_and(|&target| addr - target < MAX_SPRITE_BYTES)
+        };
+        for code in lint::unreachable_code(program, &cfg) {
+            let code = code.filter(|addr| !data(addr)).collect::<Vec<usize>>();
+            stats.dropped_bytes += code.len();
+            deleted.extend(code);
+        }
+    }
+
+    if rewritten.is_empty() && deleted.is_empty() {
+        return None;
+    }
+
+    let relocate = |addr: usize| addr - deleted.range(..addr).count();
+    let mut rom = program.to_vec();
+    for &addr in instructions.keys() {
+        let instruction = current(&rewritten, addr);
+        let instruction = match target(instruction) {
+            // jumps may go to the end of the program, ANNN there points at free memory
+            Some(nnn) if nnn >= start && nnn <= cfg.end && !deleted.is_empty() => {
+                match Op::decode(instruction) {
+                    Op::SetI(_) if nnn == cfg.end => instruction,
+                    _ => instruction & 0xf000 | relocate(nnn) as u16,
+                }
+            }
+            _ => instruction,
+        };
+        let offset = addr - start;
+        rom[offset..offset + 2].copy_from_slice(&instruction.to_be_bytes());
+    }
+    Some(
+        rom.into_iter()
+            .enumerate()
+            .filter(|(offset, _)| !deleted.contains(&(start + offset)))
+            .map(|(_, byte)| byte)
+            .collect(),
+    )
+}
+
+pub fn optimize(program: &[u8], start: usize) -> (Vec<u8>, Stats) {
+    let mut stats = Stats::default();
+    let mut program = program.to_vec();
+    for _ in 0..MAX_PASSES {
+        match pass(&program, start, &mut stats) {
+            Some(optimized) => program = optimized,
+            None => break,
+        }
+    }
+    (program, stats)
+}
+
+// every picture a rom draws while it's fed pseudo random keys. the display is compared after each
+// instruction rather than each frame, the optimized rom gets further in a frame and would show
+// pictures half drawn at other points. runs stop early when the rom fails, with the reason
+fn screens(
+    program: &[u8],
+    start: usize,
+    options: &VerifyOptions,
+) -> Result<(Vec<Vec<u64>>, Option<ExecuteError>), Error> {
+    let memory_map = MemoryMap {
+        load_address: start,
+        ..MemoryMap::default()
+    };
+    let mut cpu = Cpu::with_memory_map(false, memory_map);
+    cpu.add_program(program)?;
+    cpu.seed(options.seed);
+    let mut keyboard = KeyBoard::new();
+    let mut keys = StdRng::seed_from_u64(options.seed);
+    let mut screens = vec![cpu.d_buffer.rows().to_vec()];
+    for frame in 0..options.frames {
+        if frame % VERIFY_KEY_FRAMES == 0 {
+            keyboard.key_pressed = keys.gen_bool(0.5).then(|| keys.gen_range(0..16));
+        }
+        for _ in 0..VERIFY_TICKRATE {
+            if let Err(e) = cpu.step(&keyboard) {
+                return Ok((screens, Some(e)));
+            }
+            let rows = cpu.d_buffer.rows();
+            if screens.last().is_none_or(|last| last != rows) {
+                screens.push(rows.to_vec());
+            }
+        }
+        cpu.tick_timers();
+    }
+    Ok((screens, None))
+}
+
+// compares the screens of both roms, a faster optimized rom may get further in the same number of
+// frames so one only has to be the start of the other. returns the number of screens compared
+pub fn verify(
+    original: &[u8],
+    optimized: &[u8],
+    start: usize,
+    options: &VerifyOptions,
+) -> Result<usize, Error> {
+    let (before, before_error) = screens(original, start, options)?;
+    let (after, after_error) = screens(optimized, start, options)?;
+    let same = before
+        .iter()
+        .zip(&after)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if same < before.len().min(after.len()) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "the optimized rom draws something else for picture {} of {}",
+                same + 1,
+                before.len()
+            ),
+        ));
+    }
+    // a rom that stopped has to stop the same way at the same picture, the messages hold addresses
+    // that moved so only the kind of error is compared
+    let stopped = |error: &Option<ExecuteError>| error.as_ref().map(mem::discriminant);
+    let differs = match before.len().cmp(&after.len()) {
+        Ordering::Equal => stopped(&before_error) != stopped(&after_error),
+        Ordering::Greater => after_error.is_some(),
+        Ordering::Less => before_error.is_some(),
+    };
+    if differs {
+        let describe = |error: Option<ExecuteError>, pictures: usize| match error {
+            Some(e) => format!("stops after {} pictures: {}", pictures, e),
+            None => format!("keeps running for {} pictures", pictures),
+        };
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "the original rom {} but the optimized one {}",
+                describe(before_error, before.len()),
+                describe(after_error, after.len())
+            ),
+        ));
+    }
+    Ok(same)
+}
+
+pub fn run(
+    src: &str,
+    output: Option<PathBuf>,
+    load_address: usize,
+    verify_options: Option<VerifyOptions>,
+) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    let (optimized, stats) = optimize(&program, load_address);
+    println!(
+        "{}: {} -> {} bytes, {} jumps threaded, {} jumps removed, {} adds folded, {} bytes of unreachable code dropped",
+        src,
+        program.len(),
+        optimized.len(),
+        stats.threaded_jumps,
+        stats.removed_jumps,
+        stats.folded_adds,
+        stats.dropped_bytes
+    );
+    if let Some(options) = verify_options {
+        let screens = verify(&program, &optimized, load_address, &options)?;
+        println!(
+            "verified: both roms drew the same {} pictures in {} frames",
+            screens, options.frames
+        );
+    }
+    let output = output.unwrap_or_else(|| Path::new(src).with_extension("opt.ch8"));
+    fs::write(&output, &optimized).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to write {}: {}", output.display(), e),
+        )
+    })?;
+    println!("Wrote {} bytes to {}", optimized.len(), output.display());
+    Ok(())
+}
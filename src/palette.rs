@@ -0,0 +1,49 @@
+// colors used to render the 1bit display buffer. both values are 0RGB like minifb expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: u32,
+    pub foreground: u32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            background: 0x000000,
+            foreground: 0xffffff,
+        }
+    }
+}
+
+impl Palette {
+    // parses "<background>,<foreground>" where both are 6 digit hex colors, eg "000000,ffffff"
+    pub fn parse(value: &str) -> Result<Palette, String> {
+        let colors = value
+            .split(',')
+            .map(|c| parse_color(c.trim()))
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        match colors[..] {
+            [background, foreground] => Ok(Palette {
+                background,
+                foreground,
+            }),
+            _ => Err(format!(
+                "expected two comma separated colors (background,foreground), got \"{}\"",
+                value
+            )),
+        }
+    }
+}
+
+pub fn parse_color(value: &str) -> Result<u32, String> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("\"{}\" is not a 6 digit hex color", value));
+    }
+    u32::from_str_radix(hex, 16).map_err(|_| format!("\"{}\" is not a 6 digit hex color", value))
+}
+
+#[inline]
+pub fn to_rgb(color: u32) -> [u8; 3] {
+    [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+}
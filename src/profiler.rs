@@ -0,0 +1,228 @@
+// counts where a rom spends its cycles: executions per address and per instruction class, and a
+// call graph of the 2NNN subroutines built by following calls and returns. time is measured in
+// executed instructions since that's what the tickrate budgets.
+// the folded stacks written by write_folded can be turned into a flamegraph with flamegraph.pl or
+// inferno-flamegraph
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::Path,
+};
+
+use crate::{cpu::Cpu, opcode::Op};
+
+// rows shown in the hot spot table of the report
+const HOT_SPOTS: usize = 20;
+
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    total: u64,
+    per_address: BTreeMap<usize, u64>,
+    per_class: BTreeMap<&'static str, u64>,
+    // entry addresses of the subroutines currently being executed, innermost last
+    stack: Vec<usize>,
+    // instructions executed since the stack last changed
+    pending: u64,
+    // instructions executed with exactly this stack
+    folded: HashMap<Vec<usize>, u64>,
+    // (caller, callee) -> number of calls, None is the main program
+    calls: HashMap<(Option<usize>, usize), u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subroutine {
+    pub address: usize,
+    pub calls: u64,
+    // instructions executed in the subroutine itself
+    pub self_cycles: u64,
+    // including everything it called
+    pub total_cycles: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    // called with every instruction right before it is executed
+    pub fn record(&mut self, pc: usize, op: Op) {
+        self.total += 1;
+        self.pending += 1;
+        *self.per_address.entry(pc).or_default() += 1;
+        *self.per_class.entry(op.class()).or_default() += 1;
+
+        match op {
+            Op::Call(nnn) => {
+                *self
+                    .calls
+                    .entry((self.stack.last().copied(), nnn as usize))
+                    .or_default() += 1;
+                self.flush();
+                self.stack.push(nnn as usize);
+            }
+            // a return without a call is an error that stops the rom anyway
+            Op::Ret if !self.stack.is_empty() => {
+                self.flush();
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.pending > 0 {
+            *self.folded.entry(self.stack.clone()).or_default() += self.pending;
+            self.pending = 0;
+        }
+    }
+
+    // busiest addresses first
+    pub fn hot_spots(&self) -> Vec<(usize, u64)> {
+        let mut spots = self
+            .per_address
+            .iter()
+            .map(|(&addr, &count)| (addr, count))
+            .collect::<Vec<(usize, u64)>>();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    // stacks with the instructions executed in each, the main program is the empty stack
+    pub fn folded_stacks(&self) -> Vec<(Vec<usize>, u64)> {
+        let mut folded = self.folded.clone();
+        if self.pending > 0 {
+            *folded.entry(self.stack.clone()).or_default() += self.pending;
+        }
+        let mut folded = folded.into_iter().collect::<Vec<(Vec<usize>, u64)>>();
+        folded.sort();
+        folded
+    }
+
+    // every subroutine that was called, the most expensive first
+    pub fn subroutines(&self) -> Vec<Subroutine> {
+        let mut subroutines: BTreeMap<usize, Subroutine> = BTreeMap::new();
+        for (&(_, callee), &count) in &self.calls {
+            subroutines
+                .entry(callee)
+                .or_insert(Subroutine {
+                    address: callee,
+                    calls: 0,
+                    self_cycles: 0,
+                    total_cycles: 0,
+                })
+                .calls += count;
+        }
+        for (stack, count) in self.folded_stacks() {
+            if let Some(innermost) = stack.last() {
+                if let Some(subroutine) = subroutines.get_mut(innermost) {
+                    subroutine.self_cycles += count;
+                }
+            }
+            // recursive calls only count once
+            let mut seen = stack.clone();
+            seen.sort();
+            seen.dedup();
+            for addr in seen {
+                if let Some(subroutine) = subroutines.get_mut(&addr) {
+                    subroutine.total_cycles += count;
+                }
+            }
+        }
+        let mut subroutines = subroutines.into_values().collect::<Vec<Subroutine>>();
+        subroutines.sort_by(|a, b| {
+            b.total_cycles
+                .cmp(&a.total_cycles)
+                .then(a.address.cmp(&b.address))
+        });
+        subroutines
+    }
+
+    // (caller, callee, calls) with None as the main program
+    pub fn call_graph(&self) -> Vec<(Option<usize>, usize, u64)> {
+        let mut edges = self
+            .calls
+            .iter()
+            .map(|(&(caller, callee), &count)| (caller, callee, count))
+            .collect::<Vec<(Option<usize>, usize, u64)>>();
+        edges.sort();
+        edges
+    }
+
+    pub fn report(&self, cpu: &Cpu) -> String {
+        let share = |count: u64| count as f64 * 100.0 / self.total.max(1) as f64;
+        let name = |addr: Option<usize>| match addr {
+            Some(addr) => format!("{:#05x}", addr),
+            None => String::from("main"),
+        };
+        let mut out = format!("profile of {} executed instructions\n", self.total);
+
+        out.push_str("\nhot spots:\n");
+        for (addr, count) in self.hot_spots().into_iter().take(HOT_SPOTS) {
+            let instruction = cpu.instruction_at(addr).unwrap_or(0);
+            out.push_str(&format!(
+                "  {:#05x}  {:04X} {:<8} {:>12} {:>6.2}%\n",
+                addr,
+                instruction,
+                Op::decode(instruction).class(),
+                count,
+                share(count)
+            ));
+        }
+
+        out.push_str("\ninstruction classes:\n");
+        let mut classes = self.per_class.iter().collect::<Vec<(&&str, &u64)>>();
+        classes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (class, &count) in classes {
+            out.push_str(&format!(
+                "  {:<8} {:>12} {:>6.2}%\n",
+                class,
+                count,
+                share(count)
+            ));
+        }
+
+        let subroutines = self.subroutines();
+        if !subroutines.is_empty() {
+            out.push_str(&format!(
+                "\nsubroutines:\n  {:<7} {:>10} {:>12} {:>8} {:>12} {:>8} {:>10}\n",
+                "address", "calls", "self", "", "total", "", "per call"
+            ));
+            for subroutine in subroutines {
+                out.push_str(&format!(
+                    "  {:<7} {:>10} {:>12} {:>7.2}% {:>12} {:>7.2}% {:>10.1}\n",
+                    name(Some(subroutine.address)),
+                    subroutine.calls,
+                    subroutine.self_cycles,
+                    share(subroutine.self_cycles),
+                    subroutine.total_cycles,
+                    share(subroutine.total_cycles),
+                    subroutine.total_cycles as f64 / subroutine.calls.max(1) as f64
+                ));
+            }
+
+            out.push_str("\ncall graph:\n");
+            for (caller, callee, count) in self.call_graph() {
+                out.push_str(&format!(
+                    "  {} -> {} x{}\n",
+                    name(caller),
+                    name(Some(callee)),
+                    count
+                ));
+            }
+        }
+        out
+    }
+
+    // one line per stack: "main;0x2a0;0x2f0 1234"
+    pub fn write_folded(&self, path: &Path) -> Result<(), Error> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for (stack, count) in self.folded_stacks() {
+            let frames = std::iter::once(String::from("main"))
+                .chain(stack.iter().map(|addr| format!("{:#05x}", addr)))
+                .collect::<Vec<String>>();
+            writeln!(out, "{} {}", frames.join(";"), count)?;
+        }
+        out.flush()
+    }
+}
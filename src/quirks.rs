@@ -0,0 +1,76 @@
+// behaviors that differ between chip8 interpreters. roms written for one platform often rely on
+// them, so they are configurable instead of hard coded in Cpu::step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 0x8XY6 and 0x8XYE shift VX in place instead of storing the shifted VY in VX
+    pub shift_in_place: bool,
+    // 0xFX55 and 0xFX65 leave I pointing after the last register that was saved/loaded
+    pub load_store_increments_i: bool,
+    // 0x8XY1, 0x8XY2 and 0x8XY3 set VF to 0
+    pub logic_resets_vf: bool,
+    // 0xBNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // sprites are cut off at the edges of the screen instead of wrapping around
+    pub clip_sprites: bool,
+}
+
+// the behavior this emulator always had
+pub const DEFAULT: Quirks = Quirks {
+    shift_in_place: false,
+    load_store_increments_i: true,
+    logic_resets_vf: false,
+    jump_uses_vx: false,
+    clip_sprites: false,
+};
+
+// the original COSMAC VIP interpreter
+pub const CHIP8: Quirks = Quirks {
+    shift_in_place: false,
+    load_store_increments_i: true,
+    logic_resets_vf: true,
+    jump_uses_vx: false,
+    clip_sprites: true,
+};
+
+// SUPER-CHIP 1.1 on the HP48
+pub const SCHIP: Quirks = Quirks {
+    shift_in_place: true,
+    load_store_increments_i: false,
+    logic_resets_vf: false,
+    jump_uses_vx: true,
+    clip_sprites: true,
+};
+
+pub const XOCHIP: Quirks = Quirks {
+    shift_in_place: false,
+    load_store_increments_i: true,
+    logic_resets_vf: false,
+    jump_uses_vx: false,
+    clip_sprites: false,
+};
+
+pub const PRESETS: [(&str, Quirks); 4] = [
+    ("default", DEFAULT),
+    ("chip8", CHIP8),
+    ("schip", SCHIP),
+    ("xochip", XOCHIP),
+];
+
+impl Default for Quirks {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Quirks {
+    pub fn preset(name: &str) -> Option<Quirks> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, quirks)| *quirks)
+    }
+
+    pub fn preset_names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+}
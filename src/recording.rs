@@ -0,0 +1,189 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::palette::{to_rgb, Palette};
+
+// recordings always follow the emulated timeline, one emulated frame is 1/60th of a second
+const FRAMES_PER_SECOND: u64 = 60;
+// most gif decoders bump delays below 2 hundredths of a second up to 10, so never go below it
+const MIN_GIF_DELAY: u64 = 2;
+
+pub struct Recorder {
+    sink: Sink,
+    width: usize,
+    height: usize,
+    palette: Palette,
+    frames: u64,
+}
+
+enum Sink {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        // frame waiting to be written, kept until we know how long it stays on screen
+        pending: Option<(Vec<u8>, u64)>,
+    },
+    Y4m(BufWriter<File>),
+}
+
+impl Recorder {
+    // the format is picked from the extension, .gif or .y4m
+    pub fn start(
+        path: &Path,
+        width: usize,
+        height: usize,
+        palette: Palette,
+    ) -> Result<Recorder, Error> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        let sink = match ext.as_deref() {
+            Some("gif") => {
+                let file = BufWriter::new(File::create(path)?);
+                let global_palette =
+                    [to_rgb(palette.background), to_rgb(palette.foreground)].concat();
+                let mut encoder =
+                    gif::Encoder::new(file, width as u16, height as u16, &global_palette)
+                        .map_err(Error::other)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(Error::other)?;
+                Sink::Gif {
+                    encoder,
+                    pending: None,
+                }
+            }
+            Some("y4m") => {
+                let mut file = BufWriter::new(File::create(path)?);
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                    width, height, FRAMES_PER_SECOND
+                )?;
+                Sink::Y4m(file)
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Unsupported recording format for {}. Use .gif or .y4m",
+                        path.display()
+                    ),
+                ))
+            }
+        };
+
+        Ok(Recorder {
+            sink,
+            width,
+            height,
+            palette,
+            frames: 0,
+        })
+    }
+
+    // called once for every emulated frame with the 0RGB pixels that were on screen
+    pub fn push_frame(&mut self, pixels: &[u32]) -> Result<(), Error> {
+        let frame = self.frames;
+        self.frames += 1;
+
+        match &mut self.sink {
+            Sink::Gif { encoder, pending } => {
+                let indices = pixels
+                    .iter()
+                    .map(|&p| (p != self.palette.background) as u8)
+                    .collect::<Vec<u8>>();
+                match pending {
+                    Some((previous, _)) if *previous == indices => {}
+                    Some((_, start))
+                        if centiseconds(frame) - centiseconds(*start) < MIN_GIF_DELAY =>
+                    {
+                        // the pending frame would be shown for too short, replace it but keep its start
+                        let start = *start;
+                        *pending = Some((indices, start));
+                    }
+                    _ => {
+                        if let Some((previous, start)) = pending.take() {
+                            write_gif_frame(
+                                encoder,
+                                self.width,
+                                self.height,
+                                previous,
+                                centiseconds(frame) - centiseconds(start),
+                            )?;
+                        }
+                        *pending = Some((indices, frame));
+                    }
+                }
+            }
+            Sink::Y4m(out) => {
+                out.write_all(b"FRAME\n")?;
+                let yuv = pixels
+                    .iter()
+                    .map(|&p| to_ycbcr(p))
+                    .collect::<Vec<[u8; 3]>>();
+                for plane in 0..3 {
+                    let bytes = yuv.iter().map(|c| c[plane]).collect::<Vec<u8>>();
+                    out.write_all(&bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        match self.sink {
+            Sink::Gif {
+                mut encoder,
+                pending,
+            } => {
+                if let Some((previous, start)) = pending {
+                    let delay =
+                        (centiseconds(self.frames) - centiseconds(start)).max(MIN_GIF_DELAY);
+                    write_gif_frame(&mut encoder, self.width, self.height, previous, delay)?;
+                }
+                encoder.into_inner().map_err(Error::other)?.flush()
+            }
+            Sink::Y4m(mut out) => out.flush(),
+        }
+    }
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    indices: Vec<u8>,
+    delay: u64,
+) -> Result<(), Error> {
+    let frame = gif::Frame {
+        width: width as u16,
+        height: height as u16,
+        delay: delay.min(u16::MAX as u64) as u16,
+        buffer: Cow::Owned(indices),
+        ..gif::Frame::default()
+    };
+    encoder.write_frame(&frame).map_err(Error::other)
+}
+
+// time at which the given frame starts, in the hundredths of a second gif uses for delays
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+// limited range BT.601, which is what players assume when the y4m header has no color range
+fn to_ycbcr(color: u32) -> [u8; 3] {
+    let [r, g, b] = to_rgb(color).map(|c| c as f32 / 255.0);
+    let y = 16.0 + 65.481 * r + 128.553 * g + 24.966 * b;
+    let cb = 128.0 - 37.797 * r - 74.203 * g + 112.0 * b;
+    let cr = 128.0 + 112.0 * r - 93.786 * g - 18.214 * b;
+    [y, cb, cr].map(|c| c.round().clamp(0.0, 255.0) as u8)
+}
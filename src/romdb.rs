@@ -0,0 +1,244 @@
+// per game settings looked up by the sha1 of the rom. the database uses the same layout as
+// programs.json from the community chip-8-database (https://github.com/chip-8/chip-8-database),
+// so a copy of that file can be passed with --rom-db. a few entries for the roms in test_files
+// are built in
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use minifb::Key;
+use serde_json::{Map, Value};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    font::Font,
+    keyboard::DEFAULT_KEYMAP,
+    loader::RomOptions,
+    palette::{parse_color, Palette},
+    quirks::{self, Quirks},
+};
+
+const BUILTIN: &str = include_str!("../data/programs.json");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Option<String>,
+    pub options: RomOptions,
+    // (what the key does, chip8 key)
+    pub key_hints: Vec<(String, u8)>,
+    // quirks the rom needs that Cpu doesn't have, by their name in the database
+    pub unsupported_quirks: Vec<String>,
+}
+
+pub struct RomDb {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDb {
+    pub fn builtin() -> RomDb {
+        RomDb::parse(BUILTIN).expect("the built in rom database should be valid")
+    }
+
+    // accepts programs.json itself or the database directory containing it
+    pub fn load(path: &Path) -> Result<RomDb, Error> {
+        let path = if path.is_dir() {
+            path.join("programs.json")
+        } else {
+            path.to_path_buf()
+        };
+        let src = fs::read_to_string(&path).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Failed to read rom database {}: {}", path.display(), e),
+            )
+        })?;
+        RomDb::parse(&src).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} is not a valid rom database: {}", path.display(), e),
+            )
+        })
+    }
+
+    pub fn parse(src: &str) -> Result<RomDb, String> {
+        let programs: Value = serde_json::from_str(src).map_err(|e| e.to_string())?;
+        let programs = programs.as_array().ok_or("expected an array of programs")?;
+
+        let mut roms = HashMap::new();
+        for program in programs {
+            let title = program
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or("untitled");
+            let Some(Value::Object(entries)) = program.get("roms") else {
+                continue;
+            };
+            for (hash, rom) in entries {
+                roms.insert(hash.to_ascii_lowercase(), rom_info(title, rom));
+            }
+        }
+        Ok(RomDb { roms })
+    }
+
+    // entries from other override the ones already in this database
+    pub fn merge(&mut self, other: RomDb) {
+        self.roms.extend(other.roms);
+    }
+
+    pub fn lookup(&self, program: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(program))
+    }
+}
+
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// the database is maintained by hand, so fields that can't be understood are skipped instead of
+// rejecting the whole file
+fn rom_info(title: &str, rom: &Value) -> RomInfo {
+    let platform = rom
+        .get("platforms")
+        .and_then(Value::as_array)
+        .and_then(|platforms| platforms.first())
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    let mut unsupported_quirks = Vec::new();
+    let quirks = platform
+        .as_deref()
+        .and_then(platform_quirks)
+        .map(|mut quirks| {
+            let overrides = rom
+                .get("quirkyPlatforms")
+                .and_then(|q| q.get(platform.as_deref().unwrap_or_default()))
+                .and_then(Value::as_object);
+            if let Some(overrides) = overrides {
+                unsupported_quirks = apply_quirk_overrides(&mut quirks, overrides);
+            }
+            quirks
+        });
+
+    let tickrate = rom
+        .get("tickrate")
+        .and_then(Value::as_u64)
+        .and_then(|rate| u32::try_from(rate).ok())
+        .filter(|&rate| rate > 0);
+
+    let colors = rom
+        .get("colors")
+        .and_then(|c| c.get("pixels"))
+        .and_then(Value::as_array)
+        .map(|colors| {
+            colors
+                .iter()
+                .filter_map(|c| parse_color(c.as_str()?).ok())
+                .collect::<Vec<u32>>()
+        });
+    let palette = match colors.as_deref() {
+        Some([background, foreground, ..]) => Some(Palette {
+            background: *background,
+            foreground: *foreground,
+        }),
+        _ => None,
+    };
+
+    let key_hints = rom
+        .get("keys")
+        .and_then(Value::as_object)
+        .map(|keys| {
+            keys.iter()
+                .filter_map(|(name, key)| {
+                    let key = u8::try_from(key.as_u64()?).ok().filter(|&k| k <= 0xf)?;
+                    Some((name.clone(), key))
+                })
+                .collect::<Vec<(String, u8)>>()
+        })
+        .unwrap_or_default();
+    let keymap = if key_hints.iter().any(|(name, _)| hint_key(name).is_some()) {
+        let mut keymap = DEFAULT_KEYMAP.to_vec();
+        keymap.extend(
+            key_hints
+                .iter()
+                .filter_map(|(name, key)| Some((hint_key(name)?, *key))),
+        );
+        Some(keymap)
+    } else {
+        None
+    };
+
+    let font = platform.as_deref().and_then(Font::for_platform);
+    RomInfo {
+        title: title.to_string(),
+        platform,
+        options: RomOptions {
+            tickrate,
+            quirks,
+            palette,
+            keymap,
+            font,
+        },
+        key_hints,
+        unsupported_quirks,
+    }
+}
+
+// platform ids from platforms.json in the database
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(quirks::CHIP8),
+        "modernChip8" => Some(Quirks {
+            clip_sprites: true,
+            ..quirks::DEFAULT
+        }),
+        "chip48" | "superchip1" | "superchip" => Some(quirks::SCHIP),
+        "xochip" => Some(quirks::XOCHIP),
+        _ => None,
+    }
+}
+
+// returns the quirks that are turned on but can't be applied
+fn apply_quirk_overrides(quirks: &mut Quirks, overrides: &Map<String, Value>) -> Vec<String> {
+    let flag = |name: &str| overrides.get(name).and_then(Value::as_bool);
+    if let Some(on) = flag("shift") {
+        quirks.shift_in_place = on;
+    }
+    if let Some(on) = flag("memoryLeaveIUnchanged") {
+        quirks.load_store_increments_i = !on;
+    }
+    if let Some(on) = flag("logic") {
+        quirks.logic_resets_vf = on;
+    }
+    if let Some(on) = flag("jump") {
+        quirks.jump_uses_vx = on;
+    }
+    if let Some(on) = flag("wrap") {
+        quirks.clip_sprites = !on;
+    }
+    // FX55 and FX65 adding X instead of X + 1 to I, and waiting for the display before drawing
+    ["memoryIncrementByX", "vblank"]
+        .iter()
+        .filter(|name| flag(name) == Some(true))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+// extra keyboard keys for the hints the database gives, on top of the default keymap
+fn hint_key(name: &str) -> Option<Key> {
+    match name {
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "a" => Some(Key::Space),
+        "b" => Some(Key::LeftShift),
+        _ => None,
+    }
+}
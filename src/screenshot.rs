@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::palette::{to_rgb, Palette};
+
+pub enum ImageFormat {
+    Png,
+    // binary portable pixmap, keeps the palette colors
+    Ppm,
+    // binary portable bitmap, 1bit so only foreground/background survive
+    Pbm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<ImageFormat, Error> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("pbm") => Ok(ImageFormat::Pbm),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unsupported screenshot format for {}. Use .png, .ppm or .pbm",
+                    path.display()
+                ),
+            )),
+        }
+    }
+}
+
+// pixels are 0RGB values (the same layout the window gets) in row major order
+pub fn save(
+    path: &Path,
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+) -> Result<(), Error> {
+    let format = ImageFormat::from_path(path)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    encode(&mut out, &format, pixels, width, height, palette)?;
+    out.flush()
+}
+
+pub fn encode<W: Write>(
+    out: &mut W,
+    format: &ImageFormat,
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    palette: &Palette,
+) -> Result<(), Error> {
+    match format {
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(Error::other)?;
+            writer
+                .write_image_data(&rgb_bytes(pixels))
+                .map_err(Error::other)?;
+        }
+        ImageFormat::Ppm => {
+            write!(out, "P6\n{} {}\n255\n", width, height)?;
+            out.write_all(&rgb_bytes(pixels))?;
+        }
+        ImageFormat::Pbm => {
+            write!(out, "P4\n{} {}\n", width, height)?;
+            // in pbm a set bit is black, so anything that isn't background counts as ink
+            for row in pixels.chunks(width) {
+                for byte in row.chunks(8) {
+                    let mut packed = 0u8;
+                    for (bit, &pixel) in byte.iter().enumerate() {
+                        if pixel != palette.background {
+                            packed |= 0x80 >> bit;
+                        }
+                    }
+                    out.write_all(&[packed])?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rgb_bytes(pixels: &[u32]) -> Vec<u8> {
+    pixels.iter().flat_map(|&p| to_rgb(p)).collect()
+}
@@ -0,0 +1,362 @@
+// sprite tools for artists. extract draws every sprite a rom draws (see info::find_sprites) into a
+// png sheet, import turns a png back into sprite bytes, printed as octo or rust source or patched
+// straight into a rom. an image is cut into tiles left to right, top to bottom. tiles are 8 pixels
+// wide or 16x16 for the big SCHIP sprites, and with two planes (XO-CHIP) the bytes of the second
+// plane follow the first
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cfg::Cfg,
+    info::{find_sprites, Sprite},
+    loader,
+    memory_map::MemoryMap,
+    palette::Palette,
+    screenshot,
+};
+
+// sprites per row of an extracted sheet
+const SHEET_COLUMNS: usize = 8;
+
+// fills the gaps between the sprites of a sheet
+const SHEET_GAP_COLOR: u32 = 0x404040;
+
+// bytes per line of the printed sources
+const BYTES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Octo,
+    Rust,
+    Hex,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportOptions {
+    // 8, or 16 for 16x16 sprites
+    pub width: usize,
+    // rows per tile, the whole image when not given
+    pub height: Option<usize>,
+    // 1, or 2 for XO-CHIP sprites with 4 colors
+    pub planes: usize,
+    pub format: Format,
+    // the sprites are called name_0, name_1, ... or just name if there's one
+    pub name: String,
+}
+
+// where import writes its sprites into a rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub rom: String,
+    pub load_address: usize,
+    pub address: usize,
+    pub output: Option<PathBuf>,
+}
+
+// an image as the color index of every pixel, row major
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    // one plane: anything brighter than half is lit, like png fonts. two planes: up to 4 colors
+    // numbered from dark to light, so the darkest is the background unless there are transparent
+    // pixels. transparent pixels are background either way
+    pub fn from_png(data: &[u8], planes: usize) -> Result<Image, Error> {
+        let invalid = |reason: String| Error::new(ErrorKind::InvalidData, reason);
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder
+            .read_info()
+            .map_err(|e| invalid(format!("corrupt png: {}", e)))?;
+        let mut buf = vec![0u8; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buf)
+            .map_err(|e| invalid(format!("corrupt png: {}", e)))?;
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let channels = info.color_type.samples();
+        // brightness, or None when transparent
+        let brightness = |x: usize, y: usize| {
+            let pixel = &buf[y * info.line_size + x * channels..];
+            let (value, alpha) = match channels {
+                1 => (pixel[0] as u32, 255),
+                2 => (pixel[0] as u32, pixel[1]),
+                3 => (
+                    (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3,
+                    255,
+                ),
+                _ => (
+                    (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) / 3,
+                    pixel[3],
+                ),
+            };
+            (alpha > 127).then_some(value)
+        };
+        let values = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| brightness(x, y))
+            .collect::<Vec<Option<u32>>>();
+
+        let pixels = if planes == 1 {
+            values
+                .iter()
+                .map(|value| value.is_some_and(|v| v > 127) as u8)
+                .collect()
+        } else {
+            let mut levels = values.iter().flatten().copied().collect::<Vec<u32>>();
+            levels.sort_unstable();
+            levels.dedup();
+            // transparency is the background, then the colors start at 1
+            let first = values.contains(&None) as usize;
+            if first + levels.len() > 4 {
+                return Err(invalid(format!(
+                    "two plane sprites have up to 4 colors, this image has {}",
+                    first + levels.len()
+                )));
+            }
+            values
+                .iter()
+                .map(|value| match value {
+                    Some(v) => (first + levels.iter().position(|l| l == v).unwrap()) as u8,
+                    None => 0,
+                })
+                .collect()
+        };
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // the sprite bytes of every tile
+    pub fn tiles(&self, width: usize, height: usize, planes: usize) -> Result<Vec<Vec<u8>>, Error> {
+        if !self.width.is_multiple_of(width) || !self.height.is_multiple_of(height) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "a {}x{} image can't be cut into {}x{} sprites",
+                    self.width, self.height, width, height
+                ),
+            ));
+        }
+        let mut tiles = Vec::new();
+        for top in (0..self.height).step_by(height) {
+            for left in (0..self.width).step_by(width) {
+                let mut bytes = Vec::new();
+                for plane in 0..planes {
+                    for y in top..top + height {
+                        for column in (left..left + width).step_by(8) {
+                            bytes.push((column..column + 8).fold(0u8, |byte, x| {
+                                let color = self.pixels[y * self.width + x];
+                                byte << 1 | (color >> plane & 1)
+                            }));
+                        }
+                    }
+                }
+                tiles.push(bytes);
+            }
+        }
+        Ok(tiles)
+    }
+}
+
+fn bytes_line(bytes: &[u8], separator: &str) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:#04x}", b))
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+// the tiles as source to paste into a program
+pub fn source(tiles: &[Vec<u8>], name: &str, format: Format) -> String {
+    let mut out = String::new();
+    for (n, bytes) in tiles.iter().enumerate() {
+        let name = match tiles.len() {
+            1 => name.to_string(),
+            _ => format!("{}_{}", name, n),
+        };
+        match format {
+            Format::Octo => {
+                out.push_str(&format!(": {}\n", name));
+                for line in bytes.chunks(BYTES_PER_LINE) {
+                    out.push_str(&format!("\t{}\n", bytes_line(line, " ")));
+                }
+            }
+            Format::Rust => {
+                out.push_str(&format!(
+                    "const {}: [u8; {}] = [\n",
+                    name.to_ascii_uppercase(),
+                    bytes.len()
+                ));
+                for line in bytes.chunks(BYTES_PER_LINE) {
+                    out.push_str(&format!("    {},\n", bytes_line(line, ", ")));
+                }
+                out.push_str("];\n");
+            }
+            Format::Hex => {
+                let hex = bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                out.push_str(&format!("{} {}\n", name, hex));
+            }
+        }
+    }
+    out
+}
+
+// the sprites side by side, SHEET_COLUMNS to a row, every pixel scale times as big. the pixels are
+// 0RGB like screenshots
+pub fn sheet(
+    program: &[u8],
+    start: usize,
+    sprites: &[Sprite],
+    scale: usize,
+) -> (Vec<u32>, usize, usize) {
+    let palette = Palette::default();
+    let cell_width = sprites.iter().map(|s| s.width).max().unwrap_or(8) + 1;
+    let cell_height = sprites.iter().map(|s| s.height).max().unwrap_or(1) + 1;
+    let columns = sprites.len().clamp(1, SHEET_COLUMNS);
+    let rows = sprites.len().div_ceil(SHEET_COLUMNS).max(1);
+    let (width, height) = (columns * cell_width + 1, rows * cell_height + 1);
+
+    let mut pixels = vec![SHEET_GAP_COLOR; width * height];
+    for (n, sprite) in sprites.iter().enumerate() {
+        let (left, top) = (
+            1 + n % SHEET_COLUMNS * cell_width,
+            1 + n / SHEET_COLUMNS * cell_height,
+        );
+        let bytes = &program[sprite.address - start..sprite.address - start + sprite.bytes()];
+        for y in 0..cell_height - 1 {
+            for x in 0..cell_width - 1 {
+                let lit = x < sprite.width
+                    && y < sprite.height
+                    && bytes[y * sprite.width / 8 + x / 8] & (0x80 >> (x % 8)) != 0;
+                pixels[(top + y) * width + left + x] = match lit {
+                    true => palette.foreground,
+                    false => palette.background,
+                };
+            }
+        }
+    }
+
+    let scaled = pixels
+        .chunks(width)
+        .flat_map(|row| {
+            let row = row
+                .iter()
+                .flat_map(|&p| std::iter::repeat_n(p, scale))
+                .collect::<Vec<u32>>();
+            std::iter::repeat_n(row, scale).flatten()
+        })
+        .collect::<Vec<u32>>();
+    (scaled, width * scale, height * scale)
+}
+
+pub fn extract(
+    src: &str,
+    memory_map: &MemoryMap,
+    output: Option<PathBuf>,
+    scale: usize,
+) -> Result<(), Error> {
+    let program = loader::load(src)?;
+    let start = memory_map.load_address;
+    let sprites = find_sprites(&Cfg::build(&program, start), memory_map);
+    if sprites.is_empty() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no sprites found in {}", src),
+        ));
+    }
+    for (n, sprite) in sprites.iter().enumerate() {
+        println!(
+            "{:>3}  {:#05x}  {}x{}  column {}, row {}",
+            n,
+            sprite.address,
+            sprite.width,
+            sprite.height,
+            n % SHEET_COLUMNS,
+            n / SHEET_COLUMNS
+        );
+    }
+    let (pixels, width, height) = sheet(&program, start, &sprites, scale);
+    let output = output.unwrap_or_else(|| Path::new(src).with_extension("sprites.png"));
+    screenshot::save(&output, &pixels, width, height, &Palette::default()).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to write {}: {}", output.display(), e),
+        )
+    })?;
+    println!("Saved {} sprites to {}", sprites.len(), output.display());
+    Ok(())
+}
+
+pub fn import(path: &Path, options: &ImportOptions, patch: Option<Patch>) -> Result<(), Error> {
+    let data = fs::read(path).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to read {}: {}", path.display(), e),
+        )
+    })?;
+    let image = Image::from_png(&data, options.planes)?;
+    let height = match (options.width, options.height) {
+        (16, _) => 16,
+        (_, Some(height)) => height,
+        (_, None) => image.height,
+    };
+    if options.width == 8 && !(1..=15).contains(&height) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "8 pixel wide sprites are 1 to 15 rows tall, not {}, use --height to cut the image",
+                height
+            ),
+        ));
+    }
+    let tiles = image.tiles(options.width, height, options.planes)?;
+
+    let Some(patch) = patch else {
+        print!("{}", source(&tiles, &options.name, options.format));
+        return Ok(());
+    };
+    let mut rom = loader::load(&patch.rom)?;
+    let bytes = tiles.concat();
+    let offset = patch
+        .address
+        .checked_sub(patch.load_address)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("{:#05x} is before the start of the rom", patch.address),
+            )
+        })?;
+    if offset + bytes.len() > rom.len() {
+        rom.resize(offset + bytes.len(), 0);
+    }
+    rom[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    let output = patch
+        .output
+        .unwrap_or_else(|| Path::new(&patch.rom).with_extension("patched.ch8"));
+    fs::write(&output, &rom).map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Failed to write {}: {}", output.display(), e),
+        )
+    })?;
+    println!(
+        "Wrote {} sprite bytes at {:#05x} to {}",
+        bytes.len(),
+        patch.address,
+        output.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::{
+    io::{stdout, Stdout, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use minifb::Key;
+
+use crate::{
+    cpu::{HEIGHT, WIDTH},
+    display::Display,
+    frontend::Frontend,
+    machine::Snapshot,
+    palette::{to_rgb, Palette},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyphs {
+    // '▀' with the top pixel as foreground and the bottom pixel as background, 1x2 pixels per cell
+    HalfBlock,
+    // braille dots, 2x4 pixels per cell but only a single color
+    Braille,
+}
+
+// draws the display with unicode blocks and reads the keypad from raw terminal input.
+// the terminal is put back into its normal state when this is dropped
+pub struct TerminalFrontend {
+    out: Stdout,
+    glyphs: Glyphs,
+    palette: Palette,
+    open: bool,
+    // status messages and whether they are errors. the last one is shown under the sidebar, all of
+    // them are printed once the terminal is back to normal
+    messages: Vec<(bool, String)>,
+}
+
+// dot numbering of a braille cell, indexed by [y][x] inside the 2x4 block
+const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// the pixels of the half block at column x of a text row, (top, bottom)
+pub fn half_block(d_buffer: &Display, x: usize, row: usize) -> (bool, bool) {
+    (d_buffer.pixel(x, row * 2), d_buffer.pixel(x, row * 2 + 1))
+}
+
+// the braille character for the 2x4 pixels at column col of a text row
+pub fn braille(d_buffer: &Display, col: usize, row: usize) -> char {
+    braille_dots(|dx, dy| d_buffer.pixel(col * 2 + dx, row * 4 + dy))
+}
+
+// the braille character for a 2x4 block of pixels, lit(dx, dy) tells which of them are on
+pub fn braille_dots(lit: impl Fn(usize, usize) -> bool) -> char {
+    let mut bits = 0u32;
+    for (dy, dots) in DOTS.iter().enumerate() {
+        for (dx, dot) in dots.iter().enumerate() {
+            if lit(dx, dy) {
+                bits |= dot;
+            }
+        }
+    }
+    char::from_u32(0x2800 + bits).unwrap_or(' ')
+}
+
+impl TerminalFrontend {
+    pub fn new(glyphs: Glyphs, palette: Palette) -> Result<TerminalFrontend, std::io::Error> {
+        let mut out = stdout();
+        terminal::enable_raw_mode()?;
+        queue!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+        out.flush()?;
+        Ok(TerminalFrontend {
+            out,
+            glyphs,
+            palette,
+            open: true,
+            messages: Vec::new(),
+        })
+    }
+
+    fn display_columns(&self) -> u16 {
+        match self.glyphs {
+            Glyphs::HalfBlock => WIDTH as u16,
+            Glyphs::Braille => (WIDTH / 2) as u16,
+        }
+    }
+
+    fn draw_display(&mut self, d_buffer: &Display) -> Result<(), std::io::Error> {
+        let color = |set: bool| {
+            let [r, g, b] = to_rgb(if set {
+                self.palette.foreground
+            } else {
+                self.palette.background
+            });
+            Color::Rgb { r, g, b }
+        };
+
+        match self.glyphs {
+            Glyphs::HalfBlock => {
+                for row in 0..HEIGHT / 2 {
+                    queue!(self.out, cursor::MoveTo(0, row as u16))?;
+                    for x in 0..WIDTH {
+                        let (top, bottom) = half_block(d_buffer, x, row);
+                        queue!(
+                            self.out,
+                            SetForegroundColor(color(top)),
+                            SetBackgroundColor(color(bottom)),
+                            Print('▀')
+                        )?;
+                    }
+                }
+            }
+            Glyphs::Braille => {
+                queue!(
+                    self.out,
+                    SetForegroundColor(color(true)),
+                    SetBackgroundColor(color(false))
+                )?;
+                for row in 0..HEIGHT / 4 {
+                    queue!(self.out, cursor::MoveTo(0, row as u16))?;
+                    for col in 0..WIDTH / 2 {
+                        queue!(self.out, Print(braille(d_buffer, col, row)))?;
+                    }
+                }
+            }
+        }
+        queue!(self.out, ResetColor)
+    }
+
+    fn draw_sidebar(&mut self, cpu: &Snapshot) -> Result<(), std::io::Error> {
+        let left = self.display_columns() + 2;
+        let mut lines = vec![
+            format!("PC {:#06x}", cpu.pc),
+            format!("I  {:#06x}", cpu.i),
+            format!("SP {:<6}", cpu.stack_depth),
+            format!("DT {:02x} ST {:02x}", cpu.delay_timer, cpu.sound_timer),
+            String::new(),
+        ];
+        for r in 0..8 {
+            lines.push(format!(
+                "V{:X} {:02x}  V{:X} {:02x}",
+                r,
+                cpu.gp_registers[r],
+                r + 8,
+                cpu.gp_registers[r + 8]
+            ));
+        }
+        lines.push(String::new());
+        lines.push(String::from("F1-F5 hotkeys, Esc quits"));
+        lines.push(String::new());
+        lines.extend(self.messages.last().map(|(_, text)| text.clone()));
+
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                self.out,
+                cursor::MoveTo(left, row as u16),
+                Print(line),
+                terminal::Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        // park the cursor under everything so stray output doesn't land on the display
+        queue!(
+            self.out,
+            cursor::MoveTo(0, lines.len().max(HEIGHT / 2) as u16 + 1)
+        )
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn draw(&mut self, display: &Display, _scaled: &[u32]) {
+        self.draw_display(display)
+            .and_then(|_| self.out.flush())
+            .expect("Failed to draw to the terminal");
+    }
+
+    fn end_frame(&mut self, state: &Snapshot) {
+        self.draw_sidebar(state)
+            .and_then(|_| self.out.flush())
+            .expect("Failed to draw to the terminal");
+    }
+
+    fn keys_pressed(&mut self) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let Ok(Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            })) = event::read()
+            else {
+                continue;
+            };
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            match code {
+                KeyCode::Esc => self.open = false,
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.open = false
+                }
+                code => keys.extend(to_minifb_key(code)),
+            }
+        }
+        keys
+    }
+
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn message(&mut self, text: String) {
+        self.messages.push((false, text));
+    }
+
+    fn error(&mut self, text: String) {
+        self.messages.push((true, text));
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        let _ = queue!(
+            self.out,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = self.out.flush();
+        let _ = terminal::disable_raw_mode();
+        for (error, text) in &self.messages {
+            match error {
+                true => eprintln!("{}", text),
+                false => println!("{}", text),
+            }
+        }
+    }
+}
+
+// the terminal only gives us characters, so turn them back into the keys keyboard.rs knows about.
+// F1-F5 stand in for the numpad hotkeys since numpad digits are indistinguishable from the others
+pub fn to_minifb_key(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Char(c) => match c.to_ascii_lowercase() {
+            '1' => Key::Key1,
+            '2' => Key::Key2,
+            '3' => Key::Key3,
+            '4' => Key::Key4,
+            'q' => Key::Q,
+            'w' => Key::W,
+            'e' => Key::E,
+            'r' => Key::R,
+            'a' => Key::A,
+            's' => Key::S,
+            'd' => Key::D,
+            'f' => Key::F,
+            'z' => Key::Z,
+            'x' => Key::X,
+            'c' => Key::C,
+            'v' => Key::V,
+            _ => return None,
+        },
+        KeyCode::F(1) => Key::NumPad1,
+        KeyCode::F(2) => Key::NumPad2,
+        KeyCode::F(3) => Key::NumPad3,
+        KeyCode::F(4) => Key::NumPad4,
+        KeyCode::F(5) => Key::NumPad5,
+        _ => return None,
+    };
+    Some(key)
+}
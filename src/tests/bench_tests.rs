@@ -0,0 +1,46 @@
+use std::fs;
+
+use crate::{
+    bench::{bench, BenchOptions, Format},
+    blocks::Backend,
+    quirks,
+};
+
+#[test]
+fn every_backend_runs_the_requested_instructions() {
+    let program = fs::read("test_files/IBM Logo.c8").expect("rom should exist");
+    let options = BenchOptions {
+        instructions: 1001,
+        tickrate: 7,
+        quirks: quirks::DEFAULT,
+        format: Format::Json,
+    };
+    for (cache, backend) in [
+        (false, Backend::Interpreter),
+        (true, Backend::Interpreter),
+        (true, Backend::Blocks),
+    ] {
+        let result = bench(&program, &options, cache, backend).unwrap();
+        assert_eq!(result.instructions, 1001);
+    }
+}
+
+#[test]
+fn roms_that_stop_fail_the_benchmark() {
+    // 0x0000 isn't an instruction
+    let options = BenchOptions {
+        instructions: 10,
+        tickrate: 1,
+        quirks: quirks::DEFAULT,
+        format: Format::Text,
+    };
+    let err = bench(
+        &[0x60, 0x01, 0x00, 0x00],
+        &options,
+        true,
+        Backend::Interpreter,
+    )
+    .err()
+    .expect("should fail");
+    assert!(err.to_string().contains("after 1 instructions"));
+}
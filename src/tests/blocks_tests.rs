@@ -0,0 +1,84 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    blocks::{self, MAX_BLOCK_LEN},
+    cpu::Cpu,
+    keyboard::KeyBoard,
+    opcode::Op,
+    quirks::{self, Quirks},
+};
+
+fn run(program: &[u8], quirks: Quirks, blocks: bool, instructions: u32) -> Cpu {
+    let mut cpu = Cpu::init(false);
+    cpu.quirks = quirks;
+    cpu.seed(1);
+    cpu.set_block_backend(blocks);
+    cpu.add_program(program).unwrap();
+    let keyboard = KeyBoard::new();
+    let mut executed = 0;
+    while executed < instructions {
+        executed += cpu
+            .step_block(&keyboard, instructions - executed)
+            .unwrap()
+            .0;
+    }
+    cpu
+}
+
+#[test]
+fn compile_stops_at_control_flow() {
+    // V0 = 1, V1 += 2, V0 |= V1, jump 0x200
+    let block = blocks::compile(&[0x60, 0x01, 0x71, 0x02, 0x80, 0x11, 0x12, 0x00], 0);
+    assert_eq!(*block, [Op::SetImm(0, 1), Op::AddImm(1, 2), Op::Or(0, 1)]);
+    assert!(blocks::compile(&[0xd0, 0x15], 0).is_empty());
+    assert_eq!(
+        blocks::compile(&[0x60, 0x01].repeat(100), 0).len(),
+        MAX_BLOCK_LEN
+    );
+}
+
+#[test]
+fn blocks_match_the_interpreter() {
+    // random arithmetic, including VF as an operand, in a loop
+    let mut rng = StdRng::seed_from_u64(0xb10c);
+    let mut program = Vec::new();
+    for _ in 0..200 {
+        let x = rng.gen_range(0..16u8);
+        let y = rng.gen_range(0..16u8);
+        let op = match rng.gen_range(0..6) {
+            0 => [0x60 | x, rng.gen()],
+            1 => [0x70 | x, rng.gen()],
+            2 => [
+                0x80 | x,
+                y << 4 | [0, 1, 2, 3, 4, 5, 6, 7, 0xe][rng.gen_range(0..9)],
+            ],
+            3 => [0xc0 | x, rng.gen()],
+            4 => [0xf0 | x, 0x1e],
+            _ => [0x30 | x, rng.gen()],
+        };
+        program.extend(op);
+    }
+    program.extend([0x12, 0x00]);
+
+    for quirks in [quirks::DEFAULT, quirks::CHIP8, quirks::SCHIP] {
+        let interpreted = run(&program, quirks, false, 5000);
+        let compiled = run(&program, quirks, true, 5000);
+        assert_eq!(interpreted.gp_registers, compiled.gp_registers);
+        assert_eq!(interpreted.i, compiled.i);
+        assert_eq!(interpreted.pc, compiled.pc);
+    }
+}
+
+#[test]
+fn overwritten_blocks_are_recompiled() {
+    // 0x200: V0 = 0x62, V1 = 0x23, I = 0x20e, jump to the block at 0x20c
+    // 0x20c: V3 += 1, V2 = 0 (V2 = 0x23 once overwritten)
+    // 0x210: stop the second time around, otherwise store V0 V1 over 0x20e and run the block again
+    let program = [
+        0x60, 0x62, 0x61, 0x23, 0xa2, 0x0e, 0x12, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x73, 0x01, 0x62,
+        0x00, 0x33, 0x02, 0x12, 0x16, 0x12, 0x14, 0xf1, 0x55, 0xa2, 0x0e, 0x12, 0x0c,
+    ];
+    let cpu = run(&program, quirks::DEFAULT, true, 50);
+    assert_eq!(cpu.gp_registers[3], 2);
+    assert_eq!(cpu.gp_registers[2], 0x23);
+}
@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+
+use minifb::Key;
+
+use crate::{cartridge, loader::decode_rom, palette::Palette};
+
+// hides the payload in a 4 color gif the same way octo does
+fn build_cartridge(json: &str) -> Vec<u8> {
+    let (width, height) = (32u16, 16u16);
+    let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+    payload.extend(json.as_bytes());
+    let mut indices = payload
+        .iter()
+        .flat_map(|byte| [byte >> 6, byte >> 4 & 3, byte >> 2 & 3, byte & 3])
+        .collect::<Vec<u8>>();
+    let frame_size = width as usize * height as usize;
+    indices.resize(indices.len().div_ceil(frame_size) * frame_size, 0);
+
+    let palette = [0u8, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut gif, width, height, &palette).unwrap();
+        for chunk in indices.chunks(frame_size) {
+            let mut frame = gif::Frame {
+                width,
+                height,
+                ..gif::Frame::default()
+            };
+            frame.buffer = Cow::Borrowed(chunk);
+            encoder.write_frame(&frame).unwrap();
+        }
+    }
+    gif
+}
+
+// a payload laid out like the ones octo writes: the source as typed, with every option octo has
+const OCTO_PAYLOAD: &str = r##"{"program":"# moves a dot with the keypad\n:alias x v0\n:alias y v1\n\n: dot 0x80\n\n: main\n\ti := dot\n\tloop\n\t\tsprite x y 1\n\t\tv2 := 5 if v2 key then x += 1\n\t\tsprite x y 1\n\tagain\n","options":{"tickrate":20,"fillColor":"#FFAA00","fillColor2":"#FF6600","blendColor":"#662200","backgroundColor":"#996600","buzzColor":"#FFAA00","quietColor":"#000000","shiftQuirks":true,"loadStoreQuirks":true,"vfOrderQuirks":false,"clipQuirks":false,"vBlankQuirks":false,"jumpQuirks":false,"logicQuirks":false,"screenRotation":0,"maxSize":3584,"touchInputMode":"none","fontStyle":"octo","displayScale":null,"keys":{"5":["ArrowUp",87],"8":"s"}}}"##;
+
+#[test]
+fn cartridge_program_and_options_are_decoded() {
+    let gif = build_cartridge(OCTO_PAYLOAD);
+    let cartridge = cartridge::decode(&gif).expect("should decode cartridge");
+    assert!(cartridge
+        .program
+        .starts_with("# moves a dot with the keypad\n:alias x v0\n"));
+
+    // and compiled when it is loaded
+    let rom = decode_rom(&gif, "game.gif").expect("should decode cartridge");
+    assert_eq!(rom.program[..4], [0x12, 0x03, 0x80, 0xa2]);
+
+    let options = rom.options.expect("cartridges have options");
+    assert_eq!(options.tickrate, Some(20));
+    let quirks = options.quirks.expect("quirks were set");
+    assert!(quirks.shift_in_place);
+    assert!(!quirks.load_store_increments_i);
+    assert_eq!(
+        options.palette,
+        Some(Palette {
+            background: 0x996600,
+            foreground: 0xffaa00
+        })
+    );
+    assert_eq!(
+        options.keymap,
+        Some(vec![(Key::Up, 5), (Key::W, 5), (Key::S, 8)])
+    );
+}
+
+#[test]
+fn cartridges_need_chip8_source() {
+    // octo only writes source, and the compiler doesn't know XO-CHIP
+    let bytes = build_cartridge(r#"{"program": [0, 224, 18, 0], "options": {}}"#);
+    assert!(cartridge::decode(&bytes).is_err());
+    let xo = build_cartridge(r#"{"program": ": main plane 3 loop again", "options": {}}"#);
+    let error = decode_rom(&xo, "game.gif").err().unwrap().to_string();
+    assert!(error.contains("only cartridges of chip8 programs are supported"));
+    assert!(
+        error.contains("plane is a SUPER-CHIP or XO-CHIP statement"),
+        "{}",
+        error
+    );
+
+    // the payload has to be an object with a program in it
+    assert!(cartridge::decode(&build_cartridge("[1, 2]")).is_err());
+}
@@ -0,0 +1,47 @@
+use crate::cfg::{Cfg, Exit};
+
+// 0x200: call 0x208, skip the indirect jump if V0 is 0, otherwise jump to V0 + 0x300
+// 0x206: jump back to the start
+// 0x208: V0 += 1, return
+const PROGRAM: [u8; 12] = [
+    0x22, 0x08, 0x30, 0x00, 0xb3, 0x00, 0x12, 0x00, 0x70, 0x01, 0x00, 0xee,
+];
+
+#[test]
+fn blocks_end_at_control_flow() {
+    let cfg = Cfg::build(&PROGRAM, 0x200);
+    let exits = cfg
+        .blocks
+        .values()
+        .map(|block| (block.start, block.exit, block.successors.clone()))
+        .collect::<Vec<(usize, Exit, Vec<usize>)>>();
+    assert_eq!(
+        exits,
+        vec![
+            (0x200, Exit::Call(0x208), vec![0x202]),
+            (0x202, Exit::Skip, vec![0x204, 0x206]),
+            (0x204, Exit::Indirect(0x300), vec![]),
+            (0x206, Exit::Jump(0x200), vec![0x200]),
+            (0x208, Exit::Return, vec![]),
+        ]
+    );
+    assert_eq!(
+        cfg.blocks[&0x208].instructions,
+        vec![(0x208, 0x7001), (0x20a, 0x00ee)]
+    );
+}
+
+#[test]
+fn subroutines_and_calls_are_found() {
+    let cfg = Cfg::build(&PROGRAM, 0x200);
+    assert_eq!(
+        cfg.subroutines[&0x200].blocks,
+        vec![0x200, 0x202, 0x204, 0x206]
+    );
+    assert_eq!(cfg.subroutines[&0x208].blocks, vec![0x208]);
+    assert_eq!(cfg.call_graph(), vec![(0x200, 0x208)]);
+
+    let json = cfg.to_json();
+    assert_eq!(json["indirect_jumps"], serde_json::json!(["0x204"]));
+    assert!(cfg.to_dot().contains("indirect jump to V0 + 0x300"));
+}
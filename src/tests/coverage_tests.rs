@@ -0,0 +1,78 @@
+use crate::{
+    coverage::{Coverage, Summary},
+    cpu::Cpu,
+    keyboard::KeyBoard,
+    machine::Machine,
+};
+
+// 0x200: draw the sprite at 0x20a, store V0 and V1 to 0x20c, then jump to itself forever
+// 0x20a: one row of sprite, 0x20c: written, 0x20e: never touched
+const PROGRAM: [u8; 16] = [
+    0xa2, 0x0a, 0xd0, 0x11, 0xa2, 0x0c, 0xf1, 0x55, 0x12, 0x08, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn run() -> Machine {
+    let mut cpu = Cpu::init(false);
+    cpu.add_program(&PROGRAM).unwrap();
+    let mut machine = Machine::new(cpu, KeyBoard::new(), 10);
+    machine.coverage = Some(Coverage::new("test.ch8", 0x200, 0x210));
+    machine.run_frame().unwrap();
+    machine
+}
+
+#[test]
+fn bytes_are_marked_by_how_they_were_used() {
+    let machine = run();
+    assert_eq!(
+        machine.coverage.as_ref().unwrap().summary(),
+        Summary {
+            bytes: 16,
+            executed: 10,
+            read: 1,
+            written: 2,
+            mixed: 0,
+            untouched: 3,
+        }
+    );
+}
+
+#[test]
+fn lcov_only_counts_code_and_untouched_words() {
+    let machine = run();
+    let mut out = Vec::new();
+    machine
+        .coverage
+        .as_ref()
+        .unwrap()
+        .write_lcov(&mut out)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "TN:\nSF:test.ch8\nDA:1,1\nDA:2,1\nDA:3,1\nDA:4,1\nDA:5,6\nDA:8,0\nLF:6\nLH:5\nend_of_record\n"
+    );
+}
+
+#[test]
+fn listing_annotates_instructions_and_data() {
+    let machine = run();
+    let mut out = Vec::new();
+    machine
+        .coverage
+        .as_ref()
+        .unwrap()
+        .write_listing(&mut out, &machine.cpu)
+        .unwrap();
+    let listing = String::from_utf8(out).unwrap();
+    assert!(
+        listing.contains("0x202  D011  x    DXYN     1\n"),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains("0x208  1208  x    1NNN     6\n"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("0x20a  FF"), "{}", listing);
+    assert!(listing.contains("0x20c  00 00"), "{}", listing);
+}
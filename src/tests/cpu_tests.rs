@@ -1,4 +1,8 @@
-use crate::cpu::Cpu;
+use crate::{
+    cpu::{Cpu, ExecuteError},
+    keyboard::KeyBoard,
+    memory_map::{self, MemoryMap},
+};
 use rand::{Rng, RngCore};
 #[test]
 fn large_program_fails() {
@@ -23,3 +27,87 @@ fn program_init_success() {
         assert_eq!(mem[i], program[i - 0x200]);
     }
 }
+#[test]
+fn memory_map_sets_load_and_font_address() {
+    let mut chip8 = Cpu::with_memory_map(false, memory_map::ETI660);
+    assert_eq!(chip8.mem.len(), 4096);
+    // V0 = 0xA, I = font for V0
+    chip8.add_program(&[0x60, 0x0a, 0xf0, 0x29]).unwrap();
+    assert_eq!(chip8.pc, 0x600);
+    assert_eq!(chip8.mem[0x600..0x604], [0x60, 0x0a, 0xf0, 0x29]);
+
+    let keyboard = KeyBoard::new();
+    chip8.step(&keyboard).unwrap();
+    chip8.step(&keyboard).unwrap();
+    assert_eq!(chip8.i, 0x050 + 0xa * 5);
+    assert_eq!(chip8.mem[chip8.i as usize], 0xf0);
+
+    let too_big = vec![0u8; 4096 - 0x600 + 1];
+    assert!(Cpu::with_memory_map(false, memory_map::ETI660)
+        .add_program(&too_big)
+        .is_err());
+}
+
+#[test]
+fn memory_access_past_ram_fails() {
+    let mut chip8 = Cpu::with_memory_map(false, memory_map::CHIP8);
+    // I = 0xFFE, save V0-V3
+    chip8.add_program(&[0xaf, 0xfe, 0xf3, 0x55]).unwrap();
+    let keyboard = KeyBoard::new();
+    chip8.step(&keyboard).unwrap();
+    assert!(matches!(
+        chip8.step(&keyboard),
+        Err(ExecuteError::BadMemoryAccess(0x1001))
+    ));
+}
+
+#[test]
+fn saving_the_last_byte_of_64k_wraps_i() {
+    let mut chip8 = Cpu::with_memory_map(false, memory_map::XOCHIP);
+    assert_eq!(chip8.mem.len(), 0x10000);
+    // save V0, load V0, both at the last byte of ram
+    chip8.add_program(&[0xf0, 0x55, 0xf0, 0x65]).unwrap();
+    let keyboard = KeyBoard::new();
+    chip8.gp_registers[0] = 0x42;
+    chip8.i = 0xffff;
+    chip8.step(&keyboard).unwrap();
+    assert_eq!((chip8.mem[0xffff], chip8.i), (0x42, 0));
+    chip8.i = 0xffff;
+    chip8.step(&keyboard).unwrap();
+    assert_eq!((chip8.gp_registers[0], chip8.i), (0x42, 0));
+}
+
+#[test]
+fn font_has_to_end_before_program() {
+    let map = MemoryMap {
+        font_address: 0x1f0,
+        ..memory_map::CHIP8
+    };
+    assert!(map.validate().is_err());
+    assert!(memory_map::XOCHIP.validate().is_ok());
+}
+#[test]
+fn writes_to_memory_invalidate_decoded_instructions() {
+    let mut chip8 = Cpu::init(false);
+    // 0x200: V0 = 0x62, V1 = 0x23, I = 0x20a
+    // 0x206: run the instruction at 0x20a once, then store V0 V1 over it and jump back to it
+    // 0x20a: V2 = 0x00 the first time, V2 = 0x23 after being overwritten
+    chip8
+        .add_program(&[
+            0x60, 0x62, 0x61, 0x23, 0xa2, 0x0a, 0x12, 0x0a, 0x00, 0x00, 0x62, 0x00, 0xf1, 0x55,
+            0x12, 0x0a,
+        ])
+        .unwrap();
+    let keyboard = KeyBoard::new();
+    for _ in 0..5 {
+        chip8.step(&keyboard).unwrap();
+    }
+    assert_eq!(chip8.gp_registers[2], 0x00);
+    chip8.step(&keyboard).unwrap();
+    chip8.step(&keyboard).unwrap();
+    assert_eq!(chip8.mem[0x20a..0x20c], [0x62, 0x23]);
+    // the cached 6200 must not run again
+    chip8.step(&keyboard).unwrap();
+    assert_eq!(chip8.pc, 0x20c);
+    assert_eq!(chip8.gp_registers[2], 0x23);
+}
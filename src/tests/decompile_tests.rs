@@ -0,0 +1,100 @@
+use crate::{
+    cpu::Cpu,
+    decompile::{decompile, Format},
+    keyboard::KeyBoard,
+    octo::compile,
+};
+
+const SOURCE: &str = "
+    : main
+        v0 := 0
+        v1 := 0
+        loop
+            add
+            if v0 == 3 begin v2 := 1 else v3 += 1 end
+            while v0 != 5
+        again
+        i := sprite
+        sprite v0 v1 2
+        loop again
+    : add
+        v0 += 1
+        v1 += v0
+        return
+    : sprite 0x81 0x42
+";
+
+fn run(program: &[u8], steps: usize) -> Cpu {
+    let mut cpu = Cpu::init(false);
+    cpu.add_program(program).unwrap();
+    let keyboard = KeyBoard::new();
+    for _ in 0..steps {
+        cpu.step(&keyboard).unwrap();
+    }
+    cpu
+}
+
+#[test]
+fn skips_and_jumps_become_structured_code() {
+    let program = compile(SOURCE).unwrap();
+    let octo = decompile(&program, 0x200, Format::Octo);
+    for line in [
+        "\tloop",
+        "\t\tsub_21e",
+        "\t\tif v0 == 0x03 begin",
+        "\t\telse",
+        "\t\twhile v0 != 0x05",
+        "\tagain",
+        "\ti := data_224",
+        "# inputs: v0 v1, writes: v0 v1 vf",
+        ": data_224",
+        "\t0x81 0x42",
+    ] {
+        assert!(octo.lines().any(|l| l == line), "{:?} in\n{}", line, octo);
+    }
+
+    let c = decompile(&program, 0x200, Format::C);
+    for line in [
+        "void sub_21e(void) {",
+        "\t\tif (v0 == 0x05) break;",
+        "\t\t} else {",
+        "const uint8_t data_224[] = {",
+    ] {
+        assert!(c.lines().any(|l| l == line), "{:?} in\n{}", line, c);
+    }
+}
+
+#[test]
+fn octo_output_compiles_to_an_equivalent_rom() {
+    let program = compile(SOURCE).unwrap();
+    let recompiled = compile(&decompile(&program, 0x200, Format::Octo)).unwrap();
+    let (original, again) = (run(&program, 200), run(&recompiled, 200));
+    assert_eq!(original.gp_registers, again.gp_registers);
+    assert_eq!(original.gp_registers[..4], [5, 15, 1, 4]);
+}
+
+#[test]
+fn chained_skips_compile_again() {
+    // the first skip skips the second one
+    for program in [
+        vec![0x30, 0x01, 0x40, 0x02, 0x60, 0x05, 0x12, 0x06],
+        vec![0x60, 0x01, 0x30, 0x01, 0x41, 0x02, 0x62, 0x05, 0x12, 0x08],
+    ] {
+        let octo = decompile(&program, 0x200, Format::Octo);
+        let recompiled = compile(&octo).unwrap_or_else(|e| panic!("{} in\n{}", e, octo));
+        let (original, again) = (run(&program, 10), run(&recompiled, 10));
+        assert_eq!(original.gp_registers, again.gp_registers);
+    }
+}
+
+#[test]
+fn saving_and_loading_every_register_is_summarized() {
+    // save vf, load vf, loop forever
+    let program = [0xff, 0x55, 0xff, 0x65, 0x12, 0x04];
+    let octo = decompile(&program, 0x200, Format::Octo);
+    assert!(octo.contains("\tsave vf\n\tload vf\n"), "{}", octo);
+    assert!(octo.contains(
+        "# inputs: v0 v1 v2 v3 v4 v5 v6 v7 v8 v9 va vb vc vd ve vf i, writes: v0 v1 v2 v3 v4 v5 v6 v7 v8 v9 va vb vc vd ve vf"
+    ));
+    assert!(decompile(&program, 0x200, Format::C).contains("load(v0..=vf, i);"));
+}
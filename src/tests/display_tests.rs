@@ -0,0 +1,32 @@
+use crate::{cpu::WIDTH, display::Display};
+
+#[test]
+fn sprites_wrap_or_clip_at_the_right_edge() {
+    let mut display = Display::new();
+    assert!(!display.draw_byte(WIDTH - 4, 0, 0xff, false));
+    assert_eq!(display.rows()[0], 0xf000_0000_0000_000f);
+    // drawing it again turns every pixel off
+    assert!(display.draw_byte(WIDTH - 4, 0, 0xff, false));
+    assert_eq!(display.rows()[0], 0);
+
+    assert!(!display.draw_byte(WIDTH - 4, 1, 0xff, true));
+    assert_eq!(display.rows()[1], 0xf);
+    assert!(display.pixel(WIDTH - 1, 1));
+    assert!(!display.pixel(0, 1));
+}
+
+#[test]
+fn only_changed_rows_are_dirty() {
+    let mut display = Display::new();
+    // a new display has to be drawn completely
+    assert_eq!(display.take_dirty(), 0xffff_ffff);
+    assert_eq!(display.take_dirty(), 0);
+
+    display.draw_byte(0, 3, 0x80, false);
+    display.draw_byte(8, 31, 0x01, true);
+    assert_eq!(display.take_dirty(), 1 << 3 | 1 << 31);
+
+    // clearing only touches rows that had something on them
+    display.clear();
+    assert_eq!(display.take_dirty(), 1 << 3 | 1 << 31);
+}
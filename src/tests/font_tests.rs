@@ -0,0 +1,106 @@
+use std::fs;
+
+use crate::{
+    cpu::{Cpu, ExecuteError},
+    font::{Font, SMALL_GLYPH_SIZE},
+    keyboard::KeyBoard,
+};
+
+// the rows of a small glyph as text
+fn glyph_rows(font: &Font, digit: usize) -> Vec<String> {
+    font.small[digit * SMALL_GLYPH_SIZE..(digit + 1) * SMALL_GLYPH_SIZE]
+        .iter()
+        .map(|row| {
+            (0..4)
+                .map(|x| if row & (0x80 >> x) != 0 { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn default_digits_look_right() {
+    let font = Font::default();
+    assert_eq!(
+        glyph_rows(&font, 4),
+        ["#..#", "#..#", "####", "...#", "...#"]
+    );
+    assert_eq!(
+        glyph_rows(&font, 6),
+        ["####", "#...", "####", "#..#", "####"]
+    );
+    assert_eq!(
+        glyph_rows(&font, 8),
+        ["####", "#..#", "####", "#..#", "####"]
+    );
+    assert_eq!(
+        glyph_rows(&font, 9),
+        ["####", "#..#", "####", "...#", "####"]
+    );
+
+    let vip = Font::preset("vip").unwrap();
+    assert_eq!(
+        glyph_rows(&vip, 4),
+        ["#.#.", "#.#.", "####", "..#.", "..#."]
+    );
+}
+
+#[test]
+fn big_digits_need_a_schip_font() {
+    // V0 = 3, I = big digit for V0
+    let program = [0x60, 0x03, 0xf0, 0x30];
+    let keyboard = KeyBoard::new();
+
+    let mut cpu = Cpu::init(false);
+    cpu.load_font(&Font::preset("schip").unwrap());
+    cpu.add_program(&program).unwrap();
+    cpu.step(&keyboard).unwrap();
+    cpu.step(&keyboard).unwrap();
+    assert_eq!(cpu.i, 80 + 3 * 10);
+    assert_eq!(
+        cpu.mem[cpu.i as usize..cpu.i as usize + 3],
+        [0x3c, 0x7e, 0xc3]
+    );
+
+    let mut cpu = Cpu::init(false);
+    cpu.load_font(&Font::preset("vip").unwrap());
+    cpu.add_program(&program).unwrap();
+    cpu.step(&keyboard).unwrap();
+    assert!(matches!(
+        cpu.step(&keyboard),
+        Err(ExecuteError::BadInstruction(0xf030))
+    ));
+}
+
+#[test]
+fn custom_fonts_load_from_png() {
+    let expected = Font::preset("octo").unwrap();
+    let (width, height) = (128u32, 15u32);
+    let mut pixels = vec![0u8; (width * height) as usize];
+    let rows = expected
+        .small
+        .chunks(5)
+        .zip(expected.big.as_ref().unwrap().chunks(10))
+        .map(|(small, big)| [small, big].concat());
+    for (glyph, rows) in rows.enumerate() {
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..8 {
+                if row & (0x80 >> x) != 0 {
+                    pixels[y * width as usize + glyph * 8 + x] = 0xff;
+                }
+            }
+        }
+    }
+
+    let path = std::env::temp_dir().join("chip8_custom_fonts_load_from_png.png");
+    {
+        let file = fs::File::create(&path).unwrap();
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+    }
+    let font = Font::parse(path.to_str().unwrap());
+    fs::remove_file(&path).ok();
+    assert_eq!(font, Ok(expected));
+}
@@ -0,0 +1,163 @@
+// runs the bundled roms headlessly for a fixed number of frames and compares the final display
+// with the pbm images in test_files/golden.
+// run `CHIP8_UPDATE_GOLDENS=1 cargo test golden` to regenerate them after an intended change
+use std::{fs, path::PathBuf};
+
+use minifb::Key;
+
+use crate::{
+    chip8::{render, Chip8, Config},
+    cpu::{HEIGHT, WIDTH},
+    frontend::{FrontendKind, HeadlessFrontend},
+    palette::Palette,
+    quirks::{self, Quirks},
+    screenshot::{encode, ImageFormat},
+};
+
+const SEED: u64 = 0x8c8;
+
+struct GoldenCase {
+    rom: &'static str,
+    golden: &'static str,
+    frames: u64,
+    // (frame, key) presses replayed by the headless frontend
+    input: &'static [(u64, Key)],
+    // the platform the rom was written for
+    quirks: Quirks,
+    // where the rom has to have stopped, a jump to itself, if it does
+    halt: Option<usize>,
+}
+
+// the final display as a pbm image, and the pc
+fn run_headless(case: &GoldenCase) -> (Vec<u8>, usize) {
+    let rom = fs::read(PathBuf::from("test_files").join(case.rom)).expect("rom should exist");
+    let config = Config {
+        frontend: FrontendKind::Headless,
+        seed: Some(SEED),
+        max_frames: Some(case.frames),
+        quirks: case.quirks,
+        ..Config::default()
+    };
+    let frontend = Box::new(HeadlessFrontend::new(case.input.to_vec()));
+    let mut chip8 = Chip8::with_frontend(config, frontend).expect("should create chip8");
+    chip8.add_program(&rom).expect("rom should fit in memory");
+    // roms are allowed to stop early (eg by running off the end of the program)
+    while chip8.run_frame() {}
+
+    let palette = Palette::default();
+    let mut pixels = vec![0u32; WIDTH * HEIGHT];
+    render(&chip8.cpu().d_buffer, &palette, 1, &mut pixels);
+    let mut image = Vec::new();
+    encode(
+        &mut image,
+        &ImageFormat::Pbm,
+        &pixels,
+        WIDTH,
+        HEIGHT,
+        &palette,
+    )
+    .expect("should encode pbm");
+    (image, chip8.cpu().pc)
+}
+
+fn as_text(pbm: &[u8]) -> String {
+    let data = pbm.splitn(3, |&b| b == b'\n').nth(2).unwrap_or(&[]);
+    data.chunks(WIDTH / 8)
+        .map(|row| {
+            row.iter()
+                .flat_map(|byte| {
+                    (0..8).map(move |bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn check_golden(case: GoldenCase) {
+    let (actual, pc) = run_headless(&case);
+    if let Some(halt) = case.halt {
+        assert_eq!(pc, halt, "{} didn't run to its end", case.rom);
+    }
+    let path = PathBuf::from("test_files/golden").join(case.golden);
+
+    if std::env::var_os("CHIP8_UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).expect("should create golden dir");
+        fs::write(&path, &actual).expect("should write golden");
+        return;
+    }
+
+    let expected = fs::read(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing golden {}, run with CHIP8_UPDATE_GOLDENS=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "{} after {} frames doesn't match {}\nexpected:\n{}\nactual:\n{}",
+        case.rom,
+        case.frames,
+        path.display(),
+        as_text(&expected),
+        as_text(&actual)
+    );
+}
+
+#[test]
+fn golden_ibm_logo() {
+    check_golden(GoldenCase {
+        rom: "IBM Logo.c8",
+        golden: "ibm_logo.pbm",
+        frames: 60,
+        input: &[],
+        quirks: quirks::DEFAULT,
+        halt: None,
+    });
+}
+
+#[test]
+fn golden_draw_a() {
+    // the rom waits for a key after drawing, pressing it lets it run off the end
+    check_golden(GoldenCase {
+        rom: "draw_a.c8",
+        golden: "draw_a.pbm",
+        frames: 30,
+        input: &[(10, Key::X)],
+        quirks: quirks::DEFAULT,
+        halt: None,
+    });
+}
+
+#[test]
+fn golden_hello() {
+    // written for shifts in place, it draws garbage without them
+    check_golden(GoldenCase {
+        rom: "hello.c8",
+        golden: "hello.pbm",
+        frames: 3000,
+        input: &[],
+        quirks: quirks::SCHIP,
+        halt: Some(0x22c),
+    });
+}
+
+#[test]
+fn golden_pong2() {
+    // move the left paddle up and down a bit while the ball is in play
+    check_golden(GoldenCase {
+        rom: "PONG2.c8",
+        golden: "pong2.pbm",
+        frames: 900,
+        input: &[
+            (100, Key::Key1),
+            (101, Key::Key1),
+            (102, Key::Key1),
+            (300, Key::Q),
+            (301, Key::Q),
+            (600, Key::Key4),
+        ],
+        quirks: quirks::DEFAULT,
+        halt: None,
+    });
+}
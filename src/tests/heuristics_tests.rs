@@ -0,0 +1,40 @@
+use crate::{heuristics::detect, quirks};
+
+#[test]
+fn schip_opcodes_pick_schip() {
+    // hires, then loop forever
+    let detection = detect(&[0x00, 0xff, 0x12, 0x02], 0x200);
+    assert_eq!(detection.platform, Some("schip"));
+    assert_eq!(detection.quirks, Some(quirks::SCHIP));
+}
+
+#[test]
+fn unreachable_data_is_ignored() {
+    // loop forever, followed by sprite data that happens to look like 00FF and F000
+    let detection = detect(&[0x12, 0x00, 0x00, 0xff, 0xf0, 0x00], 0x200);
+    assert_eq!(detection.platform, None);
+    assert_eq!(detection.quirks, None);
+}
+
+#[test]
+fn load_store_quirk_is_inferred() {
+    // I = 0x300, load v0-v1, I += v2, load v0-v1 again
+    let unchanged = detect(
+        &[0xa3, 0x00, 0xf1, 0x65, 0xf2, 0x1e, 0xf1, 0x65, 0x12, 0x08],
+        0x200,
+    );
+    assert!(!unchanged.quirks.unwrap().load_store_increments_i);
+
+    // I = 0x300, save v0-v1, save v0-v1 again right after them
+    let increments = detect(&[0xa3, 0x00, 0xf1, 0x55, 0xf1, 0x55, 0x12, 0x06], 0x200);
+    assert!(increments.quirks.unwrap().load_store_increments_i);
+}
+
+#[test]
+fn jump_quirk_is_inferred() {
+    // v3 = 4, jump to 0x300 + v3
+    let detection = detect(&[0x63, 0x04, 0xb3, 0x00], 0x200);
+    let quirks = detection.quirks.expect("the jump should be recognised");
+    assert!(quirks.jump_uses_vx);
+    assert!(detection.reasons[0].contains("B300"));
+}
@@ -0,0 +1,42 @@
+use crate::{
+    cfg::Cfg,
+    info::{find_sprites, find_strings, Sprite},
+    memory_map,
+    opcode::Op,
+};
+
+// 0x200: I = 0x208, draw 3 rows, loop forever
+// 0x208: the sprite, then a name
+const PROGRAM: [u8; 17] = [
+    0xa2, 0x08, 0xd0, 0x13, 0x12, 0x04, 0x00, 0x00, 0x3c, 0x42, 0x81, b'C', b'H', b'I', b'P', b'8',
+    b'!',
+];
+
+#[test]
+fn sprites_are_found_through_i() {
+    let cfg = Cfg::build(&PROGRAM, 0x200);
+    assert_eq!(
+        find_sprites(&cfg, &memory_map::DEFAULT),
+        vec![Sprite {
+            address: 0x208,
+            width: 8,
+            height: 3,
+        }]
+    );
+}
+
+#[test]
+fn strings_are_found_outside_the_code() {
+    let cfg = Cfg::build(&PROGRAM, 0x200);
+    assert_eq!(
+        find_strings(&PROGRAM, &cfg),
+        vec![(0x20b, String::from("CHIP8!"))]
+    );
+}
+
+#[test]
+fn instructions_are_disassembled() {
+    assert_eq!(Op::decode(0xd013).to_string(), "drw v0, v1, 3");
+    assert_eq!(Op::decode(0xa208).to_string(), "ld i, 0x208");
+    assert_eq!(Op::decode(0xf365).to_string(), "ld v3, [i]");
+}
@@ -7,6 +7,8 @@ use crate::{
 
 const KEY_PRESSED: KeyBoard = KeyBoard {
     key_pressed: Some(0u8),
+    keymap: Vec::new(),
+    echo: true,
 };
 
 #[test]
@@ -15,28 +17,25 @@ fn instruction_0x00e0() {
     // clear the screen
 
     let mut cpu = Cpu::init(false);
-    let res = cpu.add_program(&vec![0x00, 0xe0]);
+    let res = cpu.add_program(&[0x00, 0xe0]);
     assert!(res.is_ok(), "Should be able to add the program.");
     // add dummy data to the d_buffer
-    {
-        let mut d_buffer = cpu.d_buffer.borrow_mut();
-        let mut rng = thread_rng();
-        let data = (0..WIDTH * HEIGHT)
-            .map(|_| rng.gen_range(0..=1))
-            .collect::<Vec<u8>>();
-        d_buffer.copy_from_slice(&data);
+    let mut rng = thread_rng();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            cpu.d_buffer.set_pixel(x, y, rng.gen());
+        }
     }
     let exec_res = cpu.step(&KEY_PRESSED);
     match exec_res {
-        Ok(()) => {}
+        Ok(_) => {}
         Err(e) => {
             cpu.dump(true, 6);
             panic!("Failed to execute instruction {:?}", e);
         }
     }
 
-    let d_buffer = cpu.d_buffer.borrow();
-    if d_buffer.contains(&0x1u8) {
+    if cpu.d_buffer.rows().iter().any(|&row| row != 0) {
         cpu.dump(true, 0);
         panic!("After clearing the d_buffer shouldn't contain any set bit");
     }
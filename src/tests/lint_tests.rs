@@ -0,0 +1,41 @@
+use crate::{
+    lint::{lint, Severity},
+    memory_map,
+};
+
+// 0x200: V0 = 0x20, font digit V0
+// 0x204: I = 0xffe, store V0-V2 past the end of 4k of memory
+// 0x208: skip, jump outside the program
+// 0x20c: skip, jump to the odd address 0x211
+// 0x210: FF00 is invalid, 0x211 is 00EE
+// 0x213: code nothing jumps to
+const PROGRAM: [u8; 23] = [
+    0x60, 0x20, 0xf0, 0x29, 0xaf, 0xfe, 0xf2, 0x55, 0x30, 0x00, 0x13, 0x00, 0x30, 0x01, 0x12, 0x11,
+    0xff, 0x00, 0xee, 0x60, 0x01, 0x70, 0x01,
+];
+
+#[test]
+fn every_check_finds_its_bug() {
+    let findings = lint(&PROGRAM, &memory_map::CHIP8)
+        .iter()
+        .map(|finding| (finding.address, finding.severity, finding.check))
+        .collect::<Vec<(usize, Severity, &str)>>();
+    assert_eq!(
+        findings,
+        vec![
+            (0x202, Severity::Error, "font-digit"),
+            (0x206, Severity::Error, "memory-access"),
+            (0x20a, Severity::Error, "target-outside-program"),
+            (0x20e, Severity::Warning, "odd-target"),
+            (0x210, Severity::Error, "invalid-instruction"),
+            (0x213, Severity::Warning, "unreachable-code"),
+        ]
+    );
+}
+
+#[test]
+fn data_behind_a_pointer_is_not_unreachable_code() {
+    // I = 0x206, draw, loop forever, then a sprite that decodes as instructions
+    let program = [0xa2, 0x06, 0xd0, 0x12, 0x12, 0x04, 0x60, 0x60];
+    assert_eq!(lint(&program, &memory_map::DEFAULT), vec![]);
+}
@@ -0,0 +1,65 @@
+use std::io::{Cursor, Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::loader::{decode_rom, detect, parse_hex_text, parse_intel_hex, RomFormat};
+
+const PROGRAM: [u8; 6] = [0x00, 0xE0, 0x60, 0x0A, 0x12, 0x02];
+
+#[test]
+fn intel_hex_is_rebased_to_program_start() {
+    let src = ":06020000 00E0600A1202 9A\n:00000001FF\n".replace(' ', "");
+    assert_eq!(parse_intel_hex(&src), Ok(PROGRAM.to_vec()));
+    assert_eq!(detect(src.as_bytes(), "-"), RomFormat::IntelHex);
+}
+
+#[test]
+fn intel_hex_reports_bad_checksum() {
+    let src = ":0602000000E0600A120200\n:00000001FF\n";
+    let err = parse_intel_hex(src).unwrap_err();
+    assert!(err.starts_with("line 1: checksum mismatch"), "{}", err);
+}
+
+#[test]
+fn hex_text_accepts_python_lists_and_comments() {
+    let src = "# clear\n[0x00E0, 0x600A] // v0 = 10\n12 02 ; loop\n";
+    assert_eq!(parse_hex_text(src), Ok(PROGRAM.to_vec()));
+
+    let err = parse_hex_text("00E0\n60 0G\n").unwrap_err();
+    assert_eq!(err, "line 2: \"0G\" is not a hex byte or word");
+}
+
+#[test]
+fn printable_roms_are_only_hex_text_when_they_parse() {
+    assert_eq!(detect(b"00E0 600A\n1202\n", "-"), RomFormat::HexText);
+    // v1 := 0x61, v1 += 0x31, v3 += 0x20
+    assert_eq!(detect(b"aaq1s ", "-"), RomFormat::Raw);
+    assert_eq!(decode_rom(b"aaq1s ", "-").unwrap().program, b"aaq1s ");
+    assert_eq!(detect(b":not intel hex", "-"), RomFormat::Raw);
+}
+
+#[test]
+fn rom_extensions_win_over_magic_numbers() {
+    // jump 0xF8B
+    let program = [0x1f, 0x8b, 0x00, 0xe0];
+    assert_eq!(detect(&program, "jump.ch8"), RomFormat::Raw);
+    assert_eq!(decode_rom(&program, "jump.c8").unwrap().program, program);
+    assert_eq!(detect(&program, "-"), RomFormat::Gzip);
+}
+
+#[test]
+fn archives_are_unpacked() {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(&PROGRAM).unwrap();
+    let gz = gz.finish().unwrap();
+    assert_eq!(decode_rom(&gz, "rom.ch8.gz").unwrap().program, PROGRAM);
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file("README.txt", options).unwrap();
+    zip.write_all(b"not a rom").unwrap();
+    zip.start_file("game.ch8", options).unwrap();
+    zip.write_all(&PROGRAM).unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+    assert_eq!(decode_rom(&zip, "game.zip").unwrap().program, PROGRAM);
+}
@@ -0,0 +1,72 @@
+use std::{fs, thread, time::Duration};
+
+use crate::{
+    cpu::Cpu,
+    keyboard::KeyBoard,
+    machine::{self, Input, Machine, Output},
+};
+
+fn ibm_logo() -> Machine {
+    let mut cpu = Cpu::init(false);
+    cpu.add_program(&fs::read("test_files/IBM Logo.c8").expect("rom should exist"))
+        .unwrap();
+    Machine::new(cpu, KeyBoard::new(), 20)
+}
+
+#[test]
+fn spawned_machine_sends_every_frame() {
+    let (inputs, outputs, handle) = machine::spawn(ibm_logo(), Some(10));
+    let mut frames = Vec::new();
+    let mut dirty = 0;
+    for output in outputs.iter() {
+        match output {
+            Output::Frame(snapshot) => {
+                dirty |= snapshot.dirty;
+                frames.push(*snapshot);
+            }
+            Output::Stopped => break,
+        }
+    }
+    handle.join().unwrap();
+    drop(inputs);
+
+    assert_eq!(
+        frames.iter().map(|s| s.frame).collect::<Vec<u64>>(),
+        (1..=10).collect::<Vec<u64>>()
+    );
+    // the logo is drawn by then, and every row it's on was reported as changed
+    let last = &frames.last().unwrap().display;
+    let drawn = (0..32)
+        .filter(|&y| last.rows()[y] != 0)
+        .fold(0, |rows, y| rows | 1 << y);
+    assert_ne!(drawn, 0);
+    assert_eq!(dirty & drawn, drawn);
+}
+
+#[test]
+fn a_machine_that_needs_every_frame_waits_for_the_window() {
+    // the window falls further behind than the queue is long
+    let mut machine = ibm_logo();
+    machine.every_frame = true;
+    let (inputs, outputs, handle) = machine::spawn(machine, Some(20));
+    thread::sleep(Duration::from_millis(500));
+    let frames = outputs
+        .iter()
+        .map_while(|output| match output {
+            Output::Frame(snapshot) => Some(snapshot.frame),
+            Output::Stopped => None,
+        })
+        .collect::<Vec<u64>>();
+    handle.join().unwrap();
+    drop(inputs);
+    assert_eq!(frames, (1..=20).collect::<Vec<u64>>());
+}
+
+#[test]
+fn dropping_the_inputs_stops_the_machine() {
+    let (inputs, outputs, handle) = machine::spawn(ibm_logo(), None);
+    inputs.send(Input::Key(minifb::Key::X)).unwrap();
+    assert!(matches!(outputs.recv().unwrap(), Output::Frame(_)));
+    drop(inputs);
+    handle.join().unwrap();
+}
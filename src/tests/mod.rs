@@ -3,3 +3,72 @@ mod cpu_tests;
 
 #[cfg(test)]
 mod instruction_tests;
+
+#[cfg(test)]
+mod screenshot_tests;
+
+#[cfg(test)]
+mod recording_tests;
+
+#[cfg(test)]
+mod golden_tests;
+
+#[cfg(test)]
+mod quirks_tests;
+
+#[cfg(test)]
+mod loader_tests;
+
+#[cfg(test)]
+mod cartridge_tests;
+
+#[cfg(test)]
+mod octo_tests;
+
+#[cfg(test)]
+mod romdb_tests;
+
+#[cfg(test)]
+mod heuristics_tests;
+
+#[cfg(test)]
+mod font_tests;
+
+#[cfg(test)]
+mod blocks_tests;
+
+#[cfg(test)]
+mod display_tests;
+
+#[cfg(test)]
+mod machine_tests;
+
+#[cfg(test)]
+mod bench_tests;
+
+#[cfg(test)]
+mod profiler_tests;
+
+#[cfg(test)]
+mod coverage_tests;
+
+#[cfg(test)]
+mod cfg_tests;
+
+#[cfg(test)]
+mod lint_tests;
+
+#[cfg(test)]
+mod info_tests;
+
+#[cfg(test)]
+mod decompile_tests;
+
+#[cfg(test)]
+mod optimize_tests;
+
+#[cfg(test)]
+mod sprites_tests;
+
+#[cfg(test)]
+mod terminal_tests;
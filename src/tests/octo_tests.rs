@@ -0,0 +1,128 @@
+use crate::{cpu::Cpu, keyboard::KeyBoard, loader::decode_rom, octo::compile};
+
+fn run(source: &str, steps: usize) -> Cpu {
+    let program = compile(source).expect("should compile");
+    let mut cpu = Cpu::init(false);
+    cpu.add_program(&program).unwrap();
+    let keyboard = KeyBoard::new();
+    for _ in 0..steps {
+        cpu.step(&keyboard).unwrap();
+    }
+    cpu
+}
+
+#[test]
+fn statements_compile_to_their_instructions() {
+    let source = "
+        : sprite 0x3c 0b01000010 -1
+        : main
+            clear
+            v0 := 5  v1 += v0  v2 -= 1  v3 =- v1  vf := key
+            i := sprite  i := hex v0  i += v1
+            sprite v0 v1 3  bcd v2  save v3  load v4
+            delay := v0  v0 := delay  v1 := random 0x0f
+            jump main
+    ";
+    assert_eq!(
+        compile(source),
+        Ok(vec![
+            0x12, 0x05, 0x3c, 0x42, 0xff, 0x00, 0xe0, 0x60, 0x05, 0x81, 0x04, 0x72, 0xff, 0x83,
+            0x17, 0xff, 0x0a, 0xa2, 0x02, 0xf0, 0x29, 0xf1, 0x1e, 0xd0, 0x13, 0xf2, 0x33, 0xf3,
+            0x55, 0xf4, 0x65, 0xf0, 0x15, 0xf0, 0x07, 0xc1, 0x0f, 0x12, 0x05,
+        ])
+    );
+}
+
+#[test]
+fn labels_constants_and_macros_are_resolved() {
+    let source = "
+        :const ROWS 4
+        :calc WIDE { ROWS * 2 + 1 }
+        :alias count v3
+        :macro twice op { op op }
+        : main
+            count := WIDE
+            twice draw
+            :unpack 0xa data
+            :next target v0 := 0
+            loop again
+        : draw ;
+        : data
+    ";
+    // calc is evaluated right to left, so WIDE is 4 * 3
+    assert_eq!(
+        compile(source),
+        Ok(vec![
+            0x12, 0x02, 0x63, 0x0c, 0x22, 0x10, 0x22, 0x10, 0x60, 0xa2, 0x61, 0x12, 0x60, 0x00,
+            0x12, 0x0e, 0x00, 0xee,
+        ])
+    );
+}
+
+#[test]
+fn comparisons_match_their_meaning() {
+    for (a, b) in [(3, 7), (7, 3), (5, 5), (0, 255), (255, 255)] {
+        let source = format!(
+            "
+            : main
+                v0 := {} v1 := {}
+                if v0 < v1 then v2 := 1
+                if v0 > v1 then v3 := 1
+                if v0 <= v1 then v4 := 1
+                if v0 >= v1 then v5 := 1
+                if v0 < {} then v6 := 1
+                if v0 > {} then v7 := 1
+                if v0 <= {} then v8 := 1
+                if v0 >= {} then v9 := 1
+                if v0 != v1 begin va := 1 else vb := 1 end
+                loop again
+            ",
+            a, b, b, b, b, b
+        );
+        let cpu = run(&source, 200);
+        let r = |x: usize| cpu.gp_registers[x] == 1;
+        let expected = [a < b, a > b, a <= b, a >= b];
+        assert_eq!([r(2), r(3), r(4), r(5)], expected, "{} and {}", a, b);
+        assert_eq!([r(6), r(7), r(8), r(9)], expected, "{} and {}", a, b);
+        assert_eq!([r(0xa), r(0xb)], [a != b, a == b], "{} and {}", a, b);
+    }
+}
+
+#[test]
+fn loops_exit_through_while() {
+    let cpu = run(
+        ": main v0 := 0 loop v0 += 1 while v0 != 10 v1 += 2 again v2 := 1 loop again",
+        200,
+    );
+    assert_eq!(cpu.gp_registers[..3], [10, 18, 1]);
+}
+
+#[test]
+fn errors_name_the_line() {
+    assert_eq!(
+        compile(": main\n  jump nowhere\n"),
+        Err(String::from("line 2: nowhere is not defined"))
+    );
+    assert_eq!(
+        compile(": start clear"),
+        Err(String::from("the program has no main label"))
+    );
+    assert_eq!(
+        compile(": main\nhires"),
+        Err(String::from(
+            "line 2: hires is a SUPER-CHIP or XO-CHIP statement, only chip8 programs are supported"
+        ))
+    );
+    assert!(compile(": main if v0 == 1 then v0 := v1 v1 += 1").is_ok_and(|rom| rom.len() == 8));
+    assert!(compile(": main if v0 == 1 then if v1 < 1 then clear").is_err());
+    assert_eq!(
+        compile(": main if v0 < 1 then if v1 == 1 then clear").map(|rom| rom[6..].to_vec()),
+        Ok(vec![0x4f, 0x00, 0x41, 0x01, 0x00, 0xe0])
+    );
+}
+
+#[test]
+fn octo_source_is_loaded_as_a_rom() {
+    let rom = decode_rom(b": main loop again", "game.8o").unwrap();
+    assert_eq!(rom.program, vec![0x12, 0x02, 0x12, 0x02]);
+}
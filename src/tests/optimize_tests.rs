@@ -0,0 +1,110 @@
+use crate::{
+    octo::compile,
+    optimize::{optimize, verify, Stats, VerifyOptions},
+};
+
+const OPTIONS: VerifyOptions = VerifyOptions {
+    frames: 120,
+    seed: 7,
+};
+
+#[test]
+fn jumps_adds_and_unreachable_code_are_optimized() {
+    // main follows the jump to it, so that jump goes too
+    let program = compile(
+        "
+        : main
+            v0 := 1 v0 += 2 v0 += 3
+            v1 += 0
+            jump hop
+        : hop jump draw
+        : dead clear clear
+        : draw
+            i := dot
+            sprite v0 v1 1
+            if v0 == 6 then v1 += 1
+            v1 += 1
+            loop again
+        : dot 0x80
+        ",
+    )
+    .unwrap();
+    let (optimized, stats) = optimize(&program, 0x200);
+    assert_eq!(
+        optimized,
+        vec![
+            0x60, 0x06, 0xa2, 0x0e, 0xd0, 0x11, 0x40, 0x06, 0x71, 0x01, 0x71, 0x01, 0x12, 0x0c,
+            0x80,
+        ]
+    );
+    assert_eq!(
+        stats,
+        Stats {
+            threaded_jumps: 1,
+            removed_jumps: 2,
+            folded_adds: 3,
+            dropped_bytes: 6,
+        }
+    );
+    assert!(verify(&program, &optimized, 0x200, &OPTIONS).is_ok());
+}
+
+#[test]
+fn skipped_instructions_and_data_stay() {
+    // a jump to the next instruction after a skip decides what gets skipped, and the jump I
+    // points into is read as data. with an indirect jump nothing can move at all
+    let program = [
+        0x12, 0x02, 0xa2, 0x01, 0xf0, 0x65, 0x30, 0x02, 0x12, 0x0a, 0x70, 0x01, 0x70, 0x01, 0x12,
+        0x0e,
+    ];
+    let (optimized, stats) = optimize(&program, 0x200);
+    assert_eq!(optimized[..10], program[..10]);
+    assert_eq!(optimized[10..], [0x70, 0x02, 0x12, 0x0c]);
+    assert_eq!(stats.folded_adds, 1);
+
+    let indirect = [0x60, 0x00, 0xb2, 0x04, 0x12, 0x06, 0x12, 0x06];
+    assert_eq!(optimize(&indirect, 0x200).0, indirect);
+}
+
+#[test]
+fn verify_notices_different_pictures() {
+    let original = compile(": main i := hex v0 sprite v0 v0 5 loop again").unwrap();
+    let mut changed = original.clone();
+    // draw 4 rows of the digit instead of 5
+    changed[5] = 0x04;
+    assert!(verify(&original, &original, 0x200, &OPTIONS).is_ok_and(|pictures| pictures == 2));
+    assert!(verify(&original, &changed, 0x200, &OPTIONS).is_err());
+
+    // stopping before the last picture is a difference too
+    let crashed = [0xf0, 0x29, 0x00, 0x00];
+    assert!(verify(&original, &crashed, 0x200, &OPTIONS).is_err());
+}
+
+#[test]
+fn verify_compares_how_roms_stop_not_where() {
+    // both recurse until the stack is full, from addresses the optimizer would move
+    let original = [0x12, 0x02, 0x22, 0x02];
+    let optimized = [0x22, 0x00];
+    assert_eq!(optimize(&original, 0x200).0, optimized);
+    assert!(verify(&original, &optimized, 0x200, &OPTIONS).is_ok());
+    let returns = [0x00, 0xee];
+    assert!(verify(&original, &returns, 0x200, &OPTIONS).is_err());
+}
+
+#[test]
+fn unreachable_code_drawn_as_a_sprite_stays() {
+    // the sprite starts at the return of the subroutine and goes on into bytes that decode as code
+    // nothing reaches, only the code that really is dead goes
+    let program = [
+        0x12, 0x06, 0x00, 0xe0, 0x00, 0xe0, 0x22, 0x0a, 0x12, 0x08, 0xa2, 0x0e, 0xd0, 0x18, 0x00,
+        0xee, 0x60, 0x01, 0x70, 0x02, 0x00, 0xe0,
+    ];
+    let (optimized, stats) = optimize(&program, 0x200);
+    assert_eq!(
+        optimized[..10],
+        [0x22, 0x04, 0x12, 0x02, 0xa2, 0x08, 0xd0, 0x18, 0x00, 0xee]
+    );
+    assert_eq!(optimized[10..], program[16..]);
+    assert_eq!(stats.dropped_bytes, 4);
+    assert!(verify(&program, &optimized, 0x200, &OPTIONS).is_ok());
+}
@@ -0,0 +1,70 @@
+use crate::{
+    cpu::Cpu,
+    keyboard::KeyBoard,
+    machine::Machine,
+    profiler::{Profiler, Subroutine},
+};
+
+// 0x200: call 0x206 then loop back to it
+// 0x206: V0 += 1, call 0x20c, return
+// 0x20c: V1 += 1, return
+const PROGRAM: [u8; 16] = [
+    0x22, 0x06, 0x12, 0x00, 0x00, 0x00, 0x70, 0x01, 0x22, 0x0c, 0x00, 0xee, 0x71, 0x01, 0x00, 0xee,
+];
+
+fn profile(frames: usize) -> Machine {
+    let mut cpu = Cpu::init(false);
+    cpu.add_program(&PROGRAM).unwrap();
+    // every loop is 7 instructions
+    let mut machine = Machine::new(cpu, KeyBoard::new(), 7);
+    machine.profiler = Some(Profiler::new());
+    for _ in 0..frames {
+        machine.run_frame().unwrap();
+    }
+    machine
+}
+
+#[test]
+fn subroutines_get_their_own_and_inclusive_cycles() {
+    let machine = profile(10);
+    let profiler = machine.profiler.as_ref().unwrap();
+
+    assert_eq!(
+        profiler.subroutines(),
+        vec![
+            Subroutine {
+                address: 0x206,
+                calls: 10,
+                self_cycles: 30,
+                total_cycles: 50,
+            },
+            Subroutine {
+                address: 0x20c,
+                calls: 10,
+                self_cycles: 20,
+                total_cycles: 20,
+            },
+        ]
+    );
+    assert_eq!(
+        profiler.call_graph(),
+        vec![(None, 0x206, 10), (Some(0x206), 0x20c, 10)]
+    );
+    assert_eq!(profiler.hot_spots()[0], (0x200, 10));
+}
+
+#[test]
+fn folded_stacks_cover_every_instruction() {
+    let machine = profile(3);
+    let profiler = machine.profiler.as_ref().unwrap();
+    let folded = profiler.folded_stacks();
+    assert_eq!(
+        folded,
+        vec![(vec![], 6), (vec![0x206], 9), (vec![0x206, 0x20c], 6)]
+    );
+
+    let path = std::env::temp_dir().join("chip8-profile-test.folded");
+    profiler.write_folded(&path).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(written, "main 6\nmain;0x206 9\nmain;0x206;0x20c 6\n");
+}
@@ -0,0 +1,190 @@
+use crate::{
+    conformance::{find_marks, glyph, read_labels, run_rom, Mark, FAIL_GLYPH, PASS_GLYPH},
+    cpu::{Cpu, HEIGHT, WIDTH},
+    display::Display,
+    keyboard::KeyBoard,
+    quirks::{self, Quirks},
+};
+
+const NO_KEY: KeyBoard = KeyBoard {
+    key_pressed: None,
+    keymap: Vec::new(),
+    echo: true,
+};
+
+fn cpu_with(quirks: Quirks, program: &[u8]) -> Cpu {
+    let mut cpu = Cpu::init(false);
+    cpu.quirks = quirks;
+    cpu.add_program(program)
+        .expect("should be able to add the program");
+    cpu
+}
+
+#[test]
+fn shift_quirk_shifts_vx_in_place() {
+    // 0x8ab6 -> shr va vb
+    let mut cpu = cpu_with(quirks::SCHIP, &[0x8a, 0xb6]);
+    cpu.gp_registers[0xa] = 0x5;
+    cpu.gp_registers[0xb] = 0x80;
+    cpu.step(&NO_KEY).expect("should execute 0x8XY6");
+    assert_eq!(cpu.gp_registers[0xa], 0x2);
+    assert_eq!(cpu.gp_registers[0xf], 0x1);
+}
+
+#[test]
+fn load_store_quirk_leaves_i_unchanged() {
+    // 0xa300 -> ld I 0x300, 0xf255 -> ld [I] v2
+    let program = [0xa3, 0x00, 0xf2, 0x55];
+    let mut cpu = cpu_with(quirks::SCHIP, &program);
+    cpu.step(&NO_KEY).expect("should execute 0xANNN");
+    cpu.step(&NO_KEY).expect("should execute 0xFX55");
+    assert_eq!(cpu.i, 0x300);
+
+    let mut cpu = cpu_with(quirks::CHIP8, &program);
+    cpu.step(&NO_KEY).expect("should execute 0xANNN");
+    cpu.step(&NO_KEY).expect("should execute 0xFX55");
+    assert_eq!(cpu.i, 0x303);
+}
+
+#[test]
+fn vf_reset_quirk_clears_vf_after_logic_ops() {
+    // 0x8ab1 -> or va vb
+    let mut cpu = cpu_with(quirks::CHIP8, &[0x8a, 0xb1]);
+    cpu.gp_registers[0xf] = 0x1;
+    cpu.step(&NO_KEY).expect("should execute 0x8XY1");
+    assert_eq!(cpu.gp_registers[0xf], 0x0);
+
+    let mut cpu = cpu_with(quirks::XOCHIP, &[0x8a, 0xb1]);
+    cpu.gp_registers[0xf] = 0x1;
+    cpu.step(&NO_KEY).expect("should execute 0x8XY1");
+    assert_eq!(cpu.gp_registers[0xf], 0x1);
+}
+
+#[test]
+fn jump_quirk_uses_vx() {
+    // 0xb204 -> jmp v2 0x204
+    let mut cpu = cpu_with(quirks::SCHIP, &[0xb2, 0x04]);
+    cpu.gp_registers[0x0] = 0x10;
+    cpu.gp_registers[0x2] = 0x20;
+    cpu.step(&NO_KEY).expect("should execute 0xBNNN");
+    assert_eq!(cpu.pc, 0x224);
+}
+
+#[test]
+fn clip_quirk_cuts_sprites_at_the_edge() {
+    // 0xa000 -> ld I 0x000 (the font for 0), 0xd015 -> drw v0 v1 5
+    let program = [0xa0, 0x00, 0xd0, 0x15];
+    for (quirks, wraps) in [(quirks::CHIP8, false), (quirks::XOCHIP, true)] {
+        let mut cpu = cpu_with(quirks, &program);
+        cpu.gp_registers[0x0] = (WIDTH - 2) as u8;
+        cpu.gp_registers[0x1] = (HEIGHT - 2) as u8;
+        cpu.step(&NO_KEY).expect("should execute 0xANNN");
+        cpu.step(&NO_KEY).expect("should execute 0xDXYN");
+        // the top left corner of the glyph wrapped around to (0, 0)
+        let wrapped = cpu.d_buffer.pixel(0, 0) || cpu.d_buffer.pixel(1, 0);
+        assert_eq!(wrapped, wraps);
+        assert!(cpu.d_buffer.pixel(WIDTH - 2, HEIGHT - 2));
+    }
+}
+
+#[test]
+fn conformance_reads_marks_in_column_order() {
+    let pass = glyph(PASS_GLYPH);
+    let fail = glyph(FAIL_GLYPH);
+    let mut d_buffer = Display::new();
+    let mut stamp = |rows: &[&str], x: usize, y: usize| {
+        for (dy, row) in rows.iter().enumerate() {
+            for (dx, c) in row.bytes().enumerate() {
+                d_buffer.set_pixel(x + dx, y + dy, c == b'#');
+            }
+        }
+    };
+    stamp(PASS_GLYPH, 40, 2);
+    stamp(FAIL_GLYPH, 10, 12);
+    stamp(PASS_GLYPH, 10, 2);
+
+    let marks = find_marks(&d_buffer, &pass, &fail);
+    assert_eq!(
+        marks,
+        vec![
+            (10, 2, Mark::Pass),
+            (10, 12, Mark::Fail),
+            (40, 2, Mark::Pass)
+        ]
+    );
+}
+
+#[test]
+fn conformance_columns_come_from_where_the_marks_are() {
+    let pass = glyph(PASS_GLYPH);
+    let fail = glyph(FAIL_GLYPH);
+    let mut d_buffer = Display::new();
+    let mut stamp = |rows: &[&str], x: usize, y: usize| {
+        for (dy, row) in rows.iter().enumerate() {
+            for (dx, c) in row.bytes().enumerate() {
+                d_buffer.set_pixel(x + dx, y + dy, c == b'#');
+            }
+        }
+    };
+    // the first column isn't lined up on 8 pixels and the second one starts right after it
+    stamp(PASS_GLYPH, 15, 2);
+    stamp(FAIL_GLYPH, 16, 12);
+    stamp(PASS_GLYPH, 23, 7);
+
+    let marks = find_marks(&d_buffer, &pass, &fail);
+    assert_eq!(
+        marks,
+        vec![
+            (15, 2, Mark::Pass),
+            (16, 12, Mark::Fail),
+            (23, 7, Mark::Pass)
+        ]
+    );
+}
+
+#[test]
+fn conformance_labels_are_read_left_of_the_marks() {
+    let pass = glyph(PASS_GLYPH);
+    let fail = glyph(FAIL_GLYPH);
+    let mut d_buffer = Display::new();
+    let mut stamp = |rows: &[&str], x: usize, y: usize| {
+        for (dy, row) in rows.iter().enumerate() {
+            for (dx, c) in row.bytes().enumerate() {
+                d_buffer.set_pixel(x + dx, y + dy, c == b'#');
+            }
+        }
+    };
+    // a label of two letters 2 columns apart, a test with two checks and a mark on its own
+    stamp(&["##..#", "##", "##", "##"], 2, 2);
+    stamp(PASS_GLYPH, 10, 2);
+    stamp(PASS_GLYPH, 19, 2);
+    stamp(FAIL_GLYPH, 40, 20);
+
+    let marks = find_marks(&d_buffer, &pass, &fail);
+    let labels = read_labels(&d_buffer, &marks, &pass, &fail);
+    assert_eq!(
+        labels,
+        vec![
+            vec!["\u{28ff}\u{2800}\u{2801}".to_string()],
+            vec!["\u{28ff}\u{2800}\u{2801}".to_string()],
+            vec![]
+        ]
+    );
+}
+
+#[test]
+fn conformance_roms_run_frames_like_emulate() {
+    // waits for a delay of 3 before drawing the 0 glyph
+    let program = [
+        0x6a, 0x03, 0xfa, 0x15, 0xfb, 0x07, 0x3b, 0x00, 0x12, 0x04, 0xf0, 0x29, 0xd0, 0x05, 0x12,
+        0x0e,
+    ];
+    let drawn = |frames: u64| {
+        run_rom(&program, quirks::DEFAULT, 1, frames, 10)
+            .unwrap()
+            .pixel(0, 0)
+    };
+    // the timer ticks once a frame, not once an instruction
+    assert!(!drawn(2));
+    assert!(drawn(4));
+}
@@ -0,0 +1,51 @@
+use std::fs;
+
+use crate::{palette::Palette, recording::Recorder};
+
+#[test]
+fn y4m_writes_every_emulated_frame() {
+    let path = std::env::temp_dir().join("chip8_y4m_writes_every_emulated_frame.y4m");
+    let (width, height) = (4, 2);
+    let mut recorder =
+        Recorder::start(&path, width, height, Palette::default()).expect("should create y4m");
+    let frame = vec![0u32; width * height];
+    for _ in 0..3 {
+        recorder.push_frame(&frame).expect("should write frame");
+    }
+    recorder.finish().expect("should finish recording");
+
+    let data = fs::read(&path).expect("recording should exist");
+    fs::remove_file(&path).ok();
+    let header = b"YUV4MPEG2 W4 H2 F60:1 Ip A1:1 C444\n";
+    assert_eq!(&data[..header.len()], header);
+    let frame_size = b"FRAME\n".len() + width * height * 3;
+    assert_eq!(data.len(), header.len() + frame_size * 3);
+}
+
+#[test]
+fn gif_delays_follow_emulated_frames() {
+    let path = std::env::temp_dir().join("chip8_gif_delays_follow_emulated_frames.gif");
+    let palette = Palette::default();
+    let mut recorder = Recorder::start(&path, 2, 1, palette).expect("should create gif");
+    let off = vec![palette.background; 2];
+    let on = vec![palette.foreground; 2];
+    // one second of nothing, then one second of set pixels
+    for _ in 0..60 {
+        recorder.push_frame(&off).expect("should write frame");
+    }
+    for _ in 0..60 {
+        recorder.push_frame(&on).expect("should write frame");
+    }
+    recorder.finish().expect("should finish recording");
+
+    let file = fs::File::open(&path).expect("recording should exist");
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(file).expect("should be a valid gif");
+    let mut delays = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().expect("should decode frame") {
+        delays.push(frame.delay);
+    }
+    fs::remove_file(&path).ok();
+    assert_eq!(delays, vec![100, 100], "identical frames should be merged");
+}
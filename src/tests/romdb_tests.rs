@@ -0,0 +1,91 @@
+use std::fs;
+
+use minifb::Key;
+
+use crate::{
+    palette::Palette,
+    quirks::{self, Quirks},
+    romdb::{sha1_hex, RomDb},
+};
+
+#[test]
+fn sha1_matches_reference() {
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+}
+
+#[test]
+fn builtin_database_knows_bundled_roms() {
+    let rom = fs::read("test_files/PONG2.c8").expect("rom should exist");
+    let db = RomDb::builtin();
+    let info = db.lookup(&rom).expect("pong 2 should be in the database");
+    assert_eq!(info.title, "Pong 2");
+    // modern chip8 is the default behavior, but clipping sprites
+    let quirks = info.options.quirks.expect("pong 2 has a platform");
+    assert!(quirks.clip_sprites);
+    assert_eq!(
+        Quirks {
+            clip_sprites: false,
+            ..quirks
+        },
+        quirks::DEFAULT
+    );
+    assert!(info.unsupported_quirks.is_empty());
+    let keymap = info.options.keymap.as_ref().expect("pong 2 has key hints");
+    assert!(keymap.contains(&(Key::Up, 1)));
+    assert!(keymap.contains(&(Key::Q, 4)));
+
+    assert!(db.lookup(b"not a known rom").is_none());
+}
+
+#[test]
+fn community_database_fields_are_applied() {
+    let hash = sha1_hex(&[0x12, 0x00]);
+    let db = RomDb::parse(&format!(
+        r##"[{{
+            "title": "Spinner",
+            "roms": {{
+                "{}": {{
+                    "platforms": ["superchip", "xochip"],
+                    "tickrate": 30,
+                    "quirkyPlatforms": {{"superchip": {{"jump": false, "wrap": true}}}},
+                    "colors": {{"pixels": ["#102030", "#f0e0d0"], "buzzer": "#ff0000"}}
+                }}
+            }}
+        }}]"##,
+        hash.to_uppercase()
+    ))
+    .expect("should parse database");
+
+    let info = db.lookup(&[0x12, 0x00]).expect("rom should be found");
+    assert_eq!(info.platform.as_deref(), Some("superchip"));
+    assert_eq!(info.options.tickrate, Some(30));
+    let quirks = info.options.quirks.expect("superchip has quirks");
+    assert!(quirks.shift_in_place);
+    assert!(!quirks.jump_uses_vx);
+    assert!(!quirks.clip_sprites);
+    assert_eq!(
+        info.options.palette,
+        Some(Palette {
+            background: 0x102030,
+            foreground: 0xf0e0d0
+        })
+    );
+}
+
+#[test]
+fn quirks_that_cant_be_applied_are_reported() {
+    let hash = sha1_hex(&[0x12, 0x00]);
+    let db = RomDb::parse(&format!(
+        r#"[{{"title": "Counter", "roms": {{"{}": {{
+            "platforms": ["modernChip8"],
+            "quirkyPlatforms": {{"modernChip8": {{"memoryIncrementByX": true, "vblank": false}}}}
+        }}}}}}]"#,
+        hash
+    ))
+    .expect("should parse database");
+    let info = db.lookup(&[0x12, 0x00]).expect("rom should be found");
+    assert_eq!(
+        info.unsupported_quirks,
+        vec![String::from("memoryIncrementByX")]
+    );
+}
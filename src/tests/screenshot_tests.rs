@@ -0,0 +1,60 @@
+use crate::{
+    chip8::render,
+    cpu::{HEIGHT, WIDTH},
+    display::Display,
+    palette::Palette,
+    screenshot::{encode, ImageFormat},
+};
+
+#[test]
+fn pbm_marks_non_background_pixels() {
+    let palette = Palette::default();
+    let mut d_buffer = Display::new();
+    // first and last pixel of the first row
+    d_buffer.set_pixel(0, 0, true);
+    d_buffer.set_pixel(WIDTH - 1, 0, true);
+    let mut pixels = vec![0u32; WIDTH * HEIGHT];
+    render(&d_buffer, &palette, 1, &mut pixels);
+
+    let mut out = Vec::new();
+    encode(&mut out, &ImageFormat::Pbm, &pixels, WIDTH, HEIGHT, &palette)
+        .expect("should encode pbm");
+
+    let header = b"P4\n64 32\n";
+    assert_eq!(&out[..header.len()], header);
+    let data = &out[header.len()..];
+    assert_eq!(data.len(), WIDTH / 8 * HEIGHT, "1 bit per pixel");
+    assert_eq!(data[0], 0x80);
+    assert_eq!(data[7], 0x01);
+    assert!(data[8..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn ppm_uses_palette_and_scale() {
+    let palette = Palette::parse("102030,a0b0c0").expect("valid palette");
+    let mut d_buffer = Display::new();
+    d_buffer.set_pixel(0, 0, true);
+    let scale = 2;
+    let mut pixels = vec![0u32; WIDTH * HEIGHT * scale * scale];
+    render(&d_buffer, &palette, scale, &mut pixels);
+
+    let mut out = Vec::new();
+    encode(
+        &mut out,
+        &ImageFormat::Ppm,
+        &pixels,
+        WIDTH * scale,
+        HEIGHT * scale,
+        &palette,
+    )
+    .expect("should encode ppm");
+
+    let header = b"P6\n128 64\n255\n";
+    assert_eq!(&out[..header.len()], header);
+    let data = &out[header.len()..];
+    // pixel (1, 1) is still part of the scaled up first pixel
+    let second_row = WIDTH * scale * 3;
+    assert_eq!(&data[0..3], &[0xa0, 0xb0, 0xc0]);
+    assert_eq!(&data[second_row + 3..second_row + 6], &[0xa0, 0xb0, 0xc0]);
+    assert_eq!(&data[6..9], &[0x10, 0x20, 0x30]);
+}
@@ -0,0 +1,85 @@
+use crate::{
+    info::Sprite,
+    sprites::{sheet, source, Format, Image},
+};
+
+fn png(width: u32, height: u32, color: png::ColorType, pixels: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut data, width, height);
+        encoder.set_color(color);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+    }
+    data
+}
+
+#[test]
+fn images_are_cut_into_sprites() {
+    // two 8x2 tiles, the first a diagonal, the second using all 4 colors
+    let mut pixels = vec![0u8; 16 * 2];
+    pixels[0] = 1;
+    pixels[16 + 1] = 1;
+    pixels[8..12].copy_from_slice(&[1, 2, 3, 0]);
+    let image = Image {
+        width: 16,
+        height: 2,
+        pixels,
+    };
+    assert_eq!(
+        image.tiles(8, 2, 2).unwrap(),
+        vec![vec![0x80, 0x40, 0x00, 0x00], vec![0xa0, 0x00, 0x60, 0x00]]
+    );
+    assert_eq!(image.tiles(8, 1, 1).unwrap().len(), 4);
+    assert!(image.tiles(16, 16, 1).is_err());
+}
+
+#[test]
+fn png_colors_become_planes() {
+    // gray with alpha: transparent, dark, light, white
+    let data = png(
+        4,
+        1,
+        png::ColorType::GrayscaleAlpha,
+        &[0, 0, 40, 255, 160, 255, 255, 255],
+    );
+    assert_eq!(Image::from_png(&data, 2).unwrap().pixels, vec![0, 1, 2, 3]);
+    assert_eq!(Image::from_png(&data, 1).unwrap().pixels, vec![0, 0, 1, 1]);
+
+    let data = png(5, 1, png::ColorType::Grayscale, &[0, 40, 80, 120, 160]);
+    assert!(Image::from_png(&data, 2).is_err());
+}
+
+#[test]
+fn sprites_print_as_source() {
+    let tiles = vec![vec![0x3c, 0x42], vec![0xff]];
+    assert_eq!(
+        source(&tiles, "ball", Format::Octo),
+        ": ball_0\n\t0x3c 0x42\n: ball_1\n\t0xff\n"
+    );
+    assert_eq!(
+        source(&tiles[..1], "ball", Format::Rust),
+        "const BALL: [u8; 2] = [\n    0x3c, 0x42,\n];\n"
+    );
+    assert_eq!(
+        source(&tiles, "ball", Format::Hex),
+        "ball_0 3c42\nball_1 ff\n"
+    );
+}
+
+#[test]
+fn extracted_sprites_are_drawn_on_a_sheet() {
+    let program = [0xa2, 0x04, 0x81, 0x7e];
+    let sprites = [Sprite {
+        address: 0x202,
+        width: 8,
+        height: 2,
+    }];
+    let (pixels, width, height) = sheet(&program, 0x200, &sprites, 2);
+    // one sprite with a gap of a pixel around it
+    assert_eq!((width, height), (20, 8));
+    let lit = |x: usize, y: usize| pixels[y * width + x] == 0xffffff;
+    assert!(lit(2, 2) && lit(3, 3) && !lit(4, 2));
+    assert!(lit(16, 2) && !lit(2, 4) && lit(4, 4));
+    assert_eq!(pixels[0], 0x404040);
+}
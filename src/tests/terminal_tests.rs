@@ -0,0 +1,47 @@
+use crossterm::event::KeyCode;
+use minifb::Key;
+
+use crate::{
+    display::Display,
+    terminal::{braille, half_block, to_minifb_key},
+};
+
+#[test]
+fn terminal_keys_become_keypad_keys_and_hotkeys() {
+    assert_eq!(to_minifb_key(KeyCode::Char('1')), Some(Key::Key1));
+    assert_eq!(to_minifb_key(KeyCode::Char('v')), Some(Key::V));
+    // shift doesn't change the key
+    assert_eq!(to_minifb_key(KeyCode::Char('Q')), Some(Key::Q));
+    assert_eq!(to_minifb_key(KeyCode::F(4)), Some(Key::NumPad4));
+    assert_eq!(to_minifb_key(KeyCode::Char('5')), None);
+    assert_eq!(to_minifb_key(KeyCode::F(6)), None);
+    assert_eq!(to_minifb_key(KeyCode::Enter), None);
+}
+
+#[test]
+fn half_blocks_show_two_rows_per_line() {
+    let mut display = Display::new();
+    display.set_pixel(3, 4, true);
+    display.set_pixel(5, 7, true);
+    assert_eq!(half_block(&display, 3, 2), (true, false));
+    assert_eq!(half_block(&display, 5, 3), (false, true));
+    assert_eq!(half_block(&display, 3, 3), (false, false));
+    assert_eq!(half_block(&display, 63, 15), (false, false));
+}
+
+#[test]
+fn braille_dots_follow_the_pixels() {
+    let mut display = Display::new();
+    assert_eq!(braille(&display, 0, 0), '\u{2800}');
+    // the left column is dots 1, 2, 3 and 7, the right one 4, 5, 6 and 8
+    display.set_pixel(2, 0, true);
+    display.set_pixel(2, 3, true);
+    display.set_pixel(3, 1, true);
+    assert_eq!(braille(&display, 1, 0), '\u{2851}');
+    display.set_pixel(3, 3, true);
+    assert_eq!(braille(&display, 1, 0), '\u{28d1}');
+    // the pixels below belong to the next line
+    display.set_pixel(2, 4, true);
+    assert_eq!(braille(&display, 1, 1), '\u{2801}');
+    assert_eq!(braille(&display, 0, 0), '\u{2800}');
+}